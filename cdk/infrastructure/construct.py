@@ -23,6 +23,8 @@ class RustFunction(Construct):
             ),
             removal_policy=RemovalPolicy.DESTROY, # For dev/test
             billing_mode=dynamodb.BillingMode.PAY_PER_REQUEST,
+            # Expires message dedup markers (see CurioDbClient::mark_processed)
+            time_to_live_attribute="expires_at",
         )
 
         # GSI: ParentIndex (Query children)
@@ -67,8 +67,9 @@ class RustLambdaStack(Stack):
         consumer_fn = RustFunction(self, "CurioLambda")
         
         # Add SQS Event Source to Rust Lambda
+        # The handler returns batchItemFailures so only failed messages are redelivered.
         consumer_fn.function.add_event_source(
-            lambda_events.SqsEventSource(queue)
+            lambda_events.SqsEventSource(queue, report_batch_item_failures=True)
         )
 
         # 3. Python Producer Lambda (for testing)
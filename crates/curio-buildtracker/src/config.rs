@@ -0,0 +1,92 @@
+//! Typed settings for the buildtracker, loaded once at startup.
+//!
+//! Defaults come from the embedded `config.json`; each field can be overridden by an
+//! environment variable (set per deployment by the CDK stack):
+//!
+//! | Field             | Env override      |
+//! |-------------------|-------------------|
+//! | `table_name`      | `TABLE_NAME`      |
+//! | `artifact_bucket` | `ARTIFACT_BUCKET` |
+//! | `config_source`   | `CONFIG_SOURCE`   |
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+// Embed the config.json file at compile time
+const CONFIG_JSON: &str = include_str!("../config.json");
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BuildTrackerConfig {
+    /// DynamoDB table for records.
+    #[serde(default)]
+    pub table_name: String,
+    /// S3 bucket where build artifacts are written.
+    #[serde(default)]
+    pub artifact_bucket: String,
+    /// Where the `CurioConfig` YAML lives (e.g. `s3://bucket/curio.yaml`).
+    #[serde(default)]
+    pub config_source: Option<String>,
+}
+
+impl BuildTrackerConfig {
+    /// Loads the embedded config with overrides from the process environment.
+    pub fn load() -> Result<Self> {
+        Self::from_json(CONFIG_JSON, |key| std::env::var(key).ok())
+    }
+
+    /// Parses `json` and applies overrides from `env` (a lookup by variable name).
+    pub fn from_json(json: &str, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config: Self = serde_json::from_str(json)?;
+
+        if let Some(v) = env("TABLE_NAME") {
+            config.table_name = v;
+        }
+        if let Some(v) = env("ARTIFACT_BUCKET") {
+            config.artifact_bucket = v;
+        }
+        if let Some(v) = env("CONFIG_SOURCE") {
+            config.config_source = Some(v);
+        }
+
+        if config.table_name.is_empty() {
+            return Err(anyhow!("table_name must be set in config.json or TABLE_NAME"));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_env_overrides_embedded_config() {
+        let json = r#"{"table_name": "FromFile", "artifact_bucket": "file-bucket"}"#;
+
+        let config = BuildTrackerConfig::from_json(json, |_| None).unwrap();
+        assert_eq!(config.table_name, "FromFile");
+        assert_eq!(config.artifact_bucket, "file-bucket");
+        assert_eq!(config.config_source, None);
+
+        let env = HashMap::from([
+            ("TABLE_NAME", "FromEnv"),
+            ("CONFIG_SOURCE", "s3://bucket/curio.yaml"),
+        ]);
+        let config = BuildTrackerConfig::from_json(json, |k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.table_name, "FromEnv");
+        assert_eq!(config.artifact_bucket, "file-bucket");
+        assert_eq!(config.config_source.as_deref(), Some("s3://bucket/curio.yaml"));
+    }
+
+    #[test]
+    fn test_embedded_config_parses() {
+        let config = BuildTrackerConfig::from_json(CONFIG_JSON, |_| None).unwrap();
+        assert!(!config.table_name.is_empty());
+    }
+
+    #[test]
+    fn test_missing_table_name_is_an_error() {
+        assert!(BuildTrackerConfig::from_json("{}", |_| None).is_err());
+    }
+}
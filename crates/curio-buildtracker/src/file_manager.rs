@@ -1,20 +1,75 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use anyhow::{Result, anyhow, Context};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWriteExt};
 use std::fmt::Debug;
 use aws_sdk_s3::Client;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
 // use aws_sdk_s3::primitives::ByteStream; 
-use std::sync::Mutex;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
+/// Typed `get_file` failures (wrapped in `anyhow::Error`) so callers can decide whether
+/// to skip, retry or abort via `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileManagerError {
+    NotFound { uri: String },
+    AccessDenied { uri: String },
+    Throttled { uri: String },
+    Other { uri: String, message: String },
+}
+
+impl std::fmt::Display for FileManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileManagerError::NotFound { uri } => write!(f, "Artifact not found: {}", uri),
+            FileManagerError::AccessDenied { uri } => write!(f, "Access denied to artifact: {}", uri),
+            FileManagerError::Throttled { uri } => write!(f, "Throttled while fetching artifact: {}", uri),
+            FileManagerError::Other { uri, message } => write!(f, "Failed to fetch artifact {}: {}", uri, message),
+        }
+    }
+}
+
+impl std::error::Error for FileManagerError {}
+
+impl FileManagerError {
+    /// Classifies an S3 SDK error by its error code, falling back to the HTTP status.
+    fn from_s3<E>(err: &SdkError<E, HttpResponse>, uri: &str) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+    {
+        let uri = uri.to_string();
+        let status = err.raw_response().map(|r| r.status().as_u16());
+        match (err.code(), status) {
+            (Some("NoSuchKey" | "NotFound" | "NoSuchBucket"), _) | (_, Some(404)) => FileManagerError::NotFound { uri },
+            (Some("AccessDenied" | "Forbidden"), _) | (_, Some(403)) => FileManagerError::AccessDenied { uri },
+            (Some("SlowDown" | "Throttling" | "ThrottlingException" | "RequestLimitExceeded" | "TooManyRequests"), _)
+            | (_, Some(429 | 503)) => FileManagerError::Throttled { uri },
+            _ => FileManagerError::Other { uri, message: aws_sdk_s3::error::DisplayErrorContext(err).to_string() },
+        }
+    }
+}
+
 #[async_trait]
 pub trait FileManager: Send + Sync + Debug {
     /// Request an input file. Returns a local path that is guaranteed to exist.
-    /// Handles downloading if necessary.
+    /// Handles downloading if necessary. Failures to locate or fetch the artifact are
+    /// reported as `FileManagerError`.
     async fn get_file(&self, uri: &str) -> Result<PathBuf>;
 
+    /// Opens an input for sequential reading, for primitives that only stream its bytes
+    /// (hashing, compression). Errors like `get_file`. Managers override this to stream
+    /// remote content directly instead of materializing it locally first.
+    async fn open_read(&self, uri: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        let path = self.get_file(uri).await?;
+        Ok(Box::pin(fs::File::open(path).await?))
+    }
+
     /// Request a local writable path for an output artifact.
     /// The caller should write to this path.
     /// If the URI is not fully specified (e.g. just a filename), the manager decides placement.
@@ -24,11 +79,167 @@ pub trait FileManager: Send + Sync + Debug {
     /// This signals that writing is complete and the file should be persisted/uploaded to its final URI.
     /// Returns the stable artifact URI.
     async fn commit_output(&self, uri: &str, temp_path: &Path) -> Result<String>;
+
+    /// Like `commit_output`, also attaching `metadata` (e.g. source workflow, content hash,
+    /// created-by) to the stored object. Managers without object metadata ignore it.
+    async fn commit_output_with_meta(&self, uri: &str, temp_path: &Path, metadata: HashMap<String, String>) -> Result<String> {
+        let _ = metadata;
+        self.commit_output(uri, temp_path).await
+    }
+
+    /// Like `commit_output`, but only the first writer of `uri` wins. If the artifact
+    /// already exists (e.g. another worker produced the same derived output concurrently),
+    /// it is left untouched and its URI returned.
+    async fn commit_output_exclusive(&self, uri: &str, temp_path: &Path) -> Result<String>;
+
+    /// Returns the stable URI that committing `uri` would produce, if that artifact
+    /// has already been committed. Used to skip re-running idempotent work.
+    async fn find_output(&self, uri: &str) -> Result<Option<String>>;
+
+    /// Stores a second, independent copy of the artifact at `src_uri` under `dest_hint`
+    /// (interpreted like a `commit_output` URI) and returns the new artifact URI.
+    /// Managers override this to copy in place rather than round-trip the content.
+    async fn copy_within(&self, src_uri: &str, dest_hint: &str) -> Result<String> {
+        let src = self.get_file(src_uri).await?;
+        let temp_path = self.prepare_output(dest_hint).await?;
+        fs::copy(&src, &temp_path).await?;
+        self.commit_output(dest_hint, &temp_path).await
+    }
     
     /// Cleans up tracked local files.
     async fn cleanup(&self) -> Result<()>;
 }
 
+/// Decodes an RFC 2397 `data:` URI (`data:[<mediatype>][;base64],<data>`) into its bytes.
+/// Returns `None` if `uri` isn't a data URI.
+fn decode_data_uri(uri: &str) -> Option<Result<Vec<u8>>> {
+    use base64::Engine;
+    let rest = uri.strip_prefix("data:")?;
+    Some((|| {
+        let (header, data) = rest.split_once(',').ok_or_else(|| anyhow!("Malformed data URI: missing ','"))?;
+        let data: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+        if header.ends_with(";base64") {
+            base64::engine::general_purpose::STANDARD.decode(&data).map_err(|e| anyhow!("Malformed data URI: {}", e))
+        } else {
+            Ok(data)
+        }
+    })())
+}
+
+fn uri_is_data(uri: &str) -> bool {
+    uri.starts_with("data:")
+}
+
+/// The `FileManager` a primitive sees through `ExecutionContext`. It forwards to the
+/// underlying manager, except that with `commit` unset (a dry run) outputs are staged as
+/// usual but never persisted: every commit discards the staged file and returns a
+/// synthetic `dryrun://` URI.
+/// `get_file` also accepts `data:` URIs, materializing the inline content to a temp file
+/// so any file input can be given inline.
+#[derive(Debug, Clone, Copy)]
+pub struct ContextFileManager<'a> {
+    inner: &'a dyn FileManager,
+    commit: bool,
+}
+
+impl<'a> ContextFileManager<'a> {
+    pub fn new(inner: &'a dyn FileManager, commit: bool) -> Self {
+        Self { inner, commit }
+    }
+
+    pub fn inner(&self) -> &'a dyn FileManager {
+        self.inner
+    }
+
+    pub fn commits(&self) -> bool {
+        self.commit
+    }
+
+    /// The synthetic URI a dry run reports for an output committed under `uri`.
+    pub fn dry_run_uri(uri: &str) -> String {
+        format!("dryrun://{}", uri)
+    }
+
+    async fn discard(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        let _ = fs::remove_file(temp_path).await;
+        Ok(Self::dry_run_uri(uri))
+    }
+}
+
+// Lets primitives call the manager's methods without importing `FileManager`, as they
+// could when the context held a `&dyn FileManager`.
+impl<'a> std::ops::Deref for ContextFileManager<'a> {
+    type Target = dyn FileManager + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[async_trait]
+impl FileManager for ContextFileManager<'_> {
+    async fn get_file(&self, uri: &str) -> Result<PathBuf> {
+        let Some(data) = decode_data_uri(uri) else {
+            return self.inner.get_file(uri).await;
+        };
+        // A staged output is a tracked temp file, so cleanup() removes it with the rest.
+        let path = self.inner.prepare_output("data_uri").await?;
+        fs::write(&path, data?).await?;
+        Ok(path)
+    }
+
+    async fn open_read(&self, uri: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        match decode_data_uri(uri) {
+            Some(data) => Ok(Box::pin(std::io::Cursor::new(data?))),
+            None => self.inner.open_read(uri).await,
+        }
+    }
+
+    async fn prepare_output(&self, uri: &str) -> Result<PathBuf> {
+        self.inner.prepare_output(uri).await
+    }
+
+    async fn commit_output(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        if !self.commit {
+            return self.discard(uri, temp_path).await;
+        }
+        self.inner.commit_output(uri, temp_path).await
+    }
+
+    async fn commit_output_with_meta(&self, uri: &str, temp_path: &Path, metadata: HashMap<String, String>) -> Result<String> {
+        if !self.commit {
+            return self.discard(uri, temp_path).await;
+        }
+        self.inner.commit_output_with_meta(uri, temp_path, metadata).await
+    }
+
+    async fn commit_output_exclusive(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        if !self.commit {
+            return self.discard(uri, temp_path).await;
+        }
+        self.inner.commit_output_exclusive(uri, temp_path).await
+    }
+
+    async fn find_output(&self, uri: &str) -> Result<Option<String>> {
+        self.inner.find_output(uri).await
+    }
+
+    async fn copy_within(&self, src_uri: &str, dest_hint: &str) -> Result<String> {
+        if !self.commit {
+            return Ok(Self::dry_run_uri(dest_hint));
+        }
+        if uri_is_data(src_uri) {
+            let src = self.get_file(src_uri).await?;
+            return self.inner.commit_output(dest_hint, &src).await;
+        }
+        self.inner.copy_within(src_uri, dest_hint).await
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        self.inner.cleanup().await
+    }
+}
+
 #[derive(Debug)]
 pub struct LocalFileManager {
     pub base_dir: PathBuf,
@@ -43,6 +254,18 @@ impl LocalFileManager {
         }
     }
 
+    /// Copies `temp_path` to a uniquely named sibling of `dest`, ready to be moved into place.
+    async fn stage_beside(&self, dest: &Path, temp_path: &Path) -> Result<PathBuf> {
+        let parent = dest.parent().unwrap_or(&self.base_dir);
+        fs::create_dir_all(parent).await?;
+        let staged = parent.join(format!(".{}.partial", Uuid::new_v4()));
+        if let Err(e) = fs::copy(temp_path, &staged).await {
+            let _ = fs::remove_file(&staged).await;
+            return Err(e.into());
+        }
+        Ok(staged)
+    }
+
     fn track(&self, path: PathBuf) {
         let mut piles = self.temp_files.lock().unwrap();
         piles.push(path);
@@ -92,7 +315,7 @@ impl FileManager for LocalFileManager {
 
         // If not found, error? Or return path if it's expected to exist?
         // get_file expects EXISTENCE.
-        Err(anyhow!("File not found: {}", uri))
+        Err(FileManagerError::NotFound { uri: uri.to_string() }.into())
     }
 
     async fn prepare_output(&self, _uri: &str) -> Result<PathBuf> {
@@ -121,11 +344,9 @@ impl FileManager for LocalFileManager {
         let key = if _uri.is_empty() { Uuid::new_v4().to_string() } else { _uri.to_string() };
         let dest = self.base_dir.join(&key);
         
-        if let Some(parent) = dest.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-        
-        fs::copy(temp_path, &dest).await?;
+        // Copy next to dest, then rename into place so concurrent commits never interleave.
+        let staged = self.stage_beside(&dest, temp_path).await?;
+        fs::rename(&staged, &dest).await?;
         // Note: we don't delete temp_path here, cleanup() will do it.
         // Or we could move (rename) it? 
         // If we rename, track() list contains invalid path.
@@ -136,6 +357,41 @@ impl FileManager for LocalFileManager {
         Ok(format!("file://{}", abs.to_string_lossy()))
     }
 
+    async fn commit_output_exclusive(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        let key = if uri.is_empty() { Uuid::new_v4().to_string() } else { uri.to_string() };
+        let dest = self.base_dir.join(&key);
+
+        // hard_link fails if dest exists, so exactly one writer gets to create it.
+        let staged = self.stage_beside(&dest, temp_path).await?;
+        let linked = fs::hard_link(&staged, &dest).await;
+        let _ = fs::remove_file(&staged).await;
+        match linked {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                tracing::info!("{} already committed by another writer; reusing it", dest.display());
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let abs = dest.canonicalize().unwrap_or(dest);
+        Ok(format!("file://{}", abs.to_string_lossy()))
+    }
+
+    async fn find_output(&self, uri: &str) -> Result<Option<String>> {
+        let dest = self.base_dir.join(uri);
+        if !fs::try_exists(&dest).await? {
+            return Ok(None);
+        }
+        let abs = dest.canonicalize().unwrap_or(dest);
+        Ok(Some(format!("file://{}", abs.to_string_lossy())))
+    }
+
+    async fn copy_within(&self, src_uri: &str, dest_hint: &str) -> Result<String> {
+        // commit_output copies its source into place, so the original is left untouched.
+        let src = self.get_file(src_uri).await?;
+        self.commit_output(dest_hint, &src).await
+    }
+
     async fn cleanup(&self) -> Result<()> {
         let files = self.drain_tracked();
         delete_files(files).await;
@@ -143,24 +399,240 @@ impl FileManager for LocalFileManager {
     }
 }
 
+/// Keeps committed outputs in memory under `memory://<uri>` URIs, for tests that want to
+/// inspect what a primitive produced without touching a bucket or a shared directory.
+/// Staged outputs, and `memory://` inputs asked for through `get_file`, live in a private
+/// temp directory removed on `cleanup` or drop. Other URIs are read as local paths.
+/// Only built for tests, or with the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug)]
+pub struct InMemoryFileManager {
+    temp_dir: PathBuf,
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Default for InMemoryFileManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl InMemoryFileManager {
+    pub fn new() -> Self {
+        Self {
+            temp_dir: std::env::temp_dir().join(format!("curio_mem_{}", Uuid::new_v4())),
+            objects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The committed content of `uri`, as returned by `commit_output`.
+    pub fn contents(&self, uri: &str) -> Option<Vec<u8>> {
+        let key = uri.strip_prefix("memory://")?;
+        self.objects.lock().unwrap().get(key).cloned()
+    }
+
+    /// URIs of every committed output, sorted.
+    pub fn uris(&self) -> Vec<String> {
+        let mut uris: Vec<String> = self.objects.lock().unwrap().keys().map(|k| format!("memory://{}", k)).collect();
+        uris.sort();
+        uris
+    }
+
+    fn object(&self, uri: &str) -> Result<Vec<u8>> {
+        self.contents(uri).ok_or_else(|| FileManagerError::NotFound { uri: uri.to_string() }.into())
+    }
+
+    async fn temp_path(&self) -> Result<PathBuf> {
+        fs::create_dir_all(&self.temp_dir).await?;
+        Ok(self.temp_dir.join(Uuid::new_v4().to_string()))
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Drop for InMemoryFileManager {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.temp_dir);
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+#[async_trait]
+impl FileManager for InMemoryFileManager {
+    async fn get_file(&self, uri: &str) -> Result<PathBuf> {
+        if uri.starts_with("memory://") {
+            let content = self.object(uri)?;
+            let path = self.temp_path().await?;
+            fs::write(&path, content).await?;
+            return Ok(path);
+        }
+        let path = PathBuf::from(uri.strip_prefix("file://").unwrap_or(uri));
+        if fs::try_exists(&path).await? {
+            Ok(path)
+        } else {
+            Err(FileManagerError::NotFound { uri: uri.to_string() }.into())
+        }
+    }
+
+    async fn open_read(&self, uri: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        if uri.starts_with("memory://") {
+            return Ok(Box::pin(std::io::Cursor::new(self.object(uri)?)));
+        }
+        Ok(Box::pin(fs::File::open(self.get_file(uri).await?).await?))
+    }
+
+    async fn prepare_output(&self, _uri: &str) -> Result<PathBuf> {
+        self.temp_path().await
+    }
+
+    async fn commit_output(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        let key = if uri.is_empty() { Uuid::new_v4().to_string() } else { uri.to_string() };
+        let content = fs::read(temp_path).await?;
+        let _ = fs::remove_file(temp_path).await;
+        self.objects.lock().unwrap().insert(key.clone(), content);
+        Ok(format!("memory://{}", key))
+    }
+
+    async fn commit_output_exclusive(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        let key = if uri.is_empty() { Uuid::new_v4().to_string() } else { uri.to_string() };
+        let content = fs::read(temp_path).await?;
+        let _ = fs::remove_file(temp_path).await;
+        self.objects.lock().unwrap().entry(key.clone()).or_insert(content);
+        Ok(format!("memory://{}", key))
+    }
+
+    async fn find_output(&self, uri: &str) -> Result<Option<String>> {
+        Ok(self.objects.lock().unwrap().contains_key(uri).then(|| format!("memory://{}", uri)))
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        if fs::try_exists(&self.temp_dir).await? {
+            fs::remove_dir_all(&self.temp_dir).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds an S3 client from the ambient AWS config.
+/// If `S3_ENDPOINT` is set (e.g. moto/minio for local testing), the client targets it
+/// with path-style addressing, mirroring `DYNAMODB_ENDPOINT` in `curio-db`.
+pub async fn s3_client_from_env() -> Client {
+    s3_client_with(None, None).await
+}
+
+/// Like `s3_client_from_env`, with an explicit region and/or endpoint taking precedence
+/// over the environment. Any custom endpoint (minio, moto) uses path-style addressing.
+pub async fn s3_client_with(region: Option<&str>, endpoint: Option<&str>) -> Client {
+    let config = aws_config::load_from_env().await;
+    let mut builder = aws_sdk_s3::config::Builder::from(&config);
+    if let Some(region) = region {
+        builder = builder.region(aws_sdk_s3::config::Region::new(region.to_string()));
+    }
+    if let Some(endpoint) = endpoint.map(str::to_string).or_else(|| std::env::var("S3_ENDPOINT").ok()) {
+        tracing::info!("Using custom S3 endpoint: {}", endpoint);
+        builder = builder.endpoint_url(endpoint).force_path_style(true);
+    }
+    Client::from_conf(builder.build())
+}
+
+/// Characters escaped in a `copy_object` copy source; `/` separates key segments.
+const COPY_SOURCE_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'/').remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// Shared outcome of one S3 download.
+type Download = OnceCell<Result<PathBuf, FileManagerError>>;
+
 #[derive(Debug)]
 pub struct S3FileManager {
     client: Client,
     pub bucket: String,
+    /// Namespace prepended to keys in `bucket`, so pipelines sharing a bucket don't collide.
+    pub prefix: Option<String>,
     pub cache_dir: PathBuf,
     temp_files: Mutex<Vec<PathBuf>>,
+    /// Downloads in progress, keyed by cache path, so concurrent `get_file` calls for the
+    /// same object share one download.
+    in_flight: Mutex<HashMap<PathBuf, Arc<Download>>>,
 }
 
 impl S3FileManager {
-    pub fn new(client: Client, bucket: String) -> Self {
+    pub fn new(client: Client, bucket: String, prefix: Option<String>) -> Self {
         Self {
             client,
             bucket,
+            prefix: prefix.map(|p| p.trim_matches('/').to_string()).filter(|p| !p.is_empty()),
             cache_dir: std::env::temp_dir().join("curio_s3_cache"),
             temp_files: Mutex::new(Vec::new()),
+            in_flight: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Creates a manager using a client built by `s3_client_from_env`.
+    pub async fn from_env(bucket: String, prefix: Option<String>) -> Self {
+        Self::new(s3_client_from_env().await, bucket, prefix)
+    }
+
+    /// Splits an output URI into (bucket, key): a full `s3://` URI, or a key in our bucket.
+    /// Keys in our bucket are placed under `prefix`, unless they already include it.
+    fn resolve_key(&self, uri: &str) -> Result<(String, String)> {
+        let (bucket, key) = if uri.starts_with("s3://") {
+             let without_scheme = &uri[5..];
+             let parts: Vec<&str> = without_scheme.splitn(2, '/').collect();
+             if parts.len() != 2 {
+                  return Err(anyhow!("Invalid S3 URI format: {}", uri));
+             }
+             (parts[0].to_string(), parts[1].to_string())
+        } else {
+             let key = uri.trim_start_matches('/');
+             let key = match &self.prefix {
+                 Some(prefix) if !key.starts_with(&format!("{}/", prefix)) => format!("{}/{}", prefix, key),
+                 _ => key.to_string(),
+             };
+             (self.bucket.clone(), key)
+        };
+        Ok((bucket, key.trim_start_matches('/').to_string()))
+    }
+
+    /// Where `get_file` keeps its copy of `bucket`/`key`.
+    fn cache_path(&self, bucket: &str, key: &str) -> PathBuf {
+        let filename = format!("{:x}", md5::compute(format!("s3://{}/{}", bucket, key)));
+        let ext = Path::new(key).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+        let final_name = if !ext.is_empty() { format!("{}.{}", filename, ext) } else { filename };
+        self.cache_dir.join(bucket).join(final_name)
+    }
+
+    /// Downloads `bucket`/`key` to `dest_path`, via a sibling partial file that is renamed
+    /// into place so readers never see a half-written cache entry.
+    async fn download(&self, bucket: &str, key: &str, uri: &str, dest_path: &Path) -> Result<PathBuf, FileManagerError> {
+        let other = |e: &dyn std::fmt::Display| FileManagerError::Other { uri: uri.to_string(), message: e.to_string() };
+
+        if let Some(parent) = dest_path.parent() {
+             fs::create_dir_all(parent).await.map_err(|e| other(&e))?;
+        }
+
+        let mut resp = self.client.get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| FileManagerError::from_s3(&e, uri))?;
+
+        let partial = dest_path.with_file_name(format!("{}.{}.partial", dest_path.file_name().unwrap_or_default().to_string_lossy(), Uuid::new_v4()));
+        let written = async {
+            let mut file = fs::File::create(&partial).await.map_err(|e| other(&e))?;
+            while let Some(bytes) = resp.body.try_next().await.map_err(|e| other(&e))? {
+                 file.write_all(&bytes).await.map_err(|e| other(&e))?;
+            }
+            file.flush().await.map_err(|e| other(&e))?;
+            fs::rename(&partial, dest_path).await.map_err(|e| other(&e))
+        }.await;
+        if written.is_err() {
+            let _ = fs::remove_file(&partial).await;
+        }
+        written.map(|_| dest_path.to_path_buf())
+    }
+
     fn track(&self, path: PathBuf) {
         let mut piles = self.temp_files.lock().unwrap();
         piles.push(path);
@@ -187,26 +659,12 @@ impl FileManager for S3FileManager {
              return Ok(PathBuf::from(uri.strip_prefix("file://").unwrap()));
         }
 
-        // Parse S3 URI
-        if !uri.starts_with("s3://") {
-             return Err(anyhow!("Invalid S3 URI: {}", uri));
-        }
-        
-        let without_scheme = &uri[5..];
-        let parts: Vec<&str> = without_scheme.splitn(2, '/').collect();
-        if parts.len() != 2 {
-             return Err(anyhow!("Invalid S3 URI format: {}", uri));
-        }
-        let bucket = parts[0];
-        let key = parts[1];
+        // Parse S3 URI; a bare key refers to our bucket (under our prefix)
+        let (bucket, key) = self.resolve_key(uri)?;
+        let uri = &format!("s3://{}/{}", bucket, key);
         
         // Check cache
-        let hash = md5::compute(uri);
-        let filename = format!("{:x}", hash);
-        let ext = Path::new(key).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
-        let final_name = if !ext.is_empty() { format!("{}.{}", filename, ext) } else { filename };
-
-        let dest_path = self.cache_dir.join(&bucket).join(final_name);
+        let dest_path = self.cache_path(&bucket, &key);
 
         self.track(dest_path.clone());
 
@@ -214,24 +672,37 @@ impl FileManager for S3FileManager {
              return Ok(dest_path);
         }
 
-        if let Some(parent) = dest_path.parent() {
-             fs::create_dir_all(parent).await?;
+        // Single-flight: the first caller downloads, concurrent callers await its result.
+        let cell = self.in_flight.lock().unwrap().entry(dest_path.clone()).or_default().clone();
+        let result = cell.get_or_init(|| self.download(&bucket, &key, uri, &dest_path)).await.clone();
+        {
+            // Forget the finished download so a failed one can be retried later.
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight.get(&dest_path).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                in_flight.remove(&dest_path);
+            }
         }
+        Ok(result?)
+    }
 
-        let mut resp = self.client.get_object()
-            .bucket(bucket)
-            .key(key)
+    /// Streams the `get_object` body without writing a cache file; an object `get_file`
+    /// already downloaded is read from the cache instead.
+    async fn open_read(&self, uri: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        if let Some(path) = uri.strip_prefix("file://") {
+            return Ok(Box::pin(fs::File::open(path).await?));
+        }
+        let (bucket, key) = self.resolve_key(uri)?;
+        let cached = self.cache_path(&bucket, &key);
+        if cached.exists() {
+            return Ok(Box::pin(fs::File::open(cached).await?));
+        }
+        let resp = self.client.get_object()
+            .bucket(&bucket)
+            .key(&key)
             .send()
             .await
-            .context(format!("Failed to get object {} from {}", key, bucket))?;
-        
-        let mut file = fs::File::create(&dest_path).await?;
-        
-        while let Some(bytes) = resp.body.try_next().await? {
-             file.write_all(&bytes).await?;
-        }
-
-        Ok(dest_path)
+            .map_err(|e| FileManagerError::from_s3(&e, &format!("s3://{}/{}", bucket, key)))?;
+        Ok(Box::pin(resp.body.into_async_read()))
     }
 
     async fn prepare_output(&self, uri: &str) -> Result<PathBuf> {
@@ -239,11 +710,15 @@ impl FileManager for S3FileManager {
          let temp_dir = std::env::temp_dir().join("curio_s3_temp");
          fs::create_dir_all(&temp_dir).await?;
          
-         // Use uri as a hint for name/ext?
-         let filename = if uri.is_empty() {
+         // Always unique, so concurrent outputs with the same hint never share a temp file;
+         // the sanitized hint is kept as a suffix for readability (and its extension).
+         let hint: String = uri.chars()
+             .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+             .collect();
+         let filename = if hint.is_empty() {
              Uuid::new_v4().to_string()
          } else {
-             uri.replace("/", "_") // rudimentary sanitization
+             format!("{}_{}", Uuid::new_v4(), hint)
          };
          
          let temp_path = temp_dir.join(filename);
@@ -257,18 +732,13 @@ impl FileManager for S3FileManager {
         // uri is expected to be a KEY suffix or full s3 path?
         // Let's assume it's a relative KEY for our bucket unless it starts with s3://
         
-        let (bucket, key) = if uri.starts_with("s3://") {
-             let without_scheme = &uri[5..];
-             let parts: Vec<&str> = without_scheme.splitn(2, '/').collect();
-             if parts.len() != 2 {
-                  return Err(anyhow!("Invalid S3 URI format: {}", uri));
-             }
-             (parts[0].to_string(), parts[1].to_string())
-        } else {
-             (self.bucket.clone(), uri.to_string())
-        };
-        
-        let clean_key = key.trim_start_matches('/');
+        self.commit_output_with_meta(uri, temp_path, HashMap::new()).await
+    }
+
+    /// Stored as S3 user metadata (`x-amz-meta-*`), returned by `head_object`.
+    async fn commit_output_with_meta(&self, uri: &str, temp_path: &Path, metadata: HashMap<String, String>) -> Result<String> {
+        let (bucket, key) = self.resolve_key(uri)?;
+        let clean_key = key.as_str();
         
         let body = aws_sdk_s3::primitives::ByteStream::from_path(temp_path).await?;
         
@@ -276,6 +746,7 @@ impl FileManager for S3FileManager {
             .bucket(&bucket)
             .key(clean_key)
             .body(body)
+            .set_metadata(Some(metadata).filter(|m| !m.is_empty()))
             .send()
             .await
             .context(format!("Failed to put object {} to {}", clean_key, bucket))?;
@@ -283,6 +754,52 @@ impl FileManager for S3FileManager {
         Ok(format!("s3://{}/{}", bucket, clean_key))
     }
 
+    async fn commit_output_exclusive(&self, uri: &str, temp_path: &Path) -> Result<String> {
+        let (bucket, key) = self.resolve_key(uri)?;
+        let body = aws_sdk_s3::primitives::ByteStream::from_path(temp_path).await?;
+
+        // Conditional write: S3 rejects the put with 412 (or 409 if a racing write is
+        // still in progress) when the key already exists.
+        match self.client.put_object().bucket(&bucket).key(&key).body(body).if_none_match("*").send().await {
+            Ok(_) => {}
+            Err(e) if matches!(e.raw_response().map(|r| r.status().as_u16()), Some(409 | 412)) => {
+                tracing::info!("s3://{}/{} already committed by another writer; reusing it", bucket, key);
+            }
+            Err(e) => return Err(FileManagerError::from_s3(&e, &format!("s3://{}/{}", bucket, key)).into()),
+        }
+        Ok(format!("s3://{}/{}", bucket, key))
+    }
+
+    async fn find_output(&self, uri: &str) -> Result<Option<String>> {
+        let (bucket, key) = self.resolve_key(uri)?;
+        match self.client.head_object().bucket(&bucket).key(&key).send().await {
+            Ok(_) => Ok(Some(format!("s3://{}/{}", bucket, key))),
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_not_found() {
+                    Ok(None)
+                } else {
+                    Err(anyhow::Error::new(e).context(format!("Failed to check object {} in {}", key, bucket)))
+                }
+            }
+        }
+    }
+
+    /// Server-side `copy_object`, so the content never passes through this process.
+    async fn copy_within(&self, src_uri: &str, dest_hint: &str) -> Result<String> {
+        let (src_bucket, src_key) = self.resolve_key(src_uri)?;
+        let (bucket, key) = self.resolve_key(dest_hint)?;
+        let copy_source = format!("{}/{}", src_bucket, percent_encoding::utf8_percent_encode(&src_key, COPY_SOURCE_ENCODE_SET));
+        self.client.copy_object()
+            .bucket(&bucket)
+            .key(&key)
+            .copy_source(copy_source)
+            .send()
+            .await
+            .map_err(|e| FileManagerError::from_s3(&e, &format!("s3://{}/{}", src_bucket, src_key)))?;
+        Ok(format!("s3://{}/{}", bucket, key))
+    }
+
     async fn cleanup(&self) -> Result<()> {
         let files = self.drain_tracked();
         delete_files(files).await;
@@ -1,52 +1,89 @@
 mod primitives;
 pub mod file_manager;
+mod config;
 
 use lambda_runtime::{service_fn, Error, LambdaEvent};
-use aws_lambda_events::event::sqs::SqsEvent;
-use serde::{Deserialize, Serialize};
+use aws_lambda_events::event::sqs::{BatchItemFailure, SqsBatchResponse, SqsEvent, SqsMessage};
+use async_trait::async_trait;
 use curio_db::CurioDbClient;
-use std::env;
+use config::BuildTrackerConfig;
+use std::time::Duration;
 
-// Embed the config.json file at compile time
-const CONFIG_JSON: &str = include_str!("../config.json");
+/// How long a processed message id is remembered. Matches the maximum SQS retention,
+/// so any redelivery of a message is recognised.
+const DEDUP_TTL: Duration = Duration::from_secs(14 * 24 * 60 * 60);
 
-#[derive(Deserialize, Serialize)]
-struct Config {
-    message: String,
-    database_url: String,
+/// Persistence used by the record loop; abstracted so it can be exercised without DynamoDB.
+#[async_trait]
+trait RecordStore: Send + Sync {
+    async fn save_record(&self, id: &str, data: &str) -> anyhow::Result<()>;
+    async fn is_processed(&self, message_id: &str) -> anyhow::Result<bool>;
+    async fn mark_processed(&self, message_id: &str) -> anyhow::Result<()>;
 }
 
-#[derive(Serialize)]
-struct Response {
-    msg: String,
+#[async_trait]
+impl RecordStore for CurioDbClient {
+    async fn save_record(&self, id: &str, data: &str) -> anyhow::Result<()> {
+        CurioDbClient::save_record(self, id, data).await?;
+        Ok(())
+    }
+
+    async fn is_processed(&self, message_id: &str) -> anyhow::Result<bool> {
+        Ok(CurioDbClient::is_processed(self, message_id).await?)
+    }
+
+    async fn mark_processed(&self, message_id: &str) -> anyhow::Result<()> {
+        CurioDbClient::mark_processed(self, message_id, DEDUP_TTL).await?;
+        Ok(())
+    }
 }
 
-async fn function_handler(event: LambdaEvent<SqsEvent>) -> Result<Response, Error> {
-    // Parse the embedded config
-    let config: Config = serde_json::from_str(CONFIG_JSON)?;
-    
-    // Initialize DynamoDB Client
-    let table_name = env::var("TABLE_NAME").expect("TABLE_NAME must be set");
-    let db_client = CurioDbClient::new(table_name).await;
-    
-    // Process records
-    for record in event.payload.records {
-        if let Some(body) = record.body {
-            tracing::info!("Received message: {}", body);
-            tracing::info!("Config Message: {}", config.message);
-            
-            // Save to DynamoDB
-            // Use messageId as ID, body as data
-            if let Some(id) = record.message_id {
-                let _ = db_client.save_record(&id, &body).await;
-                tracing::info!("Saved record {} to DynamoDB", id);
+/// Saves each record, returning the message ids that failed so SQS redelivers only those.
+/// Messages already processed (SQS is at-least-once) are skipped.
+async fn process_records(store: &dyn RecordStore, records: Vec<SqsMessage>) -> SqsBatchResponse {
+    let mut response = SqsBatchResponse::default();
+
+    for record in records {
+        // Use messageId as ID, body as data
+        let (Some(id), Some(body)) = (record.message_id, record.body) else {
+            continue;
+        };
+        tracing::info!("Received message: {}", body);
+
+        match store.is_processed(&id).await {
+            Ok(true) => {
+                tracing::info!("Skipping already processed message {}", id);
+                continue;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to check dedup marker for {}: {:?}", id, e);
+                response.batch_item_failures.push(BatchItemFailure { item_identifier: id });
+                continue;
             }
         }
+
+        if let Err(e) = store.save_record(&id, &body).await {
+            tracing::error!("Failed to save record {}: {:?}", id, e);
+            response.batch_item_failures.push(BatchItemFailure { item_identifier: id });
+            continue;
+        }
+        tracing::info!("Saved record {} to DynamoDB", id);
+
+        // The save already succeeded; a missing marker only means a redelivery would redo it.
+        if let Err(e) = store.mark_processed(&id).await {
+            tracing::warn!("Failed to record message {} as processed: {:?}", id, e);
+        }
     }
 
-    Ok(Response {
-        msg: "Processed SQS messages".to_string(),
-    })
+    response
+}
+
+async fn function_handler(config: &BuildTrackerConfig, event: LambdaEvent<SqsEvent>) -> Result<SqsBatchResponse, Error> {
+    // Initialize DynamoDB Client
+    let db_client = CurioDbClient::new(config.table_name.clone()).await;
+
+    Ok(process_records(&db_client, event.payload.records).await)
 }
 
 #[tokio::main]
@@ -57,7 +94,76 @@ async fn main() -> Result<(), Error> {
         .without_time()
         .init();
 
-    let func = service_fn(function_handler);
+    let config = BuildTrackerConfig::load()?;
+    tracing::info!("Loaded config: {:?}", config);
+
+    let config = &config;
+    let func = service_fn(move |event| function_handler(config, event));
     lambda_runtime::run(func).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Fails to save any id in `failing`, recording the rest.
+    #[derive(Default)]
+    struct MockStore {
+        failing: Vec<String>,
+        saved: Mutex<Vec<String>>,
+        processed: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl RecordStore for MockStore {
+        async fn save_record(&self, id: &str, _data: &str) -> anyhow::Result<()> {
+            if self.failing.iter().any(|f| f == id) {
+                return Err(anyhow::anyhow!("simulated DynamoDB failure"));
+            }
+            self.saved.lock().unwrap().push(id.to_string());
+            Ok(())
+        }
+
+        async fn is_processed(&self, message_id: &str) -> anyhow::Result<bool> {
+            Ok(self.processed.lock().unwrap().iter().any(|p| p == message_id))
+        }
+
+        async fn mark_processed(&self, message_id: &str) -> anyhow::Result<()> {
+            self.processed.lock().unwrap().push(message_id.to_string());
+            Ok(())
+        }
+    }
+
+    fn message(id: &str) -> SqsMessage {
+        SqsMessage {
+            message_id: Some(id.to_string()),
+            body: Some(format!("body of {}", id)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_batch_failure() {
+        let store = MockStore { failing: vec!["msg-2".to_string()], ..Default::default() };
+
+        let response = process_records(&store, vec![message("msg-1"), message("msg-2")]).await;
+
+        let failed: Vec<&str> = response.batch_item_failures.iter().map(|f| f.item_identifier.as_str()).collect();
+        assert_eq!(failed, vec!["msg-2"]);
+        assert_eq!(*store.saved.lock().unwrap(), vec!["msg-1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_message_is_skipped() {
+        let store = MockStore::default();
+
+        let first = process_records(&store, vec![message("msg-1")]).await;
+        let second = process_records(&store, vec![message("msg-1")]).await;
+
+        assert!(first.batch_item_failures.is_empty());
+        assert!(second.batch_item_failures.is_empty());
+        assert_eq!(*store.saved.lock().unwrap(), vec!["msg-1".to_string()]);
+    }
+}
@@ -1,9 +1,13 @@
-use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext};
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, PrimitiveError, optional_value, defaulted_value, optional_flag, prepare_inputs, is_plain_filename, read_input_text};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use anyhow::{Result, anyhow};
+use futures::{StreamExt, TryStreamExt};
 use tokio::sync::mpsc;
 
+/// How many inputs `MergeJson` and `Concatenate` fetch at once unless `fetch_concurrency` says otherwise.
+const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+
 #[derive(Debug)]
 pub struct MergeJson;
 
@@ -12,6 +16,10 @@ impl Primitive for MergeJson {
     fn name(&self) -> &str {
         "MergeJson"
     }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
     
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
@@ -21,6 +29,31 @@ impl Primitive for MergeJson {
                 mime_type: "application/json".to_string(),
                 min_count: 1,
                 max_count: None, // Unlimited
+                default: None,
+            },
+            InputDef {
+                name: "scalar_conflict".to_string(),
+                description: "How to resolve a key set to different scalars: 'last' (default), 'first' or 'error'".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("last".to_string()),
+            },
+            InputDef {
+                name: "array_strategy".to_string(),
+                description: "How to merge two arrays at the same key: 'replace' (default, resolved like a scalar), 'concat' or 'union' (concat, dropping repeated elements)".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("replace".to_string()),
+            },
+            InputDef {
+                name: "fetch_concurrency".to_string(),
+                description: format!("How many inputs to fetch at once (default {})", DEFAULT_FETCH_CONCURRENCY),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_FETCH_CONCURRENCY.to_string()),
             }
         ]
     }
@@ -41,19 +74,17 @@ impl Primitive for MergeJson {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let strategy = ScalarConflict::from_inputs(&inputs)?;
+        let arrays = ArrayStrategy::from_inputs(&inputs)?;
+        let concurrency = fetch_concurrency(&inputs)?;
         let json_list = inputs.remove("inputs").ok_or_else(|| anyhow!("Missing inputs"))?;
         let mut merged = serde_json::Value::Object(serde_json::Map::new());
 
-        for input in json_list {
-            let content = match input {
-                PrimitiveInput::Value(s) => s,
-                PrimitiveInput::ArtifactPath(p) => {
-                    let local = context.file_manager.get_file(&p).await?;
-                    tokio::fs::read_to_string(local).await?
-                },
-            };
+        let contents = fetch_in_order(json_list.iter().collect(), concurrency, |input| read_input_text(input, &context)).await?;
+        for content in contents {
             let v: serde_json::Value = serde_json::from_str(&content).unwrap_or(serde_json::json!({}));
-            merge(&mut merged, v);
+            merge(&mut merged, v, strategy, arrays, "")?;
         }
 
         let result_str = serde_json::to_string(&merged)?;
@@ -70,15 +101,189 @@ impl Primitive for MergeJson {
     }
 }
 
-fn merge(a: &mut serde_json::Value, b: serde_json::Value) {
+#[derive(Debug)]
+pub struct MergeYaml;
+
+#[async_trait]
+impl Primitive for MergeYaml {
+    fn name(&self) -> &str {
+        "MergeYaml"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "inputs".to_string(),
+                description: "List of YAML documents to merge".to_string(),
+                mime_type: "application/yaml".to_string(),
+                min_count: 1,
+                max_count: None,
+                default: None,
+            },
+            InputDef {
+                name: "scalar_conflict".to_string(),
+                description: "How to resolve a key set to different scalars: 'last' (default), 'first' or 'error'".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("last".to_string()),
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "merged".to_string(),
+                description: "Merged YAML".to_string(),
+                mime_type: "application/yaml".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let strategy = ScalarConflict::from_inputs(&inputs)?;
+        let yaml_list = inputs.remove("inputs").ok_or_else(|| anyhow!("Missing inputs"))?;
+        let mut merged = serde_json::Value::Object(serde_json::Map::new());
+
+        for (i, input) in yaml_list.into_iter().enumerate() {
+            let content = read_input_text(&input, &context).await?;
+            // Aliases resolve while parsing; `<<` merge keys need an explicit pass.
+            let mut doc: serde_yaml::Value = serde_yaml::from_str(&content)
+                .map_err(|e| anyhow!("Input {} is not valid YAML: {}", i, e))?;
+            doc.apply_merge()?;
+            if doc.is_null() {
+                continue; // Empty document
+            }
+            let v: serde_json::Value = serde_yaml::from_value(doc)
+                .map_err(|e| anyhow!("Input {} cannot be merged: {}", i, e))?;
+            merge(&mut merged, v, strategy, ArrayStrategy::Replace, "")?;
+        }
+
+        let temp_path = context.file_manager.prepare_output("merged.yaml").await?;
+        tokio::fs::write(&temp_path, serde_yaml::to_string(&merged)?).await?;
+        let artifact_uri = context.file_manager.commit_output("merged_result.yaml", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "merged".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// Parses the `fetch_concurrency` input.
+fn fetch_concurrency(inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<usize> {
+    let v = defaulted_value(inputs, "fetch_concurrency")?;
+    v.trim().parse::<usize>().ok().filter(|n| *n > 0)
+        .ok_or_else(|| anyhow!("Input 'fetch_concurrency' must be a positive integer, got '{}'", v))
+}
+
+/// Runs `fetch` over `items` with at most `limit` in flight, returning the results in the
+/// order of `items` however the fetches finish.
+async fn fetch_in_order<I, T, F, Fut>(items: Vec<I>, limit: usize, fetch: F) -> Result<Vec<T>>
+where
+    F: Fn(I) -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut results: Vec<(usize, T)> = futures::stream::iter(items.into_iter().enumerate())
+        .map(|(i, item)| {
+            let fetched = fetch(item);
+            async move { fetched.await.map(|t| (i, t)) }
+        })
+        .buffer_unordered(limit)
+        .try_collect()
+        .await?;
+    results.sort_by_key(|(i, _)| *i);
+    Ok(results.into_iter().map(|(_, t)| t).collect())
+}
+
+/// Resolution for a key that two inputs set to different non-object values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarConflict {
+    Last,
+    First,
+    Error,
+}
+
+impl ScalarConflict {
+    fn from_inputs(inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<Self> {
+        match defaulted_value(inputs, "scalar_conflict")?.as_str() {
+            "last" => Ok(ScalarConflict::Last),
+            "first" => Ok(ScalarConflict::First),
+            "error" => Ok(ScalarConflict::Error),
+            other => Err(anyhow!("Input 'scalar_conflict' must be 'last', 'first' or 'error', got '{}'", other)),
+        }
+    }
+}
+
+/// How two arrays at the same key are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArrayStrategy {
+    /// Treated like any other pair of differing values, per `ScalarConflict`.
+    Replace,
+    /// Elements of the later array are appended.
+    Concat,
+    /// Like `Concat`, keeping only the first of any elements that serialize identically,
+    /// including repeats within a single array.
+    Union,
+}
+
+impl ArrayStrategy {
+    fn from_inputs(inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<Self> {
+        match defaulted_value(inputs, "array_strategy")?.as_str() {
+            "replace" => Ok(ArrayStrategy::Replace),
+            "concat" => Ok(ArrayStrategy::Concat),
+            "union" => Ok(ArrayStrategy::Union),
+            other => Err(anyhow!("Input 'array_strategy' must be 'replace', 'concat' or 'union', got '{}'", other)),
+        }
+    }
+}
+
+/// Deep-merges `b` into `a`. `path` is the dotted key path of `a`, used in conflict errors.
+fn merge(a: &mut serde_json::Value, b: serde_json::Value, strategy: ScalarConflict, arrays: ArrayStrategy, path: &str) -> Result<()> {
     match (a, b) {
         (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
             for (alt_key, v) in b {
-                merge(a.entry(alt_key).or_insert(serde_json::Value::Null), v);
+                let child = if path.is_empty() { alt_key.clone() } else { format!("{}.{}", path, alt_key) };
+                merge(a.entry(alt_key).or_insert(serde_json::Value::Null), v, strategy, arrays, &child)?;
+            }
+        }
+        (serde_json::Value::Array(a), serde_json::Value::Array(b)) if arrays != ArrayStrategy::Replace => {
+            a.extend(b);
+            if arrays == ArrayStrategy::Union {
+                let mut seen = std::collections::HashSet::new();
+                a.retain(|v| seen.insert(v.to_string()));
             }
         }
-        (a, b) => *a = b,
+        // Nothing set yet: under `union`, fill an empty container so `b`'s own repeats are dropped too
+        (a, b) if a.is_null() && arrays == ArrayStrategy::Union && (b.is_array() || b.is_object()) => {
+            *a = if b.is_array() { serde_json::Value::Array(Vec::new()) } else { serde_json::Value::Object(serde_json::Map::new()) };
+            merge(a, b, strategy, arrays, path)?;
+        }
+        // Nothing set yet, or both sides agree
+        (a, b) if a.is_null() || *a == b => *a = b,
+        (a, b) => match strategy {
+            ScalarConflict::Last => *a = b,
+            ScalarConflict::First => {}
+            ScalarConflict::Error => {
+                let path = if path.is_empty() { "<root>".to_string() } else { path.to_string() };
+                return Err(PrimitiveError::MergeConflict { path }.into());
+            }
+        },
     }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -90,6 +295,10 @@ impl Primitive for Concatenate {
         "Concatenate"
     }
 
+    fn idempotent(&self) -> bool {
+        true
+    }
+
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
             InputDef {
@@ -98,6 +307,39 @@ impl Primitive for Concatenate {
                 mime_type: "*/*".to_string(),
                 min_count: 1,
                 max_count: None,
+                default: None,
+            },
+            InputDef {
+                name: "separator".to_string(),
+                description: "Written between inputs, e.g. a newline. Default: none (binary concat)".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "trim_trailing_newline".to_string(),
+                description: "Strip a trailing newline from each input before joining".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "output_name".to_string(),
+                description: "Filename (and so extension) to commit the result under. Default: concatenated_result.bin".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("concatenated_result.bin".to_string()),
+            },
+            InputDef {
+                name: "fetch_concurrency".to_string(),
+                description: format!("How many inputs to fetch at once (default {})", DEFAULT_FETCH_CONCURRENCY),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_FETCH_CONCURRENCY.to_string()),
             }
         ]
     }
@@ -118,27 +360,44 @@ impl Primitive for Concatenate {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         let input_list = inputs.remove("inputs").ok_or_else(|| anyhow!("Missing inputs"))?;
+        let concurrency = fetch_concurrency(&inputs)?;
+        let separator = optional_value(&inputs, "separator")?.unwrap_or_default();
+        let trim_trailing_newline = optional_flag(&inputs, "trim_trailing_newline", false)?;
+        let output_name = defaulted_value(&inputs, "output_name")?.trim().to_string();
+        if !is_plain_filename(&output_name) {
+            return Err(anyhow!("Input 'output_name' must be a plain filename, got '{}'", output_name));
+        }
         
-        let temp_path = context.file_manager.prepare_output("concat.bin").await?;
+        let uris = input_list.into_iter()
+            .map(|input| match input {
+                PrimitiveInput::ArtifactPath(p) => Ok(p),
+                PrimitiveInput::Value(_) => Err(anyhow!("Concatenate expects artifact paths")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Fetch concurrently, then write in input order
+        let locals = fetch_in_order(uris, concurrency, |uri| async move { context.file_manager.get_file(&uri).await }).await?;
+
+        let temp_path = context.file_manager.prepare_output(&output_name).await?;
         let mut out_file = tokio::fs::File::create(&temp_path).await?;
         use tokio::io::AsyncWriteExt; // ensure write_all is available
-        use tokio::io::AsyncReadExt;
-
-        for input in input_list {
-             let path_str = match input {
-                 PrimitiveInput::ArtifactPath(p) => p,
-                 PrimitiveInput::Value(_) => return Err(anyhow!("Concatenate expects artifact paths")),
-             };
-             let local = context.file_manager.get_file(&path_str).await?;
+
+        for (i, local) in locals.into_iter().enumerate() {
+             if i > 0 && !separator.is_empty() {
+                 out_file.write_all(separator.as_bytes()).await?;
+             }
              let mut f = tokio::fs::File::open(local).await?;
-             let mut buf = Vec::new();
-             f.read_to_end(&mut buf).await?;
-             out_file.write_all(&buf).await?;
+             if trim_trailing_newline {
+                 copy_trimming_newline(&mut f, &mut out_file).await?;
+             } else {
+                 tokio::io::copy(&mut f, &mut out_file).await?;
+             }
         }
+        out_file.flush().await?;
         
         // Commit
-        let artifact_uri = context.file_manager.commit_output("concatenated_result.bin", &temp_path).await?;
+        let artifact_uri = context.file_manager.commit_output(&output_name, &temp_path).await?;
 
         Ok(vec![
             PrimitiveOutput {
@@ -148,3 +407,34 @@ impl Primitive for Concatenate {
         ])
     }
 }
+
+/// Streams `reader` into `writer`, dropping a single trailing "\n" or "\r\n".
+/// Newline bytes at the end of each chunk are held back until more data proves they aren't trailing.
+async fn copy_trimming_newline<R, W>(reader: &mut R, writer: &mut W) -> Result<()>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let mut data = std::mem::take(&mut pending);
+        data.extend_from_slice(&buf[..n]);
+
+        let held = if data.ends_with(b"\r\n") { 2 } else if data.ends_with(b"\n") || data.ends_with(b"\r") { 1 } else { 0 };
+        let split = data.len() - held;
+        writer.write_all(&data[..split]).await?;
+        pending = data.split_off(split);
+    }
+    // A lone "\r" held back at a chunk boundary wasn't part of a trailing newline
+    if pending == b"\r" {
+        writer.write_all(&pending).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, prepare_inputs};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use anyhow::{Result, anyhow};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+
+/// Bytes hashed per read; the input is never held in memory as a whole.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct Sha256Checksum;
+
+#[async_trait]
+impl Primitive for Sha256Checksum {
+    fn name(&self) -> &str {
+        "Sha256Checksum"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "input".to_string(),
+                description: "File to checksum".to_string(),
+                mime_type: "*/*".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "checksum".to_string(),
+                description: "JSON with the hex sha256 digest and the byte count".to_string(),
+                mime_type: "application/json".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let uri = match inputs.get("input").and_then(|v| v.first()) {
+            Some(PrimitiveInput::ArtifactPath(p)) => p.clone(),
+            Some(PrimitiveInput::Value(_)) => return Err(anyhow!("Sha256Checksum expects an artifact path for input")),
+            None => return Err(anyhow!("Missing required input 'input'")),
+        };
+
+        let mut reader = context.file_manager.open_read(&uri).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let mut bytes: u64 = 0;
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            bytes += n as u64;
+        }
+        let checksum = serde_json::json!({
+            "sha256": format!("{:x}", hasher.finalize()),
+            "bytes": bytes,
+        });
+
+        let temp_path = context.file_manager.prepare_output("checksum.json").await?;
+        tokio::fs::write(&temp_path, serde_json::to_string(&checksum)?).await?;
+        let artifact_uri = context.file_manager.commit_output("checksum.json", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "checksum".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
@@ -0,0 +1,225 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, PrimitiveError, optional_value, defaulted_value, prepare_inputs, is_plain_filename};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+
+const DEFAULT_COMMAND_TIMEOUT_SECS: f64 = 60.0;
+/// Bytes of stderr kept for the error message when the command fails.
+const MAX_STDERR_BYTES: usize = 4096;
+
+/// Inputs `RunCommand` reads itself; every other input is a file for the command.
+const RESERVED_INPUTS: [&str; 3] = ["command", "output", "timeout_secs"];
+
+/// Splits `command` into program and arguments: a JSON array of strings as-is, otherwise
+/// on whitespace. Splitting happens before placeholders are filled in, so a path with
+/// spaces stays a single argument.
+fn parse_command(command: &str) -> Result<Vec<String>> {
+    let command = command.trim();
+    let tokens: Vec<String> = if command.starts_with('[') {
+        serde_json::from_str(command).map_err(|e| anyhow!("Input 'command' is not a JSON array of strings: {}", e))?
+    } else {
+        command.split_whitespace().map(str::to_string).collect()
+    };
+    if tokens.is_empty() {
+        return Err(anyhow!("Input 'command' is empty"));
+    }
+    Ok(tokens)
+}
+
+/// Replaces each `{name}` in `token` with the path bound to `name`, adding `name` to
+/// `used`. `{{` and `}}` stand for literal braces, as in `format!`, so arguments like awk
+/// or jq programs can use them.
+fn fill_placeholders(token: &str, paths: &HashMap<String, PathBuf>, used: &mut HashSet<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = token;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let brace = &rest[start..start + 1];
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix(brace) {
+            out.push_str(brace);
+            rest = escaped;
+            continue;
+        }
+        if brace == "}" {
+            return Err(anyhow!("Unmatched '}}' in command argument '{}'; write '}}}}' for a literal brace", token));
+        }
+        let end = after.find('}')
+            .ok_or_else(|| anyhow!("Unclosed placeholder in command argument '{}'; write '{{{{' for a literal brace", token))?;
+        let name = &after[..end];
+        let path = paths.get(name)
+            .ok_or_else(|| anyhow!("Unknown placeholder '{{{}}}' in command; write '{{{{' and '}}}}' for literal braces", name))?;
+        out.push_str(&path.to_string_lossy());
+        used.insert(name.to_string());
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Where input `name` is staged in the working directory, keeping the source's extension
+/// for tools that detect formats by it.
+fn staged_path(dir: &Path, name: &str, source: &Path) -> PathBuf {
+    match source.extension() {
+        Some(ext) => dir.join(format!("{}.{}", name, ext.to_string_lossy())),
+        None => dir.join(name),
+    }
+}
+
+#[derive(Debug)]
+pub struct RunCommand;
+
+#[async_trait]
+impl Primitive for RunCommand {
+    fn name(&self) -> &str {
+        "RunCommand"
+    }
+
+    /// Every input outside the schema is a file staged for the command under its name.
+    fn accepts_undeclared_inputs(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "command".to_string(),
+                description: "Program and arguments, whitespace-separated or a JSON array; '{name}' is replaced by the path of file input 'name' and '{output}' by the output path, and '{{' / '}}' are literal braces".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "output".to_string(),
+                description: "Filename the command writes its result to (default 'output'); without an '{output}' placeholder, stdout is captured instead".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("output".to_string()),
+            },
+            InputDef {
+                name: "timeout_secs".to_string(),
+                description: format!("Kill the command after this many seconds (default {})", DEFAULT_COMMAND_TIMEOUT_SECS),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_COMMAND_TIMEOUT_SECS.to_string()),
+            },
+            // Any other named input is a file staged for the command.
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "output".to_string(),
+                description: "The file the command wrote, or its stdout".to_string(),
+                mime_type: "*/*".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let command = optional_value(&inputs, "command")?.ok_or_else(|| anyhow!("Missing required input 'command'"))?;
+        let tokens = parse_command(&command)?;
+        let output_name = defaulted_value(&inputs, "output")?.trim().to_string();
+        if !is_plain_filename(&output_name) {
+            return Err(anyhow!("Input 'output' must be a plain filename, got '{}'", output_name));
+        }
+        let timeout = defaulted_value(&inputs, "timeout_secs")?;
+        let timeout = timeout.trim().parse::<f64>().ok().filter(|s| s.is_finite() && *s > 0.0)
+            .ok_or_else(|| anyhow!("Input 'timeout_secs' must be a positive number, got '{}'", timeout))?;
+        let timeout = std::time::Duration::from_secs_f64(timeout);
+
+        // The command runs in a scratch directory holding only its inputs and output.
+        let work_dir = std::env::temp_dir().join(format!("curio_cmd_{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&work_dir).await?;
+        let result = run_in(&work_dir, &tokens, &output_name, timeout, &inputs, &context).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        let artifact_uri = result?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "output".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// Stages the file inputs in `work_dir`, runs the command there and commits its output.
+async fn run_in(
+    work_dir: &Path,
+    tokens: &[String],
+    output_name: &str,
+    timeout: std::time::Duration,
+    inputs: &HashMap<String, Vec<PrimitiveInput>>,
+    context: &ExecutionContext<'_>,
+) -> Result<String> {
+    let mut paths = HashMap::new();
+    for (name, values) in inputs {
+        if RESERVED_INPUTS.contains(&name.as_str()) {
+            continue;
+        }
+        // The name becomes the staged filename, so it must not reach outside `work_dir`.
+        if !is_plain_filename(name) {
+            return Err(anyhow!("File input name '{}' must be a plain filename", name));
+        }
+        let uri = match values.first() {
+            Some(PrimitiveInput::ArtifactPath(p)) => p,
+            _ => return Err(anyhow!("RunCommand expects an artifact path for file input '{}'", name)),
+        };
+        let source = context.file_manager.get_file(uri).await?;
+        let staged = staged_path(work_dir, name, &source);
+        tokio::fs::copy(&source, &staged).await?;
+        paths.insert(name.clone(), staged);
+    }
+    let output_path = work_dir.join(output_name);
+    paths.insert("output".to_string(), output_path.clone());
+
+    let mut used = HashSet::new();
+    let args = tokens.iter().map(|t| fill_placeholders(t, &paths, &mut used)).collect::<Result<Vec<_>>>()?;
+    let capture_stdout = !used.contains("output");
+
+    // No shell is involved, so arguments are never re-parsed; the environment is reduced
+    // to PATH so the command can't see the worker's credentials.
+    let child = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .current_dir(work_dir)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .stdin(Stdio::null())
+        .stdout(if capture_stdout { Stdio::piped() } else { Stdio::null() })
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start '{}': {}", args[0], e))?;
+
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => output?,
+        Err(_) => return Err(PrimitiveError::Timeout { target: format!("command '{}'", args[0]), after: timeout }.into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr[..output.stderr.len().min(MAX_STDERR_BYTES)]);
+        return Err(anyhow!("Command '{}' failed ({}): {}", args[0], output.status, stderr.trim()));
+    }
+    if capture_stdout {
+        tokio::fs::write(&output_path, &output.stdout).await?;
+    } else if !output_path.exists() {
+        return Err(anyhow!("Command '{}' did not write its output file '{}'", args[0], output_name));
+    }
+
+    let temp_path = context.file_manager.prepare_output(output_name).await?;
+    tokio::fs::copy(&output_path, &temp_path).await?;
+    context.file_manager.commit_output(output_name, &temp_path).await
+}
@@ -0,0 +1,164 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, prepare_inputs};
+use async_trait::async_trait;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+use tokio_util::io::SyncIoBridge;
+
+fn input_schema(description: &str, mime_type: &str) -> Vec<InputDef> {
+    vec![
+        InputDef {
+            name: "input".to_string(),
+            description: description.to_string(),
+            mime_type: mime_type.to_string(),
+            min_count: 1,
+            max_count: Some(1),
+            default: None,
+        }
+    ]
+}
+
+fn output_schema(description: &str, mime_type: &str) -> Vec<OutputDef> {
+    vec![
+        OutputDef {
+            name: "output".to_string(),
+            description: description.to_string(),
+            mime_type: mime_type.to_string(),
+        }
+    ]
+}
+
+/// The artifact URI of the single `input`.
+fn input_uri(inputs: &HashMap<String, Vec<PrimitiveInput>>, primitive: &str) -> Result<String> {
+    match inputs.get("input").and_then(|v| v.first()) {
+        Some(PrimitiveInput::ArtifactPath(p)) => Ok(p.clone()),
+        Some(PrimitiveInput::Value(_)) => Err(anyhow!("{} expects an artifact path for input", primitive)),
+        None => Err(anyhow!("Missing required input 'input'")),
+    }
+}
+
+/// Last path segment of `uri`, used to name the output after its source.
+fn file_name(uri: &str) -> Option<&str> {
+    Path::new(uri).file_name().and_then(|n| n.to_str()).filter(|n| !n.is_empty())
+}
+
+#[derive(Debug)]
+pub struct Gzip;
+
+#[async_trait]
+impl Primitive for Gzip {
+    fn name(&self) -> &str {
+        "Gzip"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        input_schema("File to compress", "*/*")
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        output_schema("Gzip-compressed file", "application/gzip")
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let uri = input_uri(&inputs, "Gzip")?;
+        let source = SyncIoBridge::new(context.file_manager.open_read(&uri).await?);
+        let output_name = format!("{}.gz", file_name(&uri).unwrap_or("output"));
+
+        let temp_path = context.file_manager.prepare_output(&output_name).await?;
+        let target = temp_path.clone();
+        // Streams the input into the file, so large artifacts are never held in memory.
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let mut reader = source;
+            let mut encoder = GzEncoder::new(std::fs::File::create(&target)?, Compression::default());
+            std::io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }).await??;
+        let artifact_uri = context.file_manager.commit_output(&output_name, &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "output".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub struct Gunzip;
+
+#[async_trait]
+impl Primitive for Gunzip {
+    fn name(&self) -> &str {
+        "Gunzip"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        input_schema("Gzip-compressed file", "application/gzip")
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        output_schema("Decompressed file", "*/*")
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let uri = input_uri(&inputs, "Gunzip")?;
+        let source = SyncIoBridge::new(context.file_manager.open_read(&uri).await?);
+        let output_name = file_name(&uri)
+            .and_then(|n| n.strip_suffix(".gz"))
+            .filter(|n| !n.is_empty())
+            .unwrap_or("gunzipped.bin")
+            .to_string();
+
+        let temp_path = context.file_manager.prepare_output(&output_name).await?;
+        let target = temp_path.clone();
+        let decoded = tokio::task::spawn_blocking(move || -> Result<()> {
+            // Multi-member files (e.g. concatenated .gz parts) decode to the joined content.
+            let mut decoder = MultiGzDecoder::new(source);
+            let mut writer = std::fs::File::create(&target)?;
+            std::io::copy(&mut decoder, &mut writer).map_err(|e| match e.kind() {
+                std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof =>
+                    anyhow!("Input '{}' is not valid gzip: {}", uri, e),
+                _ => e.into(),
+            })?;
+            Ok(())
+        }).await?;
+        if let Err(e) = decoded {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        let artifact_uri = context.file_manager.commit_output(&output_name, &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "output".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
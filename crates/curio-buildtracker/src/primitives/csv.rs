@@ -1,10 +1,79 @@
-use super::{Primitive, PrimitiveStatus, PrimitiveInput, PrimitiveOutput, InputDef, OutputDef, ExecutionContext};
+use super::{Primitive, PrimitiveStatus, PrimitiveInput, PrimitiveOutput, InputDef, OutputDef, ExecutionContext, optional_value, defaulted_value, optional_flag, prepare_inputs};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use anyhow::{Result, anyhow};
 use tokio::sync::mpsc;
 use polars::prelude::*;
 use polars::sql::SQLContext;
+use polars_plan::prelude::{ALogicalPlan, FileType, SinkType};
+
+/// Rows per batch when streaming a lazy result to CSV.
+const SINK_BATCH_SIZE: usize = 8192;
+
+/// Writes `lf` to `path` as CSV with Polars' streaming engine, so memory stays bounded
+/// by the batch size rather than the input size. Plans the streaming engine can't run
+/// are collected in memory instead. Returns whether the result was streamed.
+pub(crate) fn sink_csv_or_collect(lf: LazyFrame, path: &std::path::Path) -> Result<bool> {
+    let options = CsvWriterOptions {
+        include_header: true,
+        batch_size: SINK_BATCH_SIZE,
+        maintain_order: true,
+        ..Default::default()
+    };
+    if streamable(&lf, path, options.clone())? {
+        lf.sink_csv(path.to_path_buf(), options)?;
+        return Ok(true);
+    }
+
+    let mut df = lf.collect()?;
+    let mut file = std::fs::File::create(path)?;
+    CsvWriter::new(&mut file).finish(&mut df)?;
+    Ok(false)
+}
+
+/// Whether `sink_csv` can run `lf` on the streaming engine. The optimizer replaces a
+/// sink with a streaming pipeline only when the whole plan beneath it can stream.
+fn streamable(lf: &LazyFrame, path: &std::path::Path, options: CsvWriterOptions) -> Result<bool> {
+    let mut probe = lf.clone().with_streaming(true);
+    probe.logical_plan = LogicalPlan::Sink {
+        input: Box::new(probe.logical_plan),
+        payload: SinkType::File { path: Arc::new(path.to_path_buf()), file_type: FileType::Csv(options) },
+    };
+    let (top, plan, _) = probe.to_alp_optimized()?;
+    Ok(!matches!(plan.get(top), ALogicalPlan::Sink { .. }))
+}
+
+/// Expands `CsvSelect` column specs against the CSV's `header`. A spec prefixed `re:` is a
+/// regex searched for in each name; one containing `*` is a glob over the whole name
+/// (`*` any run, `?` one character). Either selects every matching column in header order
+/// and must match at least one. Other specs are column names, kept as given. Columns
+/// selected by more than one spec appear once, at their first position.
+fn expand_column_specs(specs: &[String], header: &[String]) -> Result<Vec<String>> {
+    let mut selected: Vec<String> = Vec::new();
+    for spec in specs {
+        let pattern = if let Some(re) = spec.strip_prefix("re:") {
+            Some(regex::Regex::new(re).map_err(|e| anyhow!("Invalid column regex '{}': {}", re, e))?)
+        } else if spec.contains('*') {
+            let glob = regex::escape(spec).replace(r"\*", ".*").replace(r"\?", ".");
+            Some(regex::Regex::new(&format!("^{}$", glob))?)
+        } else {
+            None
+        };
+        let matched: Vec<&String> = match &pattern {
+            Some(pattern) => header.iter().filter(|name| pattern.is_match(name)).collect(),
+            None => vec![spec],
+        };
+        if matched.is_empty() {
+            return Err(anyhow!("Column pattern '{}' matches no columns", spec));
+        }
+        for name in matched {
+            if !selected.contains(name) {
+                selected.push(name.clone());
+            }
+        }
+    }
+    Ok(selected)
+}
 
 #[derive(Debug)]
 pub struct CsvSelect;
@@ -15,6 +84,10 @@ impl Primitive for CsvSelect {
         "CsvSelect"
     }
 
+    fn idempotent(&self) -> bool {
+        true
+    }
+
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
             InputDef {
@@ -23,13 +96,15 @@ impl Primitive for CsvSelect {
                 mime_type: "text/csv".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
             },
             InputDef {
                 name: "columns".to_string(),
-                description: "Columns to select".to_string(),
+                description: "Columns to select: names, 're:'-prefixed regexes or globs like 'metric_*'".to_string(),
                 mime_type: "text/plain".to_string(),
                 min_count: 1,
                 max_count: None, // Multiple columns
+                default: None,
             }
         ]
     }
@@ -50,6 +125,7 @@ impl Primitive for CsvSelect {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         let csv_path_str = match &inputs["csv"][0] {
              PrimitiveInput::ArtifactPath(p) => p.clone(),
              PrimitiveInput::Value(_) => return Err(anyhow!("CsvSelect requires artifact path input")),
@@ -67,21 +143,22 @@ impl Primitive for CsvSelect {
         // Prepare output path
         let output_path = context.file_manager.prepare_output("csv_select_out.csv").await?;
         
-        // Polars logic: Read -> Select -> Write
-        let df = CsvReader::from_path(&local_path)?
+        // Polars logic: Scan -> Select -> Sink. The projection is pushed down into the
+        // scan and rows are streamed to the output, so the input never has to fit in memory.
+        let lf = LazyCsvReader::new(&local_path)
             .has_header(true)
             .finish()?;
 
         // Select columns if specified and valid
-        let result_df = if !cols.is_empty() && cols[0] != "" {
-             df.select(cols)?
+        let result_lf = if !cols.is_empty() && cols[0] != "" {
+             let header: Vec<String> = lf.schema()?.iter_names().map(|name| name.to_string()).collect();
+             let cols = expand_column_specs(&cols, &header)?;
+             lf.select(cols.iter().map(|c| col(c)).collect::<Vec<_>>())
         } else {
-             df
+             lf
         };
-        
-        let mut file = std::fs::File::create(&output_path)?; // Polars uses std::fs::File
-        CsvWriter::new(&mut file)
-            .finish(&mut result_df.clone())?; // Write result
+
+        sink_csv_or_collect(result_lf, &output_path)?;
         
         // Track output (prepare_output tracks it)
         // Commit output
@@ -89,13 +166,257 @@ impl Primitive for CsvSelect {
         
         Ok(vec![
              PrimitiveOutput {
-                 name: "selected".to_string(),
+                 name: "output".to_string(),
                  artifact_path: artifact_uri,
              }
         ])
     }
 }
 
+/// Marks rows that found a partner in `MergeCsv`'s joins.
+const MERGE_MATCHED: &str = "__merge_matched";
+/// Suffix for the update side's copy of each column while the two are combined.
+const MERGE_UPDATE_SUFFIX: &str = "__merge_update";
+
+/// Upserts `updates` into `base` by the `key` column: base rows keep their position but
+/// take their values from the update row with the same key, and update rows with new keys
+/// are appended in order. If `updates` repeats a key, its last row wins. The result has
+/// `base`'s columns and dtypes.
+fn merge_on_key(base: LazyFrame, updates: LazyFrame, key: &str) -> Result<LazyFrame> {
+    let schema = base.schema()?;
+    if schema.get(key).is_none() {
+        return Err(anyhow!("Key column '{}' not found in base CSV", key));
+    }
+    let columns: Vec<(String, DataType)> = schema.iter().map(|(name, dtype)| (name.to_string(), dtype.clone())).collect();
+
+    let updates = updates
+        .select(columns.iter().map(|(name, dtype)| col(name).cast(dtype.clone())).collect::<Vec<_>>())
+        .unique_stable(Some(vec![key.to_string()]), UniqueKeepStrategy::Last);
+
+    let renamed: Vec<Expr> = columns.iter()
+        .map(|(name, _)| if name == key { col(name) } else { col(name).alias(&format!("{}{}", name, MERGE_UPDATE_SUFFIX)) })
+        .chain([lit(true).alias(MERGE_MATCHED)])
+        .collect();
+    let replaced = base.clone()
+        .left_join(updates.clone().select(renamed), col(key), col(key))
+        .select(columns.iter().map(|(name, _)| {
+            if name == key {
+                col(name)
+            } else {
+                when(col(MERGE_MATCHED).is_not_null())
+                    .then(col(&format!("{}{}", name, MERGE_UPDATE_SUFFIX)))
+                    .otherwise(col(name))
+                    .alias(name)
+            }
+        }).collect::<Vec<_>>());
+
+    let base_keys = base.select([col(key), lit(true).alias(MERGE_MATCHED)]).unique(None, UniqueKeepStrategy::Any);
+    let added = updates
+        .left_join(base_keys, col(key), col(key))
+        .filter(col(MERGE_MATCHED).is_null())
+        .select(columns.iter().map(|(name, _)| col(name)).collect::<Vec<_>>());
+
+    Ok(concat([replaced, added], UnionArgs::default())?)
+}
+
+#[derive(Debug)]
+pub struct MergeCsv;
+
+#[async_trait]
+impl Primitive for MergeCsv {
+    fn name(&self) -> &str {
+        "MergeCsv"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "base".to_string(),
+                description: "CSV to update".to_string(),
+                mime_type: "text/csv".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "updates".to_string(),
+                description: "CSV whose rows replace or extend the base rows".to_string(),
+                mime_type: "text/csv".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "key".to_string(),
+                description: "Column identifying a row in both files".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "merged".to_string(),
+                description: "Base rows with updates applied, followed by rows with new keys".to_string(),
+                mime_type: "text/csv".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let mut frames = Vec::with_capacity(2);
+        for name in ["base", "updates"] {
+            let path = match &inputs[name][0] {
+                PrimitiveInput::ArtifactPath(p) => p.clone(),
+                PrimitiveInput::Value(_) => return Err(anyhow!("MergeCsv requires an artifact path for '{}'", name)),
+            };
+            let local_path = context.file_manager.get_file(&path).await?;
+            frames.push(LazyCsvReader::new(local_path).has_header(true).finish()?);
+        }
+        let updates = frames.pop().unwrap();
+        let base = frames.pop().unwrap();
+        let key = optional_value(&inputs, "key")?.ok_or_else(|| anyhow!("Missing required input 'key'"))?;
+
+        let merged = merge_on_key(base, updates, key.trim())?;
+
+        let output_path = context.file_manager.prepare_output("merge_csv_result.csv").await?;
+        sink_csv_or_collect(merged, &output_path)?;
+        let artifact_uri = context.file_manager.commit_output("merge_csv_result.csv", &output_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "merged".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// Result encodings supported by `CsvSql`'s `format` input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Csv,
+    Json,
+    Parquet,
+}
+
+impl OutputFormat {
+    fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "parquet" => Ok(Self::Parquet),
+            other => Err(anyhow!("Input 'format' must be 'csv', 'json' or 'parquet', got '{}'", other)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Parquet => "parquet",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Json => "application/json",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn write(self, df: &mut DataFrame, file: &mut std::fs::File) -> Result<()> {
+        match self {
+            Self::Csv => CsvWriter::new(file).finish(df)?,
+            // A JSON array of row objects, which is what the renderer expects
+            Self::Json => JsonWriter::new(file).with_json_format(JsonFormat::Json).finish(df)?,
+            Self::Parquet => {
+                ParquetWriter::new(file).finish(df)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders a `CsvSql` parameter as a SQL literal. Strings are single-quoted with embedded
+/// quotes doubled, so the lexer can only ever read them as one string.
+fn sql_literal(name: &str, value: &serde_json::Value) -> Result<String> {
+    match value {
+        serde_json::Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        // Parenthesized so a preceding '-' can't turn the sign into a comment
+        serde_json::Value::Number(n) if n.to_string().starts_with('-') => Ok(format!("({})", n)),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+        serde_json::Value::Null => Ok("NULL".to_string()),
+        _ => Err(anyhow!("Parameter '{}' must be a string, number, boolean or null", name)),
+    }
+}
+
+/// Replaces each `:name` placeholder in `query` with the literal for `params[name]`.
+/// Placeholders inside string literals, quoted identifiers and comments are left alone,
+/// as are `::` casts. A placeholder without a parameter is an error.
+fn bind_params(query: &str, params: &serde_json::Map<String, serde_json::Value>) -> Result<String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut out = String::with_capacity(query.len());
+    let mut i = 0;
+    // Copies chars[i..] up to and including the next `close`, or to the end.
+    let copy_through = |out: &mut String, i: &mut usize, close: &str| {
+        let rest: String = chars[*i..].iter().collect();
+        let len = rest.find(close).map(|at| at + close.len()).unwrap_or(rest.len());
+        out.push_str(&rest[..len]);
+        *i += rest[..len].chars().count();
+    };
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        match chars[i] {
+            // A doubled quote inside a literal reads as its end and a new start, which
+            // copies the same text.
+            quote @ ('\'' | '"' | '`') => {
+                out.push(quote);
+                i += 1;
+                copy_through(&mut out, &mut i, &quote.to_string());
+            }
+            '-' if next == Some('-') => copy_through(&mut out, &mut i, "\n"),
+            '/' if next == Some('*') => copy_through(&mut out, &mut i, "*/"),
+            ':' if next == Some(':') => {
+                out.push_str("::");
+                i += 2;
+            }
+            ':' if next.is_some_and(|c| c.is_ascii_alphabetic() || c == '_') => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                let value = params.get(&name).ok_or_else(|| anyhow!("Query placeholder ':{}' has no value in 'params'", name))?;
+                out.push_str(&sql_literal(&name, value)?);
+                i = end;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
 #[derive(Debug)]
 pub struct CsvSql;
 
@@ -105,9 +426,22 @@ impl Primitive for CsvSql {
         "CsvSql"
     }
 
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    /// Every input outside the schema is registered as a table under its name.
+    fn accepts_undeclared_inputs(&self) -> bool {
+        true
+    }
+
     fn input_schema(&self) -> Vec<InputDef> {
          vec![
-             InputDef { name: "query".to_string(), description: "SQL Query".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1) },
+             InputDef { name: "query".to_string(), description: "SQL Query".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+             InputDef { name: "format".to_string(), description: "Result format: csv (default), json or parquet".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: Some("csv".to_string()) },
+             InputDef { name: "max_result_rows".to_string(), description: "Fail instead of producing more rows than this (default unlimited)".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+             InputDef { name: "params".to_string(), description: "JSON object of values for ':name' placeholders in the query, bound as literals".to_string(), mime_type: "application/json".to_string(), min_count: 0, max_count: Some(1), default: None },
+             InputDef { name: "emit_schema".to_string(), description: "If 'true', also emit the result schema as JSON".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
              // Dynamic inputs for tables?
          ]
     }
@@ -116,8 +450,15 @@ impl Primitive for CsvSql {
         vec![
             OutputDef {
                 name: "result".to_string(),
-                description: "SQL Result CSV".to_string(),
-                mime_type: "text/csv".to_string(),
+                description: format!("SQL result; {} unless `format` selects {} or {}",
+                    OutputFormat::Csv.mime_type(), OutputFormat::Json.mime_type(), OutputFormat::Parquet.mime_type()),
+                // Depends on `format`, which the schema can't express
+                mime_type: "*/*".to_string(),
+            },
+            OutputDef {
+                name: "schema".to_string(),
+                description: "Result columns mapped to their Polars dtype (only with `emit_schema`)".to_string(),
+                mime_type: "application/json".to_string(),
             }
         ]
     }
@@ -128,11 +469,29 @@ impl Primitive for CsvSql {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         let query = match &inputs["query"][0] {
              PrimitiveInput::Value(s) => s.clone(),
              _ => return Err(anyhow!("Query must be inline value")),
         };
         inputs.remove("query");
+        let query = match optional_value(&inputs, "params")? {
+            Some(params) => {
+                let params: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&params)
+                    .map_err(|e| anyhow!("Input 'params' must be a JSON object: {}", e))?;
+                bind_params(&query, &params)?
+            }
+            None => query,
+        };
+        inputs.remove("params");
+        let format = OutputFormat::parse(&defaulted_value(&inputs, "format")?)?;
+        inputs.remove("format");
+        let emit_schema = optional_flag(&inputs, "emit_schema", false)?;
+        inputs.remove("emit_schema");
+        let max_result_rows = optional_value(&inputs, "max_result_rows")?
+            .map(|v| v.trim().parse::<usize>().map_err(|_| anyhow!("Input 'max_result_rows' must be a non-negative integer, got '{}'", v)))
+            .transpose()?;
+        inputs.remove("max_result_rows");
 
         // Register remaining inputs as tables
         let mut ctx = SQLContext::new();
@@ -147,19 +506,54 @@ impl Primitive for CsvSql {
              }
         }
         
-        let df = ctx.execute(&query)?.collect()?;
+        let lf = ctx.execute(&query)?;
+        // Resolved from the plan, so it costs nothing extra when the result is streamed
+        let schema = if emit_schema { Some(lf.schema()?) } else { None };
         
-        let output_path = context.file_manager.prepare_output("sql_result.csv").await?;
-        let mut file = std::fs::File::create(&output_path)?;
-        CsvWriter::new(&mut file).finish(&mut df.clone())?;
+        let output_key = format!("sql_result.{}", format.extension());
+        let output_path = context.file_manager.prepare_output(&output_key).await?;
+        // A frame holds at most `IdxSize::MAX` rows, so a cap with no room for the extra row
+        // below can never be exceeded and is the same as no cap.
+        let row_limit = max_result_rows.and_then(|max_rows| {
+            max_rows.checked_add(1).and_then(|n| IdxSize::try_from(n).ok()).map(|limit| (max_rows, limit))
+        });
+        if let Some((max_rows, limit)) = row_limit {
+            // Collecting one row past the limit bounds what is materialized (the slice is
+            // pushed down into the plan), and when within the limit it is the full result.
+            let mut df = lf.limit(limit).collect()?;
+            if df.height() > max_rows {
+                return Err(anyhow!("Query result exceeds max_result_rows ({}); aborted before materializing it", max_rows));
+            }
+            let mut file = std::fs::File::create(&output_path)?;
+            format.write(&mut df, &mut file)?;
+        } else if format == OutputFormat::Csv {
+            sink_csv_or_collect(lf, &output_path)?;
+        } else {
+            let mut df = lf.collect()?;
+            let mut file = std::fs::File::create(&output_path)?;
+            format.write(&mut df, &mut file)?;
+        }
 
-        let artifact_uri = context.file_manager.commit_output("sql_result.csv", &output_path).await?;
-        
-        Ok(vec![
+        let artifact_uri = context.file_manager.commit_output(&output_key, &output_path).await?;
+        let mut outputs = vec![
              PrimitiveOutput {
                 name: "result".to_string(),
                 artifact_path: artifact_uri,
              }
-        ])
+        ];
+
+        if let Some(schema) = schema {
+            let columns: serde_json::Map<String, serde_json::Value> = schema.iter()
+                .map(|(name, dtype)| (name.to_string(), serde_json::Value::String(dtype.to_string())))
+                .collect();
+            let schema_path = context.file_manager.prepare_output("sql_schema.json").await?;
+            tokio::fs::write(&schema_path, serde_json::to_string_pretty(&columns)?).await?;
+            outputs.push(PrimitiveOutput {
+                name: "schema".to_string(),
+                artifact_path: context.file_manager.commit_output("sql_schema.json", &schema_path).await?,
+            });
+        }
+
+        Ok(outputs)
     }
 }
@@ -0,0 +1,179 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, optional_value, defaulted_value, prepare_inputs, read_input_text};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+
+const DEFAULT_TEXT_WIDTH: usize = 80;
+
+async fn commit_text(context: &ExecutionContext<'_>, hint: &str, text: &str) -> Result<Vec<PrimitiveOutput>> {
+    let temp_path = context.file_manager.prepare_output(hint).await?;
+    tokio::fs::write(&temp_path, text).await?;
+    let artifact_uri = context.file_manager.commit_output(hint, &temp_path).await?;
+    Ok(vec![
+        PrimitiveOutput {
+            name: "text".to_string(),
+            artifact_path: artifact_uri,
+        }
+    ])
+}
+
+fn text_input_schema(description: &str) -> Vec<InputDef> {
+    vec![
+        InputDef {
+            name: "text".to_string(),
+            description: description.to_string(),
+            mime_type: "text/*".to_string(),
+            min_count: 1,
+            max_count: Some(1),
+            default: None,
+        }
+    ]
+}
+
+#[derive(Debug)]
+pub struct DecodeHtmlEntities;
+
+#[async_trait]
+impl Primitive for DecodeHtmlEntities {
+    fn name(&self) -> &str {
+        "DecodeHtmlEntities"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        text_input_schema("Text or HTML containing entities (e.g. '&lt;')")
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "text".to_string(),
+                description: "Text with entities decoded".to_string(),
+                mime_type: "text/plain".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let text_input = inputs.remove("text").ok_or_else(|| anyhow!("Missing text input"))?;
+        let text = read_input_text(&text_input[0], &context).await?;
+        let decoded = html_escape::decode_html_entities(&text).into_owned();
+        commit_text(&context, "html_decoded.txt", &decoded).await
+    }
+}
+
+#[derive(Debug)]
+pub struct EncodeHtmlEntities;
+
+#[async_trait]
+impl Primitive for EncodeHtmlEntities {
+    fn name(&self) -> &str {
+        "EncodeHtmlEntities"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        text_input_schema("Plain text to escape for embedding in HTML")
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "text".to_string(),
+                description: "Text with '&', '<' and '>' encoded as entities".to_string(),
+                mime_type: "text/html".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let text_input = inputs.remove("text").ok_or_else(|| anyhow!("Missing text input"))?;
+        let text = read_input_text(&text_input[0], &context).await?;
+        let encoded = html_escape::encode_text(&text).into_owned();
+        commit_text(&context, "html_encoded.txt", &encoded).await
+    }
+}
+
+#[derive(Debug)]
+pub struct HtmlToText;
+
+#[async_trait]
+impl Primitive for HtmlToText {
+    fn name(&self) -> &str {
+        "HtmlToText"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "html".to_string(),
+                description: "HTML document or fragment".to_string(),
+                mime_type: "text/html".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "width".to_string(),
+                description: format!("Line wrap width in columns (default {})", DEFAULT_TEXT_WIDTH),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_TEXT_WIDTH.to_string()),
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "text".to_string(),
+                description: "Plain text with tags stripped".to_string(),
+                mime_type: "text/plain".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let width = defaulted_value(&inputs, "width")?;
+        let width = width.trim().parse::<usize>().ok().filter(|w| *w > 0)
+            .ok_or_else(|| anyhow!("Input 'width' must be a positive integer, got '{}'", width))?;
+        let html_input = inputs.remove("html").ok_or_else(|| anyhow!("Missing html input"))?;
+        let html = read_input_text(&html_input[0], &context).await?;
+
+        // Same as the renderer: decode entities first, then strip tags
+        let decoded = html_escape::decode_html_entities(&html);
+        let text = html2text::from_read(decoded.as_bytes(), width);
+
+        commit_text(&context, "html_text.txt", &text).await
+    }
+}
@@ -1,4 +1,7 @@
-use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext};
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, LogLevel, PrimitiveError, optional_value, defaulted_value, optional_flag, content_type_matches, prepare_inputs};
+use crate::file_manager::{s3_client_with, ContextFileManager, FileManager};
+use aws_sdk_s3::types::ObjectCannedAcl;
+use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use anyhow::{Result, anyhow};
@@ -8,6 +11,257 @@ use tokio::io::AsyncWriteExt; // For file writing
 // use aws_config;
 // use aws_sdk_s3;
 
+/// Attempts made for transient failures (connect errors, 5xx) before giving up.
+const FETCH_MAX_ATTEMPTS: u32 = 3;
+const FETCH_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Redirects `FetchUrl` follows when `max_redirects` isn't given (reqwest's own default).
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Defaults for `FETCH_CONNECT_TIMEOUT_SECS` / `FETCH_READ_TIMEOUT_SECS`.
+const DEFAULT_CONNECT_TIMEOUT_SECS: f64 = 10.0;
+const DEFAULT_READ_TIMEOUT_SECS: f64 = 30.0;
+
+static HTTP_CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+static RAW_HTTP_CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+
+fn timeout_from_env(var: &str, default_secs: f64) -> std::time::Duration {
+    let secs = std::env::var(var).ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .unwrap_or(default_secs);
+    std::time::Duration::from_secs_f64(secs)
+}
+
+/// Shared HTTP client for `FetchUrl` and `HttpUpload`, reused across invocations so warm
+/// Lambdas keep their connection pool.
+/// The connect timeout is fixed when the client is first built.
+/// It advertises and transparently decodes gzip, deflate and br `Content-Encoding`s.
+/// Redirects are not followed by the client; `FetchUrl` follows them itself so it can
+/// enforce `max_redirects` and report where the content came from.
+fn http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(timeout_from_env("FETCH_CONNECT_TIMEOUT_SECS", DEFAULT_CONNECT_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+/// Like `http_client`, but leaves bodies exactly as sent (`FetchUrl`'s `raw` mode).
+fn raw_http_client() -> &'static reqwest::Client {
+    RAW_HTTP_CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(timeout_from_env("FETCH_CONNECT_TIMEOUT_SECS", DEFAULT_CONNECT_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none())
+            .no_gzip()
+            .no_deflate()
+            .no_brotli()
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+/// `url` with each of `secrets` (the values `with_secrets` substituted) replaced by
+/// `REDACTED`, for status messages, errors and the persisted `final_url`. A secret is
+/// matched verbatim and in the percent-encoded forms URL parsing gives it in a path,
+/// query or userinfo.
+fn redact_url(url: &reqwest::Url, secrets: &[String]) -> String {
+    let mut shown = url.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        let mut probe = reqwest::Url::parse("http://redact.invalid/").expect("valid probe URL");
+        let mut forms = vec![secret.clone()];
+        probe.set_path(&format!("/{}", secret));
+        forms.push(probe.path()[1..].to_string());
+        probe.set_query(Some(secret));
+        forms.extend(probe.query().map(str::to_string));
+        if probe.set_password(Some(secret)).is_ok() {
+            forms.extend(probe.password().map(str::to_string));
+        }
+        for form in forms {
+            shown = shown.replace(&form, "REDACTED");
+        }
+    }
+    shown
+}
+
+/// Maps a reqwest timeout onto `PrimitiveError::Timeout`, leaving other failures untouched
+/// apart from dropping the URL reqwest attaches (`url` is the redacted one to report).
+fn classify_fetch_error(e: reqwest::Error, url: &str, read_timeout: std::time::Duration) -> anyhow::Error {
+    if e.is_timeout() {
+        PrimitiveError::Timeout { target: url.to_string(), after: read_timeout }.into()
+    } else {
+        anyhow!("Request to {} failed: {}", url, e.without_url())
+    }
+}
+
+/// The `timeout_secs` input, or `FETCH_READ_TIMEOUT_SECS` when it isn't given.
+fn read_timeout_input(inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<std::time::Duration> {
+    match optional_value(inputs, "timeout_secs")? {
+        Some(v) => v.trim().parse::<f64>().ok().filter(|s| s.is_finite() && *s > 0.0)
+            .map(std::time::Duration::from_secs_f64)
+            .ok_or_else(|| anyhow!("Input 'timeout_secs' must be a positive number, got '{}'", v)),
+        None => Ok(timeout_from_env("FETCH_READ_TIMEOUT_SECS", DEFAULT_READ_TIMEOUT_SECS)),
+    }
+}
+
+/// Waits up to `read_timeout` for `fut`, one read from the server: the response head or
+/// the next body chunk. The window restarts with each read, so a slow but steady body
+/// may take longer than `read_timeout` in total.
+async fn within_read_timeout<T>(
+    read_timeout: std::time::Duration,
+    url: &str,
+    fut: impl std::future::Future<Output = Result<T, reqwest::Error>>,
+) -> Result<T> {
+    match tokio::time::timeout(read_timeout, fut).await {
+        Ok(result) => result.map_err(|e| classify_fetch_error(e, url, read_timeout)),
+        Err(_) => Err(PrimitiveError::Timeout { target: url.to_string(), after: read_timeout }.into()),
+    }
+}
+
+/// HTTP validators from the last fetch of a URL, committed beside its artifact when
+/// `FetchUrl`'s `cache` flag is set.
+#[derive(Debug, Serialize, Deserialize)]
+struct FetchCacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Whether the artifact holds the undecoded body (`raw`).
+    raw: bool,
+    artifact_uri: String,
+}
+
+/// Loads the cache entry for `artifact_key`, if one exists for the same `raw` mode and its
+/// artifact is still in place. Unreadable entries are treated as absent.
+async fn load_fetch_cache(cache_key: &str, artifact_key: &str, raw: bool, file_manager: &dyn FileManager) -> Result<Option<FetchCacheEntry>> {
+    let Some(uri) = file_manager.find_output(cache_key).await? else {
+        return Ok(None);
+    };
+    let path = file_manager.get_file(&uri).await?;
+    let Ok(entry) = serde_json::from_slice::<FetchCacheEntry>(&tokio::fs::read(&path).await?) else {
+        return Ok(None);
+    };
+    let current = file_manager.find_output(artifact_key).await?;
+    Ok(Some(entry).filter(|e| e.raw == raw && current.as_deref() == Some(e.artifact_uri.as_str())))
+}
+
+/// Headers that carry credentials. They are dropped on a redirect to another origin and
+/// left out of `FetchUrl`'s artifact key, since their values may be resolved secrets.
+const CREDENTIAL_HEADERS: [reqwest::header::HeaderName; 3] =
+    [reqwest::header::AUTHORIZATION, reqwest::header::COOKIE, reqwest::header::PROXY_AUTHORIZATION];
+
+/// Method, headers and body of a `FetchUrl` request.
+#[derive(Debug, Clone)]
+struct FetchRequest {
+    method: reqwest::Method,
+    headers: reqwest::header::HeaderMap,
+    body: Option<bytes::Bytes>,
+}
+
+impl FetchRequest {
+    /// Adjusts the request for a redirect to `next`. 303, and 301/302 after a POST, turn it
+    /// into a body-less GET (as browsers do); credentials aren't sent to another origin.
+    fn redirected(&mut self, status: reqwest::StatusCode, from: &reqwest::Url, next: &reqwest::Url) {
+        use reqwest::StatusCode;
+        let to_get = status == StatusCode::SEE_OTHER
+            || (matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND) && self.method == reqwest::Method::POST);
+        if to_get && self.method != reqwest::Method::HEAD {
+            self.method = reqwest::Method::GET;
+            self.body = None;
+            self.headers.remove(reqwest::header::CONTENT_TYPE);
+        }
+        if from.origin() != next.origin() {
+            for name in CREDENTIAL_HEADERS {
+                self.headers.remove(name);
+            }
+        }
+    }
+}
+
+/// Parses `FetchUrl`'s `headers` input, a JSON object of header name -> value.
+fn parse_fetch_headers(json: &str) -> Result<reqwest::header::HeaderMap> {
+    let fields: HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|e| anyhow!("Input 'headers' must be a JSON object of strings: {}", e))?;
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in fields {
+        let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow!("Invalid header name '{}' in input 'headers'", name))?;
+        // The value may hold a resolved secret, so it isn't echoed back.
+        let header_value = reqwest::header::HeaderValue::from_str(&value)
+            .map_err(|_| anyhow!("Invalid value for header '{}' in input 'headers'", name))?;
+        headers.insert(header_name, header_value);
+    }
+    Ok(headers)
+}
+
+/// Sends `request` to `url`, retrying transient failures up to `FETCH_MAX_ATTEMPTS`:
+/// connect errors always, 5xx responses only for idempotent methods, since the server
+/// may already have acted on a POST. A read timeout is not retried: a hung server would
+/// hold the worker for every attempt.
+async fn send_with_retry(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    request: &FetchRequest,
+    read_timeout: std::time::Duration,
+    validators: Option<&FetchCacheEntry>,
+    redact: &[String],
+    status_tx: &Option<mpsc::Sender<PrimitiveStatus>>,
+) -> Result<reqwest::Response> {
+    let mut attempt = 1;
+    loop {
+        let mut builder = client.request(request.method.clone(), url.clone())
+            .headers(request.headers.clone());
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        if let Some(entry) = validators {
+            if let Some(etag) = &entry.etag {
+                builder = builder.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                builder = builder.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let Ok(result) = tokio::time::timeout(read_timeout, builder.send()).await else {
+            return Err(PrimitiveError::Timeout { target: redact_url(url, redact), after: read_timeout }.into());
+        };
+        let retry_reason = match &result {
+            Ok(r) if r.status().is_server_error() && request.method.is_idempotent() => Some(format!("HTTP {}", r.status())),
+            // reqwest's own message names the URL, so report only the underlying cause
+            Err(e) if e.is_connect() => Some(std::error::Error::source(e).map_or_else(|| "connection failed".to_string(), |c| c.to_string())),
+            _ => None,
+        };
+        match retry_reason {
+            Some(reason) if attempt < FETCH_MAX_ATTEMPTS => {
+                if let Some(tx) = status_tx {
+                    let _ = tx.send(PrimitiveStatus::Log {
+                        level: LogLevel::Warn,
+                        message: format!("Attempt {}/{} for {} failed ({}), retrying", attempt, FETCH_MAX_ATTEMPTS, redact_url(url, redact), reason),
+                    }).await;
+                }
+                tokio::time::sleep(FETCH_RETRY_BACKOFF * attempt).await;
+                attempt += 1;
+            }
+            _ => return result.map_err(|e| classify_fetch_error(e, &redact_url(url, redact), read_timeout)),
+        }
+    }
+}
+
+/// Where a followed redirect points, if `resp` is one.
+fn redirect_target(resp: &reqwest::Response, redact: &[String]) -> Option<Result<reqwest::Url>> {
+    use reqwest::StatusCode;
+    let redirect = matches!(resp.status(),
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER
+        | StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT);
+    if !redirect {
+        return None;
+    }
+    let location = resp.headers().get(reqwest::header::LOCATION)?;
+    Some(location.to_str().map_err(anyhow::Error::from)
+        .and_then(|l| resp.url().join(l).map_err(anyhow::Error::from))
+        .map_err(|e| anyhow!("Invalid redirect Location from {}: {}", redact_url(resp.url(), redact), e)))
+}
+
 #[derive(Debug)]
 pub struct FetchUrl;
 
@@ -25,6 +279,87 @@ impl Primitive for FetchUrl {
                 mime_type: "text/plain".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "method".to_string(),
+                description: "HTTP method (default GET)".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some("GET".to_string()),
+            },
+            InputDef {
+                name: "headers".to_string(),
+                description: "Request headers as a JSON object of name -> value".to_string(),
+                mime_type: "application/json".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "body".to_string(),
+                description: "Request body, inline or as an artifact; not allowed for GET or HEAD".to_string(),
+                mime_type: "*/*".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "max_bytes".to_string(),
+                description: "Reject responses larger than this many bytes".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "timeout_secs".to_string(),
+                description: format!("Give up once the server sends nothing for this many seconds (default FETCH_READ_TIMEOUT_SECS, or {})", DEFAULT_READ_TIMEOUT_SECS),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "expect_content_type".to_string(),
+                description: "Required response Content-Type (e.g. 'application/json' or 'text/*')".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "raw".to_string(),
+                description: "If 'true', store the body verbatim instead of decoding its Content-Encoding".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "cache".to_string(),
+                description: "If 'true', revalidate with ETag/Last-Modified and reuse the previous artifact on 304".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "follow_redirects".to_string(),
+                description: "If 'false', store a redirect response itself instead of following it (default true)".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "max_redirects".to_string(),
+                description: format!("Fail after following this many redirects (default {})", DEFAULT_MAX_REDIRECTS),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_MAX_REDIRECTS.to_string()),
             }
         ]
     }
@@ -35,6 +370,11 @@ impl Primitive for FetchUrl {
                 name: "content".to_string(),
                 description: "The fetched content body".to_string(),
                 mime_type: "*/*".to_string(), // Could be anything
+            },
+            OutputDef {
+                name: "final_url".to_string(),
+                description: "The URL the content was actually served from, after redirects".to_string(),
+                mime_type: "text/plain".to_string(),
             }
         ]
     }
@@ -45,6 +385,7 @@ impl Primitive for FetchUrl {
         context: ExecutionContext<'_>,
         status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         if let Some(tx) = &status_tx {
             let _ = tx.send(PrimitiveStatus::Starting).await;
         }
@@ -61,27 +402,165 @@ impl Primitive for FetchUrl {
             PrimitiveInput::ArtifactPath(_) => return Err(anyhow!("FetchUrl expects inline value for url, not artifact path")),
         };
 
+        let max_bytes = optional_value(&inputs, "max_bytes")?
+            .map(|v| v.trim().parse::<u64>().map_err(|_| anyhow!("Input 'max_bytes' must be a non-negative integer, got '{}'", v)))
+            .transpose()?;
+        let expect_content_type = optional_value(&inputs, "expect_content_type")?;
+        let raw = optional_flag(&inputs, "raw", false)?;
+        let follow_redirects = optional_flag(&inputs, "follow_redirects", true)?;
+        let max_redirects = defaulted_value(&inputs, "max_redirects")?;
+        let max_redirects = max_redirects.trim().parse::<usize>()
+            .map_err(|_| anyhow!("Input 'max_redirects' must be a non-negative integer, got '{}'", max_redirects))?;
+        let client = if raw { raw_http_client() } else { http_client() };
+
+        let method_name = defaulted_value(&inputs, "method")?;
+        let method = reqwest::Method::from_bytes(method_name.trim().to_ascii_uppercase().as_bytes())
+            .map_err(|_| anyhow!("Input 'method' is not a valid HTTP method: '{}'", method_name))?;
+        let headers = match optional_value(&inputs, "headers")? {
+            Some(json) => parse_fetch_headers(&json)?,
+            None => reqwest::header::HeaderMap::new(),
+        };
+        let body = match inputs.get("body").and_then(|v| v.first()) {
+            None => None,
+            Some(PrimitiveInput::Value(v)) => Some(bytes::Bytes::from(v.clone())),
+            Some(PrimitiveInput::ArtifactPath(uri)) => {
+                let path = context.file_manager.get_file(uri).await?;
+                Some(bytes::Bytes::from(tokio::fs::read(&path).await?))
+            }
+        };
+        if body.is_some() && matches!(method, reqwest::Method::GET | reqwest::Method::HEAD) {
+            return Err(anyhow!("Input 'body' was given for a {} request, which can't carry one; set 'method' (e.g. POST)", method));
+        }
+        let use_cache = optional_flag(&inputs, "cache", false)?;
+        if use_cache && method != reqwest::Method::GET {
+            return Err(anyhow!("Input 'cache' only applies to GET requests, not {}", method));
+        }
+
+        // The artifact is keyed by URL and headers other than credentials (plus method and
+        // body when not a GET); with `cache`, its validators live in a sidecar beside it.
+        let mut key = md5::Context::new();
+        if method != reqwest::Method::GET {
+            key.consume(method.as_str());
+            key.consume(b"\0");
+            key.consume(body.as_deref().unwrap_or_default());
+            key.consume(b"\0");
+        }
+        key.consume(&url);
+        let mut sorted_headers: Vec<(&str, &[u8])> = headers.iter()
+            .filter(|(name, _)| !CREDENTIAL_HEADERS.contains(name))
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+            .collect();
+        sorted_headers.sort();
+        for (name, value) in sorted_headers {
+            key.consume(b"\0");
+            key.consume(name);
+            key.consume(b":");
+            key.consume(value);
+        }
+        let filename = format!("fetch_{}", key.compute().iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        let mut request = FetchRequest { method, headers, body };
+        let cache_key = format!("{}.cache.json", filename);
+        let cached = if use_cache {
+            Some(load_fetch_cache(&cache_key, &filename, raw, &context.file_manager).await?)
+        } else {
+            None
+        };
+        let validators = cached.as_ref().and_then(|c| c.as_ref());
+
+        let mut target = reqwest::Url::parse(&url).map_err(|e| anyhow!("Input 'url' is not a valid URL: {}", e))?;
+        // Only this form of the URL is reported or stored.
+        let shown_url = redact_url(&target, context.redact);
         if let Some(tx) = &status_tx {
-            let _ = tx.send(PrimitiveStatus::Progress(0.1, format!("Fetching {}", url))).await;
+            let _ = tx.send(PrimitiveStatus::Progress(0.1, format!("Fetching {}", shown_url))).await;
         }
-        
-        // Prepare output
-        let temp_path = context.file_manager.prepare_output("fetch_url_temp").await?;
 
-        // Real implementation using reqwest
-        let resp = reqwest::get(&url).await?;
-        let bytes = resp.bytes().await?;
-        
-        tokio::fs::write(&temp_path, &bytes).await?;
-        
-        // Determine predictable filename or hash for artifact URI if needed, 
-        // or just let file_manager decide. 
-        // We used MD5 of URL before.
-        let filename = format!("fetch_{}", md5::compute(&url).iter().map(|b| format!("{:02x}", b)).collect::<String>());
+        // Real implementation using reqwest, retrying transient failures per request and
+        // following redirects here rather than in the client.
+        let read_timeout = read_timeout_input(&inputs)?;
+        let mut redirects = 0;
+        let mut resp = loop {
+            let resp = send_with_retry(client, &target, &request, read_timeout, validators, context.redact, &status_tx).await?;
+            let Some(next) = redirect_target(&resp, context.redact).filter(|_| follow_redirects) else {
+                break resp;
+            };
+            if redirects == max_redirects {
+                return Err(anyhow!("Too many redirects fetching {}: exceeded max_redirects ({})", shown_url, max_redirects));
+            }
+            redirects += 1;
+            let next = next?;
+            request.redirected(resp.status(), &target, &next);
+            target = next;
+            if let Some(tx) = &status_tx {
+                let _ = tx.send(PrimitiveStatus::Log {
+                    level: LogLevel::Info,
+                    message: format!("{} redirected to {}", redact_url(resp.url(), context.redact), redact_url(&target, context.redact)),
+                }).await;
+            }
+        };
+        let final_url = redact_url(resp.url(), context.redact);
+        if let Some(entry) = validators.filter(|_| resp.status() == reqwest::StatusCode::NOT_MODIFIED) {
+            if let Some(tx) = &status_tx {
+                let _ = tx.send(PrimitiveStatus::Log {
+                    level: LogLevel::Info,
+                    message: format!("{} not modified; reusing {}", shown_url, entry.artifact_uri),
+                }).await;
+                let _ = tx.send(PrimitiveStatus::Completed).await;
+            }
+            return Ok(vec![
+                PrimitiveOutput {
+                    name: "content".to_string(),
+                    artifact_path: entry.artifact_uri.clone(),
+                },
+                commit_final_url(&context, &filename, &final_url).await?,
+            ]);
+        }
+        let header = |name: reqwest::header::HeaderName| resp.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let (etag, last_modified) = (header(reqwest::header::ETAG), header(reqwest::header::LAST_MODIFIED));
+
+        // Validate headers before reading any of the body.
+        if let Some(expected) = &expect_content_type {
+            let actual = resp.headers().get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            if !content_type_matches(expected, actual) {
+                return Err(anyhow!("Unexpected Content-Type from {}: expected '{}', got '{}'", shown_url, expected, actual));
+            }
+        }
+        if let (Some(limit), Some(len)) = (max_bytes, resp.content_length()) {
+            if len > limit {
+                return Err(anyhow!("Response from {} is {} bytes, exceeding max_bytes {}", shown_url, len, limit));
+            }
+        }
 
+        // Prepare output and stream the body into it, enforcing the limit as bytes arrive
+        // (Content-Length may be absent or wrong, and is dropped when the body is decoded,
+        // in which case the limit applies to the decoded size).
+        let temp_path = context.file_manager.prepare_output("fetch_url_temp").await?;
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        let mut received: u64 = 0;
+        while let Some(chunk) = within_read_timeout(read_timeout, &shown_url, resp.chunk()).await? {
+            received += chunk.len() as u64;
+            if let Some(limit) = max_bytes {
+                if received > limit {
+                    drop(file);
+                    let _ = tokio::fs::remove_file(&temp_path).await;
+                    return Err(anyhow!("Response from {} exceeded max_bytes {}", shown_url, limit));
+                }
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        
         // Commit artifact
         let artifact_uri = context.file_manager.commit_output(&filename, &temp_path).await?; // Use filename as hint
 
+        if cached.is_some() {
+            let entry = FetchCacheEntry { etag, last_modified, raw, artifact_uri: artifact_uri.clone() };
+            let cache_path = context.file_manager.prepare_output(&cache_key).await?;
+            tokio::fs::write(&cache_path, serde_json::to_vec(&entry)?).await?;
+            context.file_manager.commit_output(&cache_key, &cache_path).await?;
+        }
+
         if let Some(tx) = &status_tx {
             let _ = tx.send(PrimitiveStatus::Completed).await;
         }
@@ -90,11 +569,148 @@ impl Primitive for FetchUrl {
             PrimitiveOutput {
                 name: "content".to_string(),
                 artifact_path: artifact_uri,
+            },
+            commit_final_url(&context, &filename, &final_url).await?,
+        ])
+    }
+}
+
+/// Commits `FetchUrl`'s `final_url` output (already redacted) beside the content artifact.
+async fn commit_final_url(context: &ExecutionContext<'_>, filename: &str, final_url: &str) -> Result<PrimitiveOutput> {
+    let key = format!("{}.url", filename);
+    let temp_path = context.file_manager.prepare_output(&key).await?;
+    tokio::fs::write(&temp_path, final_url).await?;
+    Ok(PrimitiveOutput {
+        name: "final_url".to_string(),
+        artifact_path: context.file_manager.commit_output(&key, &temp_path).await?,
+    })
+}
+
+#[derive(Debug)]
+pub struct HttpUpload;
+
+#[async_trait]
+impl Primitive for HttpUpload {
+    fn name(&self) -> &str {
+        "HttpUpload"
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef { name: "url".to_string(), description: "Upload endpoint".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "file".to_string(), description: "File to upload".to_string(), mime_type: "*/*".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "field_name".to_string(), description: "Form field name for the file part".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "fields".to_string(), description: "Extra form fields as a JSON object of name -> value".to_string(), mime_type: "application/json".to_string(), min_count: 0, max_count: Some(1), default: None },
+            InputDef { name: "timeout_secs".to_string(), description: format!("Give up if sending the file and getting the response head, or any later read, takes longer than this many seconds (default FETCH_READ_TIMEOUT_SECS, or {})", DEFAULT_READ_TIMEOUT_SECS), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef { name: "response".to_string(), description: "The response body".to_string(), mime_type: "*/*".to_string() }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        if let Some(tx) = &status_tx {
+            let _ = tx.send(PrimitiveStatus::Starting).await;
+        }
+
+        let url = optional_value(&inputs, "url")?.ok_or_else(|| anyhow!("Missing required input 'url'"))?;
+        let target = reqwest::Url::parse(&url).map_err(|e| anyhow!("Input 'url' is not a valid URL: {}", e))?;
+        // Only this form of the URL is reported
+        let shown_url = redact_url(&target, context.redact);
+        let read_timeout = read_timeout_input(&inputs)?;
+        let field_name = optional_value(&inputs, "field_name")?.ok_or_else(|| anyhow!("Missing required input 'field_name'"))?;
+        let file_uri = match inputs.get("file").and_then(|v| v.first()) {
+            Some(PrimitiveInput::ArtifactPath(p)) => p.clone(),
+            Some(PrimitiveInput::Value(_)) => return Err(anyhow!("HttpUpload expects artifact path for file")),
+            None => return Err(anyhow!("Missing required input 'file'")),
+        };
+
+        let mut form = reqwest::multipart::Form::new();
+        if let Some(fields) = optional_value(&inputs, "fields")? {
+            let fields: HashMap<String, String> = serde_json::from_str(&fields)
+                .map_err(|e| anyhow!("Input 'fields' must be a JSON object of strings: {}", e))?;
+            for (name, value) in fields {
+                form = form.text(name, value);
+            }
+        }
+
+        // A dry run stops once the inputs check out: nothing is read or sent
+        if !context.commit() {
+            if let Some(tx) = &status_tx {
+                let _ = tx.send(PrimitiveStatus::Completed).await;
+            }
+            return Ok(vec![
+                PrimitiveOutput {
+                    name: "response".to_string(),
+                    artifact_path: ContextFileManager::dry_run_uri("http_upload_response"),
+                }
+            ]);
+        }
+
+        // Stream the file rather than buffering it
+        let local_path = context.file_manager.get_file(&file_uri).await?;
+        let file = tokio::fs::File::open(&local_path).await?;
+        let length = file.metadata().await?.len();
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(file));
+        let file_name = local_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "upload".to_string());
+        form = form.part(field_name, reqwest::multipart::Part::stream_with_length(body, length).file_name(file_name));
+
+        if let Some(tx) = &status_tx {
+            let _ = tx.send(PrimitiveStatus::Progress(0.1, format!("Uploading to {}", shown_url))).await;
+        }
+
+        // The stream can't be replayed, so redirects are not followed and nothing is retried
+        let request = http_client().post(target).multipart(form).send();
+        let mut resp = within_read_timeout(read_timeout, &shown_url, request).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Upload to {} failed with HTTP {}", shown_url, resp.status()));
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = within_read_timeout(read_timeout, &shown_url, resp.chunk()).await? {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let temp_path = context.file_manager.prepare_output("http_upload_response").await?;
+        tokio::fs::write(&temp_path, &bytes).await?;
+        let artifact_uri = context.file_manager.commit_output("http_upload_response", &temp_path).await?;
+
+        if let Some(tx) = &status_tx {
+            let _ = tx.send(PrimitiveStatus::Completed).await;
+        }
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "response".to_string(),
+                artifact_path: artifact_uri,
             }
         ])
     }
 }
 
+/// Optional `region` / `endpoint` inputs shared by `S3Get` and `S3Put`.
+fn s3_override_inputs() -> Vec<InputDef> {
+    vec![
+        InputDef { name: "region".to_string(), description: "Bucket region (defaults to the ambient AWS region)".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+        InputDef { name: "endpoint".to_string(), description: "S3-compatible endpoint URL, e.g. minio (uses path-style addressing)".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+    ]
+}
+
+/// Builds the client for an `S3Get` / `S3Put` invocation from its optional overrides.
+async fn s3_client_for(inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<aws_sdk_s3::Client> {
+    let region = optional_value(inputs, "region")?;
+    let endpoint = optional_value(inputs, "endpoint")?;
+    Ok(s3_client_with(region.as_deref(), endpoint.as_deref()).await)
+}
+
 #[derive(Debug)]
 pub struct S3Get;
 
@@ -106,9 +722,10 @@ impl Primitive for S3Get {
 
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
-            InputDef { name: "bucket".to_string(), description: "Bucket name".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1) },
-            InputDef { name: "key".to_string(), description: "Object key".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1) },
-        ]
+            InputDef { name: "bucket".to_string(), description: "Bucket name".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "key".to_string(), description: "Object key".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "mode".to_string(), description: "'ingest' (default) copies the object into internal storage; 'reference' returns its s3:// URI as-is".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: Some("ingest".to_string()) },
+        ].into_iter().chain(s3_override_inputs()).collect()
     }
 
     fn output_schema(&self) -> Vec<OutputDef> {
@@ -119,12 +736,33 @@ impl Primitive for S3Get {
 
     async fn execute(
         &self,
-        inputs: HashMap<String, Vec<PrimitiveInput>>,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         let bucket = match &inputs["bucket"][0] { PrimitiveInput::Value(s) => s, _ => return Err(anyhow!("Invalid input")) };
         let key = match &inputs["key"][0] { PrimitiveInput::Value(s) => s, _ => return Err(anyhow!("Invalid input")) };
+        let reference = match defaulted_value(&inputs, "mode")?.as_str() {
+            "ingest" => false,
+            "reference" => true,
+            other => return Err(anyhow!("Input 'mode' must be 'ingest' or 'reference', got '{}'", other)),
+        };
+
+        // Pass-through: downstream primitives read the source object via their FileManager,
+        // so nothing is downloaded or copied.
+        if reference {
+            // An s3:// URI can't say which region or endpoint it was meant for
+            if let Some(name) = ["region", "endpoint"].into_iter().find(|name| inputs.contains_key(*name)) {
+                return Err(anyhow!("Input '{}' can't be used with mode 'reference', which is read with the worker's own S3 settings", name));
+            }
+            return Ok(vec![
+                PrimitiveOutput {
+                    name: "file".to_string(),
+                    artifact_path: format!("s3://{}/{}", bucket, key),
+                }
+            ]);
+        }
 
         // We want to IMPORT from external S3 to our system.
         // We can use get_file on "s3://bucket/key" if FileManager supports generic S3.
@@ -155,8 +793,7 @@ impl Primitive for S3Get {
         
         // Let's just do manual download (Ingest) -> Commit.
         
-        let config = aws_config::load_from_env().await;
-        let client = aws_sdk_s3::Client::new(&config);
+        let client = s3_client_for(&inputs).await?;
         
         let temp_path = context.file_manager.prepare_output("s3_import_temp").await?;
         
@@ -180,6 +817,32 @@ impl Primitive for S3Get {
     }
 }
 
+/// Content type for an object key, by extension. `None` leaves it to S3's default.
+fn content_type_for_key(key: &str) -> Option<&'static str> {
+    let ext = std::path::Path::new(key).extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "parquet" => "application/vnd.apache.parquet",
+        _ => return None,
+    })
+}
+
 #[derive(Debug)]
 pub struct S3Put;
 
@@ -191,10 +854,12 @@ impl Primitive for S3Put {
 
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
-            InputDef { name: "file".to_string(), description: "File to upload".to_string(), mime_type: "*/*".to_string(), min_count: 1, max_count: Some(1) },
-            InputDef { name: "bucket".to_string(), description: "Dest Bucket".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1) },
-            InputDef { name: "key".to_string(), description: "Dest Key".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1) },
-        ]
+            InputDef { name: "file".to_string(), description: "File to upload".to_string(), mime_type: "*/*".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "bucket".to_string(), description: "Dest Bucket".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "key".to_string(), description: "Dest Key".to_string(), mime_type: "text/plain".to_string(), min_count: 1, max_count: Some(1), default: None },
+            InputDef { name: "content_type".to_string(), description: "Content-Type for the object (default: inferred from the key's extension)".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+            InputDef { name: "acl".to_string(), description: "Canned ACL, e.g. 'public-read' (default: the bucket's)".to_string(), mime_type: "text/plain".to_string(), min_count: 0, max_count: Some(1), default: None },
+        ].into_iter().chain(s3_override_inputs()).collect()
     }
 
     fn output_schema(&self) -> Vec<OutputDef> {
@@ -207,6 +872,7 @@ impl Primitive for S3Put {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
          let file_input = inputs.remove("file").ok_or_else(|| anyhow!("Missing file input"))?;
          let file_path_str = match &file_input[0] {
              PrimitiveInput::ArtifactPath(p) => p,
@@ -215,13 +881,31 @@ impl Primitive for S3Put {
          
          let bucket = match &inputs["bucket"][0] { PrimitiveInput::Value(s) => s, _ => return Err(anyhow!("Invalid input bucket")) };
          let key = match &inputs["key"][0] { PrimitiveInput::Value(s) => s, _ => return Err(anyhow!("Invalid input key")) };
+         let content_type = optional_value(&inputs, "content_type")?
+             .map(|v| v.trim().to_string())
+             .filter(|v| !v.is_empty())
+             .or_else(|| content_type_for_key(key).map(str::to_string));
+         let acl = optional_value(&inputs, "acl")?
+             .map(|v| {
+                 let v = v.trim();
+                 if ObjectCannedAcl::values().contains(&v) {
+                     Ok(ObjectCannedAcl::from(v))
+                 } else {
+                     Err(anyhow!("Input 'acl' must be one of {}, got '{}'", ObjectCannedAcl::values().join(", "), v))
+                 }
+             })
+             .transpose()?;
+
+         // A dry run stops once the inputs check out: nothing is read or uploaded
+         if !context.commit() {
+             return Ok(vec![]);
+         }
 
          // Get local path of the artifact to upload
          let local_path = context.file_manager.get_file(file_path_str).await?;
          
          // Export to external S3
-         let config = aws_config::load_from_env().await;
-         let client = aws_sdk_s3::Client::new(&config);
+         let client = s3_client_for(&inputs).await?;
          
          let body = aws_sdk_s3::primitives::ByteStream::from_path(&local_path).await?;
          
@@ -229,6 +913,8 @@ impl Primitive for S3Put {
             .bucket(bucket)
             .key(key)
             .body(body)
+            .set_content_type(content_type)
+            .set_acl(acl)
             .send()
             .await?;
 
@@ -0,0 +1,85 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, prepare_inputs};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+
+#[derive(Debug)]
+pub struct ImageMetadata;
+
+#[async_trait]
+impl Primitive for ImageMetadata {
+    fn name(&self) -> &str {
+        "ImageMetadata"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "image".to_string(),
+                description: "Image file (PNG, JPEG, GIF, WebP, BMP)".to_string(),
+                mime_type: "image/*".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "metadata".to_string(),
+                description: "JSON with width, height, format and color_type".to_string(),
+                mime_type: "application/json".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let image_input = inputs.remove("image").ok_or_else(|| anyhow!("Missing image input"))?;
+        let uri = match image_input.first() {
+            Some(PrimitiveInput::ArtifactPath(p)) => p.clone(),
+            Some(PrimitiveInput::Value(_)) => return Err(anyhow!("ImageMetadata expects artifact path for image")),
+            None => return Err(anyhow!("Missing image input")),
+        };
+        let local_path = context.file_manager.get_file(&uri).await?;
+
+        // Only the header is parsed; pixel data is never decoded.
+        let metadata = tokio::task::spawn_blocking(move || -> Result<serde_json::Value> {
+            let reader = image::ImageReader::open(&local_path)?.with_guessed_format()?;
+            let format = reader.format()
+                .ok_or_else(|| anyhow!("Unsupported or unrecognized image format: {}", uri))?;
+            let decoder = reader.into_decoder()
+                .map_err(|e| anyhow!("Cannot read {:?} header of {}: {}", format, uri, e))?;
+            let (width, height) = image::ImageDecoder::dimensions(&decoder);
+            Ok(serde_json::json!({
+                "width": width,
+                "height": height,
+                "format": format!("{:?}", format).to_lowercase(),
+                "color_type": format!("{:?}", image::ImageDecoder::color_type(&decoder)),
+            }))
+        }).await??;
+
+        let temp_path = context.file_manager.prepare_output("image_metadata.json").await?;
+        tokio::fs::write(&temp_path, serde_json::to_string(&metadata)?).await?;
+        let artifact_uri = context.file_manager.commit_output("image_metadata.json", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "metadata".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
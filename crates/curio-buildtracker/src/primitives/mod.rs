@@ -1,27 +1,68 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 use async_trait::async_trait;
-use std::collections::HashMap;
-use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
 use tokio::sync::mpsc;
+use tokio::io::AsyncReadExt;
 use std::fmt::Debug;
 
 pub mod io;
 pub mod transform;
 pub mod aggregate;
 pub mod csv;
+pub mod media;
+pub mod html;
+pub mod command;
+pub mod compress;
+pub mod checksum;
+pub mod secrets;
+pub mod status;
 
-use crate::file_manager::FileManager;
+use crate::file_manager::{ContextFileManager, FileManager};
 
-pub use io::{FetchUrl, S3Get, S3Put};
-pub use transform::{JsonSelect, TemplateRender};
-pub use aggregate::{MergeJson, Concatenate};
-pub use csv::{CsvSelect, CsvSql};
+pub use io::{FetchUrl, HttpUpload, S3Get, S3Put};
+pub use transform::{JsonSelect, JsonFlatten, JsonUnflatten, TemplateRender, HandlebarsRender};
+pub use aggregate::{MergeJson, MergeYaml, Concatenate};
+pub use csv::{CsvSelect, CsvSql, MergeCsv};
+pub use media::ImageMetadata;
+pub use html::{DecodeHtmlEntities, EncodeHtmlEntities, HtmlToText};
+pub use command::RunCommand;
+pub use compress::{Gzip, Gunzip};
+pub use checksum::Sha256Checksum;
+pub use secrets::{with_secrets, resolve_secrets, SecretSource, EnvSecretSource, AwsSecretSource, WithSecrets};
+pub use status::{ExecutionState, StatusSnapshot, StatusTracker};
 
 mod tests;
 
 /// Context passed to primitive execution.
 pub struct ExecutionContext<'a> {
-    pub file_manager: &'a dyn FileManager,
+    pub file_manager: ContextFileManager<'a>,
+    /// Secret values substituted into this invocation's inputs. Primitives that echo
+    /// input-derived text (URLs, messages) must keep these out of it.
+    pub redact: &'a [String],
+}
+
+impl<'a> ExecutionContext<'a> {
+    /// A context whose outputs are committed through `file_manager`.
+    pub fn new(file_manager: &'a dyn FileManager) -> Self {
+        Self { file_manager: ContextFileManager::new(file_manager, true), redact: &[] }
+    }
+
+    /// With `commit` false the primitive runs end-to-end, but its outputs are discarded
+    /// and reported under `dryrun://` URIs instead of being persisted.
+    pub fn with_commit(self, commit: bool) -> Self {
+        Self { file_manager: ContextFileManager::new(self.file_manager.inner(), commit), ..self }
+    }
+
+    /// The same context, with `secrets` to keep out of anything the primitive reports.
+    pub fn redacting<'b>(self, secrets: &'b [String]) -> ExecutionContext<'b> where 'a: 'b {
+        ExecutionContext { file_manager: self.file_manager, redact: secrets }
+    }
+
+    pub fn commit(&self) -> bool {
+        self.file_manager.commits()
+    }
 }
 
 
@@ -33,6 +74,8 @@ pub struct InputDef {
     pub mime_type: String,   // E.g. "application/json" or "text/*"
     pub min_count: usize,    // 0 = optional, 1 = required, >1 = array
     pub max_count: Option<usize>, // None = unlimited
+    /// Value supplied by `prepare_inputs` when the input is omitted.
+    pub default: Option<String>,
 }
 
 /// Represents a definition of an output produced by the primitive.
@@ -43,6 +86,19 @@ pub struct OutputDef {
     pub mime_type: String,
 }
 
+impl OutputDef {
+    /// Whether a produced output `name` is declared by this definition. A `{n}` in the
+    /// declared name stands for an index (e.g. `item_{n}` declares `item_0`, `item_1`, ...).
+    pub fn matches(&self, name: &str) -> bool {
+        match self.name.split_once("{n}") {
+            Some((prefix, suffix)) => name.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(suffix))
+                .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit())),
+            None => self.name == name,
+        }
+    }
+}
+
 /// Represents the actual input data passed to execution.
 #[derive(Debug, Clone)]
 pub enum PrimitiveInput {
@@ -57,15 +113,244 @@ pub struct PrimitiveOutput {
     pub artifact_path: String,
 }
 
+/// Severity of a `PrimitiveStatus::Log` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
 /// Status updates sent during execution.
 #[derive(Debug, Clone)]
 pub enum PrimitiveStatus {
     Starting,
     Progress(f32, String),
+    /// Noteworthy event (a retry, a skipped record) distinct from percentage progress.
+    Log { level: LogLevel, message: String },
     Completed,
     Failed(String),
 }
 
+/// Typed failures a primitive can return (wrapped in `anyhow::Error`) so callers
+/// can tell them apart with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrimitiveError {
+    /// A remote operation did not complete within its configured window.
+    Timeout { target: String, after: std::time::Duration },
+    /// Two inputs set the same key (dotted path) to different scalar values.
+    MergeConflict { path: String },
+    /// An artifact's sniffed content type does not satisfy the input's declared `mime_type`.
+    TypeMismatch { input: String, expected: String, actual: String },
+}
+
+impl std::fmt::Display for PrimitiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrimitiveError::Timeout { target, after } => write!(f, "Timed out after {:?} waiting for {}", after, target),
+            PrimitiveError::MergeConflict { path } => write!(f, "Conflicting values for key '{}'", path),
+            PrimitiveError::TypeMismatch { input, expected, actual } => {
+                write!(f, "Input '{}' expects {} but the supplied artifact looks like {}", input, expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrimitiveError {}
+
+impl PrimitiveError {
+    /// Whether the failure is transient, so re-running the primitive may succeed
+    /// (see `execute_with_retry`).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PrimitiveError::Timeout { .. } => true,
+            PrimitiveError::MergeConflict { .. } | PrimitiveError::TypeMismatch { .. } => false,
+        }
+    }
+}
+
+/// How `execute_with_retry` re-runs a primitive after a retryable failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for each one after.
+    pub initial_backoff: std::time::Duration,
+}
+
+/// Returns the inline value of an optional single-valued input, if supplied.
+pub(crate) fn optional_value(inputs: &HashMap<String, Vec<PrimitiveInput>>, name: &str) -> Result<Option<String>> {
+    match inputs.get(name).and_then(|v| v.first()) {
+        None => Ok(None),
+        Some(PrimitiveInput::Value(s)) => Ok(Some(s.clone())),
+        Some(PrimitiveInput::ArtifactPath(_)) => Err(anyhow!("Input '{}' must be an inline value", name)),
+    }
+}
+
+/// Parses an optional boolean flag input ("true"/"false"), falling back to `default` when absent.
+pub(crate) fn optional_flag(inputs: &HashMap<String, Vec<PrimitiveInput>>, name: &str, default: bool) -> Result<bool> {
+    match optional_value(inputs, name)? {
+        None => Ok(default),
+        Some(v) => v.trim().parse::<bool>().map_err(|_| anyhow!("Input '{}' must be 'true' or 'false', got '{}'", name, v)),
+    }
+}
+
+/// Returns the inline value of an input whose `InputDef` declares a `default`, which
+/// `prepare_inputs` has filled in if the caller omitted it.
+pub(crate) fn defaulted_value(inputs: &HashMap<String, Vec<PrimitiveInput>>, name: &str) -> Result<String> {
+    optional_value(inputs, name)?.ok_or_else(|| anyhow!("Input '{}' has no value and no default was applied", name))
+}
+
+/// Returns an inline value, or the contents of an artifact input.
+pub(crate) async fn read_input_text(input: &PrimitiveInput, context: &ExecutionContext<'_>) -> Result<String> {
+    match input {
+        PrimitiveInput::Value(s) => Ok(s.clone()),
+        PrimitiveInput::ArtifactPath(p) => {
+            let local = context.file_manager.get_file(p).await?;
+            Ok(tokio::fs::read_to_string(local).await?)
+        }
+    }
+}
+
+/// Compares a content type against an expected one, ignoring parameters
+/// (`; charset=...`) and case. `type/*` matches any subtype and `*/*` matches anything.
+pub(crate) fn content_type_matches(expected: &str, actual: &str) -> bool {
+    let essence = |s: &str| s.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let (expected, actual) = (essence(expected), essence(actual));
+    match expected.strip_suffix("/*") {
+        Some("*") => true,
+        Some(prefix) => actual.split('/').next() == Some(prefix),
+        None => expected == actual,
+    }
+}
+
+/// Guesses a content type from the leading bytes of a file. Recognises a handful of
+/// binary formats by magic number; anything else is `text/plain` if it decodes as
+/// UTF-8 and `application/octet-stream` otherwise.
+fn sniff_content_type(head: &[u8]) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => "text/plain",
+        // A multi-byte character cut off at the end of the sample is still text
+        Err(e) if e.error_len().is_none() => "text/plain",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+/// Whether a sniffed type is acceptable for a declared one. Sniffing can only tell
+/// text from binary, so `text/plain` is accepted for any textual declared type.
+fn sniffed_type_satisfies(declared: &str, sniffed: &str) -> bool {
+    if content_type_matches(declared, sniffed) {
+        return true;
+    }
+    let declared = declared.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let textual = declared.starts_with("text/")
+        || declared.ends_with("+json")
+        || declared.ends_with("+xml")
+        || matches!(declared.as_str(), "application/json" | "application/xml" | "application/yaml" | "application/javascript");
+    sniffed == "text/plain" && textual
+}
+
+/// Fills each omitted input that declares a `default` with that value, so `execute` sees
+/// every defaulted input. Supplied inputs are left alone.
+pub fn apply_input_defaults(schema: &[InputDef], inputs: &mut HashMap<String, Vec<PrimitiveInput>>) {
+    for def in schema {
+        if let Some(default) = &def.default {
+            inputs.entry(def.name.clone()).or_insert_with(|| vec![PrimitiveInput::Value(default.clone())]);
+        }
+    }
+}
+
+/// Whether `name` is a single path component: not empty, `.` or `..`, and free of path
+/// separators. Names from inputs must pass before being joined onto a directory or used
+/// as an output key, or they could point outside it.
+pub fn is_plain_filename(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+/// Applies `schema`'s defaults to `inputs` and then checks their counts. Every primitive
+/// calls this first in `execute`, so defaulted inputs are always present and a missing
+/// input is an error, not a panic.
+pub fn prepare_inputs(schema: &[InputDef], inputs: &mut HashMap<String, Vec<PrimitiveInput>>) -> Result<()> {
+    apply_input_defaults(schema, inputs);
+    check_input_counts(schema, inputs)
+}
+
+/// Checks that each input declared in `schema` has between `min_count` and `max_count`
+/// values, naming the first one that doesn't. Undeclared inputs are not looked at, so
+/// primitives taking free-form inputs (tables, partials, files) can call it too.
+pub fn check_input_counts(schema: &[InputDef], inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<()> {
+    for def in schema {
+        let supplied = inputs.get(&def.name).map_or(0, Vec::len);
+        if supplied < def.min_count {
+            return Err(anyhow!("Input '{}' requires at least {} value(s), got {}", def.name, def.min_count, supplied));
+        }
+        if let Some(max) = def.max_count {
+            if supplied > max {
+                return Err(anyhow!("Input '{}' accepts at most {} value(s), got {}", def.name, max, supplied));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks `inputs` against `primitive`'s input schema: every supplied input must be
+/// declared (unless the primitive `accepts_undeclared_inputs`), and counts must respect
+/// `min_count`/`max_count`. With `sniff_types`, each
+/// artifact input is also fetched and its leading bytes compared against the declared
+/// `mime_type`, failing with `PrimitiveError::TypeMismatch` on a mismatch.
+pub async fn validate_inputs(
+    primitive: &dyn Primitive,
+    inputs: &mut HashMap<String, Vec<PrimitiveInput>>,
+    file_manager: &dyn FileManager,
+    sniff_types: bool,
+) -> Result<()> {
+    let schema = primitive.input_schema();
+    if !primitive.accepts_undeclared_inputs() {
+        if let Some(unknown) = inputs.keys().find(|k| !schema.iter().any(|d| &d.name == *k)) {
+            return Err(anyhow!("{} has no input named '{}'", primitive.name(), unknown));
+        }
+    }
+    prepare_inputs(&schema, inputs).map_err(|e| anyhow!("{}: {}", primitive.name(), e))?;
+    if !sniff_types {
+        return Ok(());
+    }
+
+    for def in &schema {
+        let supplied = inputs.get(&def.name).map(|v| v.as_slice()).unwrap_or_default();
+        for input in supplied {
+            let PrimitiveInput::ArtifactPath(uri) = input else { continue };
+            let path = file_manager.get_file(uri).await?;
+            let mut head = Vec::with_capacity(512);
+            tokio::fs::File::open(&path).await?.take(512).read_to_end(&mut head).await?;
+            let sniffed = sniff_content_type(&head);
+            if !sniffed_type_satisfies(&def.mime_type, sniffed) {
+                return Err(PrimitiveError::TypeMismatch {
+                    input: def.name.clone(),
+                    expected: def.mime_type.clone(),
+                    actual: sniffed.to_string(),
+                }.into());
+            }
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 pub trait Primitive: Send + Sync + Debug {
     /// Unique name of the primitive (e.g. "FetchUrl")
@@ -75,6 +360,31 @@ pub trait Primitive: Send + Sync + Debug {
     fn input_schema(&self) -> Vec<InputDef>;
     fn output_schema(&self) -> Vec<OutputDef>;
 
+    /// Whether identical inputs always produce identical outputs, so a previous
+    /// run's outputs can be reused (see `execute_or_skip`).
+    fn idempotent(&self) -> bool {
+        false
+    }
+
+    /// How to retry this primitive on a retryable `PrimitiveError`, if at all.
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Whether inputs not named in `input_schema` are meaningful (tables, partials, files),
+    /// so `validate_inputs` lets them through instead of rejecting them as unknown.
+    fn accepts_undeclared_inputs(&self) -> bool {
+        false
+    }
+
+    /// Values from outside `inputs` that the outputs depend on (e.g. environment variables
+    /// the invocation reads), by name. `execute_or_skip` folds them into the derived output
+    /// id, so an idempotent primitive re-runs when they change.
+    fn ambient_inputs(&self, inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<BTreeMap<String, String>> {
+        let _ = inputs;
+        Ok(BTreeMap::new())
+    }
+
     /// Execution logic.
     /// * `inputs`: Map of argument name -> List of inputs.
     /// * `context`: Execution environment (artifacts, etc).
@@ -84,4 +394,333 @@ pub trait Primitive: Send + Sync + Debug {
         context: ExecutionContext<'_>,
         status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>>;
+
+    /// Runs several independent invocations, returning one output set per entry in `batches`.
+    /// The default executes them one at a time; primitives that can share work across
+    /// invocations (compiled queries, HTTP keep-alive) may override it.
+    async fn execute_batch(
+        &self,
+        batches: Vec<HashMap<String, Vec<PrimitiveInput>>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<Vec<PrimitiveOutput>>> {
+        let mut results = Vec::with_capacity(batches.len());
+        for inputs in batches {
+            let ctx = ExecutionContext { file_manager: context.file_manager, redact: context.redact };
+            results.push(self.execute(inputs, ctx, status_tx.clone()).await?);
+        }
+        Ok(results)
+    }
+}
+
+/// Primitives by `name()`, so a node's type name can be mapped to its implementation.
+/// `default()` holds every built-in primitive.
+#[derive(Debug, Clone)]
+pub struct PrimitiveRegistry {
+    primitives: HashMap<String, Arc<dyn Primitive>>,
+}
+
+impl PrimitiveRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self { primitives: HashMap::new() }
+    }
+
+    /// Adds `primitive` under its `name()`, replacing any primitive of the same name
+    /// (e.g. a built-in wrapped with `instrument` or `with_secrets`).
+    pub fn register(&mut self, primitive: Arc<dyn Primitive>) {
+        self.primitives.insert(primitive.name().to_string(), primitive);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Primitive>> {
+        self.primitives.get(name).cloned()
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.primitives.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Every primitive shipped with the crate, in registration order.
+pub fn builtin_primitives() -> Vec<Arc<dyn Primitive>> {
+    vec![
+        Arc::new(FetchUrl),
+        Arc::new(HttpUpload),
+        Arc::new(S3Get),
+        Arc::new(S3Put),
+        Arc::new(JsonSelect),
+        Arc::new(JsonFlatten),
+        Arc::new(JsonUnflatten),
+        Arc::new(TemplateRender::default()),
+        Arc::new(HandlebarsRender),
+        Arc::new(MergeJson),
+        Arc::new(MergeYaml),
+        Arc::new(Concatenate),
+        Arc::new(CsvSelect),
+        Arc::new(CsvSql),
+        Arc::new(MergeCsv),
+        Arc::new(ImageMetadata),
+        Arc::new(DecodeHtmlEntities),
+        Arc::new(EncodeHtmlEntities),
+        Arc::new(HtmlToText),
+        Arc::new(RunCommand),
+        Arc::new(Gzip),
+        Arc::new(Gunzip),
+        Arc::new(Sha256Checksum),
+    ]
+}
+
+impl Default for PrimitiveRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        for primitive in builtin_primitives() {
+            registry.register(primitive);
+        }
+        registry
+    }
+}
+
+/// Executes `primitive`, re-running the whole `execute` per its `retry_policy` when it
+/// fails with a retryable `PrimitiveError`. Other errors are returned immediately.
+pub async fn execute_with_retry(
+    primitive: &dyn Primitive,
+    inputs: HashMap<String, Vec<PrimitiveInput>>,
+    context: ExecutionContext<'_>,
+    status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+) -> Result<Vec<PrimitiveOutput>> {
+    let Some(policy) = primitive.retry_policy() else {
+        return primitive.execute(inputs, context, status_tx).await;
+    };
+
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        let ctx = ExecutionContext { file_manager: context.file_manager, redact: context.redact };
+        let err = match primitive.execute(inputs.clone(), ctx, status_tx.clone()).await {
+            Ok(outputs) => return Ok(outputs),
+            Err(e) => e,
+        };
+        let retryable = err.downcast_ref::<PrimitiveError>().is_some_and(PrimitiveError::is_retryable);
+        if !retryable || attempt >= policy.max_attempts {
+            return Err(err);
+        }
+
+        if let Some(tx) = &status_tx {
+            let _ = tx.send(PrimitiveStatus::Log {
+                level: LogLevel::Warn,
+                message: format!("Attempt {}/{} of {} failed ({}), retrying", attempt, policy.max_attempts, primitive.name(), err),
+            }).await;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// Whether an instrumented `execute` returned outputs or an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    Success,
+    Failure,
+}
+
+/// Receives per-run timings from primitives wrapped with `instrument`
+/// (e.g. to publish CloudWatch metrics).
+pub trait PrimitiveMetrics: Send + Sync {
+    fn record(&self, primitive: &str, duration: std::time::Duration, outcome: ExecutionOutcome);
+}
+
+/// A primitive whose every `execute` (or `execute_batch`) is timed and reported to a
+/// `PrimitiveMetrics` sink.
+/// It is itself a `Primitive`, so it can be handed to `execute_with_retry` (each attempt
+/// is recorded) or `execute_or_skip` (skipped runs are not).
+pub struct Instrumented<P> {
+    inner: P,
+    metrics: Arc<dyn PrimitiveMetrics>,
+}
+
+/// Wraps `primitive` so its runs are recorded into `metrics`.
+pub fn instrument<P: Primitive>(primitive: P, metrics: Arc<dyn PrimitiveMetrics>) -> Instrumented<P> {
+    Instrumented { inner: primitive, metrics }
+}
+
+impl<P: Debug> Debug for Instrumented<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Instrumented").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<P: Primitive> Primitive for Instrumented<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        self.inner.input_schema()
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        self.inner.output_schema()
+    }
+
+    fn idempotent(&self) -> bool {
+        self.inner.idempotent()
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.inner.retry_policy()
+    }
+
+    fn accepts_undeclared_inputs(&self) -> bool {
+        self.inner.accepts_undeclared_inputs()
+    }
+
+    fn ambient_inputs(&self, inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<BTreeMap<String, String>> {
+        self.inner.ambient_inputs(inputs)
+    }
+
+    async fn execute(
+        &self,
+        inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        let started = std::time::Instant::now();
+        let result = self.inner.execute(inputs, context, status_tx).await;
+        let outcome = if result.is_ok() { ExecutionOutcome::Success } else { ExecutionOutcome::Failure };
+        self.metrics.record(self.inner.name(), started.elapsed(), outcome);
+        result
+    }
+
+    /// Forwards to the inner primitive's `execute_batch`, so its batching is kept, and
+    /// records the whole batch as one run.
+    async fn execute_batch(
+        &self,
+        batches: Vec<HashMap<String, Vec<PrimitiveInput>>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<Vec<PrimitiveOutput>>> {
+        let started = std::time::Instant::now();
+        let result = self.inner.execute_batch(batches, context, status_tx).await;
+        let outcome = if result.is_ok() { ExecutionOutcome::Success } else { ExecutionOutcome::Failure };
+        self.metrics.record(self.inner.name(), started.elapsed(), outcome);
+        result
+    }
+}
+
+async fn artifact_digest(uri: &str, file_manager: &dyn FileManager) -> Result<String> {
+    let path = file_manager.get_file(uri).await?;
+    Ok(format!("{:x}", md5::compute(tokio::fs::read(&path).await?)))
+}
+
+/// Derives a stable id for a primitive invocation from its name, inputs and ambient inputs.
+/// Artifact inputs contribute their content rather than their path, since outputs
+/// are often committed to fixed paths and overwritten in place.
+async fn derived_output_id(
+    name: &str,
+    inputs: &HashMap<String, Vec<PrimitiveInput>>,
+    ambient: &BTreeMap<String, String>,
+    file_manager: &dyn FileManager,
+) -> Result<String> {
+    let mut keys: Vec<&String> = inputs.keys().collect();
+    keys.sort();
+
+    let mut ctx = md5::Context::new();
+    ctx.consume(name.as_bytes());
+    for key in keys {
+        ctx.consume(b"\0");
+        ctx.consume(key.as_bytes());
+        for input in &inputs[key] {
+            match input {
+                PrimitiveInput::Value(v) => {
+                    ctx.consume(b"\0v");
+                    ctx.consume(v.as_bytes());
+                }
+                PrimitiveInput::ArtifactPath(p) => {
+                    ctx.consume(b"\0a");
+                    ctx.consume(artifact_digest(p, file_manager).await?.as_bytes());
+                }
+            }
+        }
+    }
+    // Nothing is added without ambient inputs, so those ids are unchanged.
+    for (key, value) in ambient {
+        ctx.consume(b"\0e");
+        ctx.consume(key.as_bytes());
+        ctx.consume(b"\0");
+        ctx.consume(value.as_bytes());
+    }
+    Ok(format!("{:x}", ctx.compute()))
+}
+
+/// Executes `primitive`, or for idempotent primitives, returns the outputs of a previous
+/// run with the same derived output id if they were already committed.
+/// A small manifest of (output name, artifact URI, content digest) is committed under
+/// `derived/<id>.json` after each idempotent run; outputs that have since been
+/// overwritten or removed cause a normal re-run.
+pub async fn execute_or_skip(
+    primitive: &dyn Primitive,
+    mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+    context: ExecutionContext<'_>,
+    status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+) -> Result<Vec<PrimitiveOutput>> {
+    // Omitting an input and passing its default are the same invocation
+    apply_input_defaults(&primitive.input_schema(), &mut inputs);
+    // Dry-run outputs can't be digested, and their manifest would never be found again.
+    if !primitive.idempotent() || !context.commit() {
+        return primitive.execute(inputs, context, status_tx).await;
+    }
+
+    let handle = context.file_manager;
+    let file_manager: &dyn FileManager = &handle;
+    let ambient = primitive.ambient_inputs(&inputs)?;
+    let id = derived_output_id(primitive.name(), &inputs, &ambient, file_manager).await?;
+    let manifest_key = format!("derived/{}.json", id);
+
+    if let Some(manifest) = load_manifest(&manifest_key, file_manager).await? {
+        if let Some(tx) = &status_tx {
+            let _ = tx.send(PrimitiveStatus::Log {
+                level: LogLevel::Info,
+                message: format!("Skipping {}: outputs for {} already exist", primitive.name(), id),
+            }).await;
+            let _ = tx.send(PrimitiveStatus::Completed).await;
+        }
+        return Ok(manifest.into_iter()
+            .map(|(name, artifact_path, _)| PrimitiveOutput { name, artifact_path })
+            .collect());
+    }
+
+    let outputs = primitive.execute(inputs, context, status_tx).await?;
+
+    let mut manifest = Vec::with_capacity(outputs.len());
+    for o in &outputs {
+        manifest.push((o.name.as_str(), o.artifact_path.as_str(), artifact_digest(&o.artifact_path, file_manager).await?));
+    }
+    let temp_path = file_manager.prepare_output(&manifest_key).await?;
+    tokio::fs::write(&temp_path, serde_json::to_vec(&manifest)?).await?;
+    // Another worker may have finished the same invocation first; its manifest is equivalent.
+    file_manager.commit_output_exclusive(&manifest_key, &temp_path).await?;
+
+    Ok(outputs)
+}
+
+/// Loads a committed manifest, returning `None` if it is missing or any listed output
+/// no longer matches its recorded digest.
+async fn load_manifest(manifest_key: &str, file_manager: &dyn FileManager) -> Result<Option<Vec<(String, String, String)>>> {
+    let Some(uri) = file_manager.find_output(manifest_key).await? else {
+        return Ok(None);
+    };
+    let path = file_manager.get_file(&uri).await?;
+    let manifest: Vec<(String, String, String)> = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+    for (_, artifact_path, digest) in &manifest {
+        match artifact_digest(artifact_path, file_manager).await {
+            Ok(current) if &current == digest => {}
+            _ => return Ok(None),
+        }
+    }
+    Ok(Some(manifest))
 }
@@ -0,0 +1,186 @@
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, RetryPolicy};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::sync::{Arc, OnceLock};
+use anyhow::{Result, anyhow};
+use tokio::sync::mpsc;
+
+/// Matches a `secret://NAME` reference inside a value input.
+fn secret_reference() -> &'static regex::Regex {
+    static RE: OnceLock<regex::Regex> = OnceLock::new();
+    RE.get_or_init(|| regex::Regex::new(r"secret://([A-Za-z0-9_./:+=@-]+)").unwrap())
+}
+
+/// Where `secret://NAME` references are looked up.
+#[async_trait]
+pub trait SecretSource: Send + Sync {
+    /// Returns the value of secret `name`. Errors must not contain the value.
+    async fn get_secret(&self, name: &str) -> Result<String>;
+}
+
+/// Reads `secret://NAME` from the worker's environment variable `NAME`. Meant for local
+/// runs, where the variables are set by whoever starts the worker.
+#[derive(Debug, Default)]
+pub struct EnvSecretSource;
+
+#[async_trait]
+impl SecretSource for EnvSecretSource {
+    async fn get_secret(&self, name: &str) -> Result<String> {
+        std::env::var(name).map_err(|_| anyhow!("Environment variable '{}' is not set", name))
+    }
+}
+
+/// Reads `secret://ssm:NAME` from SSM Parameter Store (decrypting SecureStrings) and any
+/// other `secret://NAME` from Secrets Manager, where `NAME` is a secret id or ARN whose
+/// `SecretString` is used.
+#[derive(Debug, Clone)]
+pub struct AwsSecretSource {
+    secrets_manager: aws_sdk_secretsmanager::Client,
+    ssm: aws_sdk_ssm::Client,
+}
+
+impl AwsSecretSource {
+    /// Uses the ambient AWS configuration. `SECRETS_ENDPOINT` overrides the service
+    /// endpoint, as `S3_ENDPOINT` does for S3.
+    pub async fn from_env() -> Self {
+        let config = aws_config::load_from_env().await;
+        let source = Self {
+            secrets_manager: aws_sdk_secretsmanager::Client::new(&config),
+            ssm: aws_sdk_ssm::Client::new(&config),
+        };
+        match std::env::var("SECRETS_ENDPOINT") {
+            Ok(endpoint) => source.with_endpoint(&endpoint),
+            Err(_) => source,
+        }
+    }
+
+    /// Sends requests to `endpoint` instead of the regional service endpoints.
+    pub fn with_endpoint(self, endpoint: &str) -> Self {
+        tracing::info!("Using custom secrets endpoint: {}", endpoint);
+        Self {
+            secrets_manager: aws_sdk_secretsmanager::Client::from_conf(
+                self.secrets_manager.config().to_builder().endpoint_url(endpoint).build()),
+            ssm: aws_sdk_ssm::Client::from_conf(self.ssm.config().to_builder().endpoint_url(endpoint).build()),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretSource for AwsSecretSource {
+    async fn get_secret(&self, name: &str) -> Result<String> {
+        let value = if let Some(parameter) = name.strip_prefix("ssm:") {
+            let resp = self.ssm.get_parameter().name(parameter).with_decryption(true).send().await
+                .map_err(|e| anyhow!("GetParameter failed: {}", aws_sdk_ssm::error::DisplayErrorContext(e)))?;
+            resp.parameter.and_then(|p| p.value)
+        } else {
+            let resp = self.secrets_manager.get_secret_value().secret_id(name).send().await
+                .map_err(|e| anyhow!("GetSecretValue failed: {}", aws_sdk_secretsmanager::error::DisplayErrorContext(e)))?;
+            resp.secret_string
+        };
+        value.ok_or_else(|| anyhow!("Secret '{}' has no string value", name))
+    }
+}
+
+/// Replaces every `secret://NAME` in `inputs`' values with the secret from `source`,
+/// looking each name up once. Artifact inputs are left alone. Returns the values that
+/// were substituted.
+pub async fn resolve_secrets(inputs: &mut HashMap<String, Vec<PrimitiveInput>>, source: &dyn SecretSource) -> Result<Vec<String>> {
+    let names: BTreeSet<String> = inputs.values().flatten()
+        .filter_map(|input| match input {
+            PrimitiveInput::Value(v) => Some(v),
+            PrimitiveInput::ArtifactPath(_) => None,
+        })
+        .flat_map(|v| secret_reference().captures_iter(v).map(|c| c[1].to_string()))
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut secrets = HashMap::with_capacity(names.len());
+    for name in names {
+        let value = source.get_secret(&name).await.map_err(|e| anyhow!("Failed to resolve secret '{}': {}", name, e))?;
+        secrets.insert(name, value);
+    }
+    for input in inputs.values_mut().flatten() {
+        if let PrimitiveInput::Value(v) = input {
+            if v.contains("secret://") {
+                *v = secret_reference().replace_all(v, |c: &regex::Captures| secrets[&c[1]].clone()).into_owned();
+            }
+        }
+    }
+    Ok(secrets.into_values().collect())
+}
+
+/// A primitive whose value inputs may hold `secret://NAME` references, resolved from a
+/// `SecretSource` just before each `execute`. Wrap before `execute_or_skip` so derived
+/// output ids are computed from the references, never the secrets.
+pub struct WithSecrets<P> {
+    inner: P,
+    source: Arc<dyn SecretSource>,
+}
+
+/// Wraps `primitive` so its `secret://` references are resolved from `source`.
+pub fn with_secrets<P: Primitive>(primitive: P, source: Arc<dyn SecretSource>) -> WithSecrets<P> {
+    WithSecrets { inner: primitive, source }
+}
+
+impl<P: Debug> Debug for WithSecrets<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithSecrets").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<P: Primitive> Primitive for WithSecrets<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        self.inner.input_schema()
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        self.inner.output_schema()
+    }
+
+    fn idempotent(&self) -> bool {
+        self.inner.idempotent()
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.inner.retry_policy()
+    }
+
+    fn accepts_undeclared_inputs(&self) -> bool {
+        self.inner.accepts_undeclared_inputs()
+    }
+
+    fn ambient_inputs(&self, inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<BTreeMap<String, String>> {
+        self.inner.ambient_inputs(inputs)
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        let resolved = resolve_secrets(&mut inputs, self.source.as_ref()).await?;
+        self.inner.execute(inputs, context.redacting(&resolved), status_tx).await
+    }
+
+    async fn execute_batch(
+        &self,
+        mut batches: Vec<HashMap<String, Vec<PrimitiveInput>>>,
+        context: ExecutionContext<'_>,
+        status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<Vec<PrimitiveOutput>>> {
+        let mut resolved = Vec::new();
+        for inputs in &mut batches {
+            resolved.extend(resolve_secrets(inputs, self.source.as_ref()).await?);
+        }
+        self.inner.execute_batch(batches, context.redacting(&resolved), status_tx).await
+    }
+}
@@ -0,0 +1,98 @@
+use super::{LogLevel, PrimitiveStatus};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Log lines kept by a `StatusTracker`; older ones are dropped.
+const MAX_LOG_ENTRIES: usize = 256;
+
+/// Where an execution stands, per the statuses received so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionState {
+    /// Nothing received yet.
+    #[default]
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Point-in-time view of a `StatusTracker`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusSnapshot {
+    pub state: ExecutionState,
+    /// Latest reported fraction done, 0.0 to 1.0; 1.0 once completed.
+    pub progress: f32,
+    /// Message of the latest `Progress` update.
+    pub message: Option<String>,
+    /// `Log` lines in order received, capped at the most recent `MAX_LOG_ENTRIES`.
+    pub log: VecDeque<(LogLevel, String)>,
+    /// Reason given by `Failed`.
+    pub error: Option<String>,
+}
+
+impl StatusSnapshot {
+    /// Folds one status into the snapshot. A terminal state is kept: statuses arriving
+    /// after `Completed` or `Failed` only add log lines.
+    pub fn apply(&mut self, status: PrimitiveStatus) {
+        let terminal = matches!(self.state, ExecutionState::Completed | ExecutionState::Failed);
+        match status {
+            PrimitiveStatus::Log { level, message } => {
+                if self.log.len() == MAX_LOG_ENTRIES {
+                    self.log.pop_front();
+                }
+                self.log.push_back((level, message));
+            }
+            _ if terminal => {}
+            PrimitiveStatus::Starting => self.state = ExecutionState::Running,
+            PrimitiveStatus::Progress(fraction, message) => {
+                self.state = ExecutionState::Running;
+                self.progress = fraction.clamp(0.0, 1.0);
+                self.message = Some(message);
+            }
+            PrimitiveStatus::Completed => {
+                self.state = ExecutionState::Completed;
+                self.progress = 1.0;
+            }
+            PrimitiveStatus::Failed(reason) => {
+                self.state = ExecutionState::Failed;
+                self.error = Some(reason);
+            }
+        }
+    }
+}
+
+/// Consumes a primitive's `status_tx` stream in the background and keeps a
+/// `StatusSnapshot` of it for UIs and logs.
+#[derive(Debug)]
+pub struct StatusTracker {
+    snapshot: Arc<Mutex<StatusSnapshot>>,
+    task: JoinHandle<()>,
+}
+
+impl StatusTracker {
+    /// Starts consuming `rx`; it stops when every sender has been dropped.
+    pub fn spawn(mut rx: mpsc::Receiver<PrimitiveStatus>) -> Self {
+        let snapshot = Arc::new(Mutex::new(StatusSnapshot::default()));
+        let shared = snapshot.clone();
+        let task = tokio::spawn(async move {
+            while let Some(status) = rx.recv().await {
+                shared.lock().unwrap().apply(status);
+            }
+        });
+        Self { snapshot, task }
+    }
+
+    /// The state as of the statuses received so far.
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+
+    /// Waits for the stream to end (all senders dropped) and returns the final state.
+    pub async fn finish(self) -> StatusSnapshot {
+        let _ = self.task.await;
+        let snapshot = self.snapshot.lock().unwrap().clone();
+        snapshot
+    }
+}
@@ -1,6 +1,6 @@
-use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext};
+use super::{Primitive, InputDef, OutputDef, PrimitiveInput, PrimitiveOutput, PrimitiveStatus, ExecutionContext, optional_flag, optional_value, defaulted_value, prepare_inputs, read_input_text};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use anyhow::{Result, anyhow};
 use tokio::sync::mpsc;
 
@@ -13,6 +13,10 @@ impl Primitive for JsonSelect {
         "JsonSelect"
     }
 
+    fn idempotent(&self) -> bool {
+        true
+    }
+
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
             InputDef {
@@ -21,6 +25,7 @@ impl Primitive for JsonSelect {
                 mime_type: "application/json".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
             },
             InputDef {
                 name: "query".to_string(),
@@ -28,6 +33,23 @@ impl Primitive for JsonSelect {
                 mime_type: "text/plain".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "split".to_string(),
+                description: "If 'true', write each element of an array result as its own output".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "raw".to_string(),
+                description: "If 'true', write a string result as its bare contents instead of a quoted JSON string".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
             }
         ]
     }
@@ -38,6 +60,11 @@ impl Primitive for JsonSelect {
                 name: "result".to_string(),
                 description: "The selected JSON fragment".to_string(),
                 mime_type: "application/json".to_string(),
+            },
+            OutputDef {
+                name: "item_{n}".to_string(),
+                description: "With split=true, element n of the array result (instead of 'result')".to_string(),
+                mime_type: "application/json".to_string(),
             }
         ]
     }
@@ -48,30 +75,20 @@ impl Primitive for JsonSelect {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
-        // Extract query
-        let query_inputs = inputs.remove("query").ok_or_else(|| anyhow!("Missing query"))?;
-        let query_str = match &query_inputs[0] {
-            PrimitiveInput::Value(s) => s.clone(),
-            _ => return Err(anyhow!("Query must be inline value")),
-        };
-
-        // Extract JSON
-        let json_inputs = inputs.remove("json").ok_or_else(|| anyhow!("Missing json"))?;
-        let json_content = match &json_inputs[0] {
-            PrimitiveInput::Value(s) => s.clone(),
-            PrimitiveInput::ArtifactPath(p) => {
-                let local = context.file_manager.get_file(p).await?;
-                tokio::fs::read_to_string(local).await?
-            }
-        };
-
-        let data = serde_json::from_str(&json_content).unwrap_or(serde_json::json!({}));
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let split = optional_flag(&inputs, "split", false)?;
+        let raw = optional_flag(&inputs, "raw", false)?;
+        let (json_content, query_str) = read_json_select_inputs(inputs, &context).await?;
         let result_str = {
             let expr = jmespath::compile(&query_str)?;
-            let result = expr.search(&data)?;
-            serde_json::to_string(&result)?
+            select(&expr, &json_content)?
         };
         
+        if split {
+            return commit_split(&result_str, raw, &context).await;
+        }
+        let result_str = unquote_if_raw(result_str, raw)?;
+
         // Save result (result_str is String, which is Send)
         let temp_path = context.file_manager.prepare_output("json_select.json").await?;
         tokio::fs::write(&temp_path, result_str).await?;
@@ -85,10 +102,144 @@ impl Primitive for JsonSelect {
             }
         ])
     }
+
+    /// Compiles each distinct query once, and commits each result under a key derived
+    /// from its content so results in the same batch don't overwrite each other.
+    async fn execute_batch(
+        &self,
+        batches: Vec<HashMap<String, Vec<PrimitiveInput>>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<Vec<PrimitiveOutput>>> {
+        let mut documents = Vec::with_capacity(batches.len());
+        let mut splits = Vec::with_capacity(batches.len());
+        for mut inputs in batches {
+            prepare_inputs(&self.input_schema(), &mut inputs)?;
+            splits.push((optional_flag(&inputs, "split", false)?, optional_flag(&inputs, "raw", false)?));
+            documents.push(read_json_select_inputs(inputs, &context).await?);
+        }
+
+        // Compiled expressions aren't Send, so they must not live across an await.
+        let result_strs = {
+            let mut compiled = HashMap::new();
+            let mut results = Vec::with_capacity(documents.len());
+            for (json_content, query_str) in &documents {
+                if !compiled.contains_key(query_str) {
+                    compiled.insert(query_str.clone(), jmespath::compile(query_str)?);
+                }
+                results.push(select(&compiled[query_str], json_content)?);
+            }
+            results
+        };
+
+        let mut outputs = Vec::with_capacity(result_strs.len());
+        for (result_str, (split, raw)) in result_strs.into_iter().zip(splits) {
+            if split {
+                outputs.push(commit_split(&result_str, raw, &context).await?);
+                continue;
+            }
+            let result_str = unquote_if_raw(result_str, raw)?;
+            let key = format!("json_select_{:x}.json", md5::compute(&result_str));
+            let temp_path = context.file_manager.prepare_output(&key).await?;
+            tokio::fs::write(&temp_path, result_str).await?;
+            let artifact_uri = context.file_manager.commit_output(&key, &temp_path).await?;
+            outputs.push(vec![PrimitiveOutput { name: "result".to_string(), artifact_path: artifact_uri }]);
+        }
+        Ok(outputs)
+    }
 }
 
-#[derive(Debug)]
-pub struct TemplateRender;
+/// Extracts (json document, query) from a JsonSelect invocation's inputs.
+async fn read_json_select_inputs(
+    mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+    context: &ExecutionContext<'_>,
+) -> Result<(String, String)> {
+    // Extract query
+    let query_inputs = inputs.remove("query").ok_or_else(|| anyhow!("Missing query"))?;
+    let query_str = match &query_inputs[0] {
+        PrimitiveInput::Value(s) => s.clone(),
+        _ => return Err(anyhow!("Query must be inline value")),
+    };
+
+    // Extract JSON
+    let json_inputs = inputs.remove("json").ok_or_else(|| anyhow!("Missing json"))?;
+    let json_content = match &json_inputs[0] {
+        PrimitiveInput::Value(s) => s.clone(),
+        PrimitiveInput::ArtifactPath(p) => {
+            let local = context.file_manager.get_file(p).await?;
+            tokio::fs::read_to_string(local).await?
+        }
+    };
+
+    Ok((json_content, query_str))
+}
+
+/// Commits each element of an array result as `item_0`, `item_1`, ..., keyed by content
+/// like batch results so elements don't overwrite each other.
+async fn commit_split(result_str: &str, raw: bool, context: &ExecutionContext<'_>) -> Result<Vec<PrimitiveOutput>> {
+    let serde_json::Value::Array(items) = serde_json::from_str(result_str)? else {
+        return Err(anyhow!("split=true requires the query to return an array, got {}", result_str));
+    };
+
+    let mut outputs = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let item_str = match item {
+            serde_json::Value::String(text) if raw => text.clone(),
+            _ => serde_json::to_string(item)?,
+        };
+        let key = format!("json_select_{:x}.json", md5::compute(&item_str));
+        let temp_path = context.file_manager.prepare_output(&key).await?;
+        tokio::fs::write(&temp_path, item_str).await?;
+        let artifact_uri = context.file_manager.commit_output(&key, &temp_path).await?;
+        outputs.push(PrimitiveOutput { name: format!("item_{}", i), artifact_path: artifact_uri });
+    }
+    Ok(outputs)
+}
+
+/// With `raw`, replaces a JSON string result by its contents; other results stay JSON.
+fn unquote_if_raw(result_str: String, raw: bool) -> Result<String> {
+    if !raw {
+        return Ok(result_str);
+    }
+    match serde_json::from_str(&result_str)? {
+        serde_json::Value::String(text) => Ok(text),
+        _ => Ok(result_str),
+    }
+}
+
+fn select(expr: &jmespath::Expression, json_content: &str) -> Result<String> {
+    let data = serde_json::from_str(json_content).unwrap_or(serde_json::json!({}));
+    let result = expr.search(&data)?;
+    Ok(serde_json::to_string(&result)?)
+}
+
+/// Looks up an environment variable by name.
+type EnvLookup = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Renders a Tera template. `expose_env` names are looked up in the process environment,
+/// or through the lookup given to `with_env`; templates have no other way to read it.
+pub struct TemplateRender {
+    env: EnvLookup,
+}
+
+impl TemplateRender {
+    /// Reads exposed variables through `env` (a lookup by variable name).
+    pub fn with_env(env: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self { env: Box::new(env) }
+    }
+}
+
+impl Default for TemplateRender {
+    fn default() -> Self {
+        Self::with_env(|name| std::env::var(name).ok())
+    }
+}
+
+impl std::fmt::Debug for TemplateRender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TemplateRender").finish_non_exhaustive()
+    }
+}
 
 #[async_trait]
 impl Primitive for TemplateRender {
@@ -96,6 +247,18 @@ impl Primitive for TemplateRender {
         "TemplateRender"
     }
 
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    /// The values of `expose_env` variables are rendered, so they're part of the result.
+    fn ambient_inputs(&self, inputs: &HashMap<String, Vec<PrimitiveInput>>) -> Result<BTreeMap<String, String>> {
+        match inputs.get("expose_env") {
+            Some(names) => Ok(exposed_env(names, &self.env)?.into_iter().map(|(name, value)| (format!("env.{}", name), value)).collect()),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
     fn input_schema(&self) -> Vec<InputDef> {
         vec![
             InputDef {
@@ -104,6 +267,7 @@ impl Primitive for TemplateRender {
                 mime_type: "text/plain".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
             },
             InputDef {
                 name: "context".to_string(),
@@ -111,6 +275,15 @@ impl Primitive for TemplateRender {
                 mime_type: "application/json".to_string(),
                 min_count: 1,
                 max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "expose_env".to_string(),
+                description: "Environment variable names (comma-separated) to expose as {{ env.NAME }}".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: None,
+                default: None,
             }
         ]
     }
@@ -131,6 +304,7 @@ impl Primitive for TemplateRender {
         context: ExecutionContext<'_>,
         _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
     ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
         let template_input = inputs.remove("template").ok_or_else(|| anyhow!("Missing template"))?;
         let template_str = match &template_input[0] {
             PrimitiveInput::Value(s) => s.clone(),
@@ -148,10 +322,15 @@ impl Primitive for TemplateRender {
                 tokio::fs::read_to_string(local).await?
             }
         };
-        let context_val: serde_json::Value = serde_json::from_str(&context_str).unwrap_or(serde_json::json!({}));
-        let ctx = tera::Context::from_value(context_val)?;
+        let mut context_val: serde_json::Value = serde_json::from_str(&context_str).unwrap_or(serde_json::json!({}));
+        if let Some(names) = inputs.remove("expose_env") {
+            if let Some(map) = context_val.as_object_mut() {
+                map.insert("env".to_string(), serde_json::to_value(exposed_env(&names, &self.env)?)?);
+            }
+        }
 
-        let rendered = tera::Tera::one_off(&template_str, &ctx, false)?;
+        // The output is text/plain, so values are interpolated unescaped
+        let rendered = render_tera(&template_str, &context_val)?;
         
         let temp_path = context.file_manager.prepare_output("rendered.txt").await?;
         tokio::fs::write(&temp_path, rendered).await?;
@@ -165,3 +344,376 @@ impl Primitive for TemplateRender {
         ])
     }
 }
+
+/// Renders a one-off Tera `template` against `context`, without escaping. Tera's built-in
+/// `get_env` is replaced with one that always fails, so templates only see the variables
+/// that `expose_env` puts in `env` and can't read the rest of the process environment.
+fn render_tera(template: &str, context: &serde_json::Value) -> Result<String> {
+    let ctx = tera::Context::from_value(context.clone())?;
+    let mut tera = tera::Tera::default();
+    tera.register_function("get_env", |_: &HashMap<String, serde_json::Value>| -> tera::Result<serde_json::Value> {
+        Err(tera::Error::msg("get_env is not available in templates; use expose_env"))
+    });
+    tera.autoescape_on(vec![]);
+    Ok(tera.render_str(template, &ctx)?)
+}
+
+/// Collects the whitelisted environment variables for `TemplateRender`'s `env` namespace.
+/// Only the named variables are read; names that aren't set are left out.
+fn exposed_env(names: &[PrimitiveInput], lookup: impl Fn(&str) -> Option<String>) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for input in names {
+        let PrimitiveInput::Value(list) = input else {
+            return Err(anyhow!("Input 'expose_env' must be inline variable names"));
+        };
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if let Some(value) = lookup(name) {
+                env.insert(name.to_string(), value);
+            }
+        }
+    }
+    Ok(env)
+}
+
+#[derive(Debug)]
+pub struct HandlebarsRender;
+
+#[async_trait]
+impl Primitive for HandlebarsRender {
+    fn name(&self) -> &str {
+        "HandlebarsRender"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    /// Every input outside the schema is registered as a partial under its name.
+    fn accepts_undeclared_inputs(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "template".to_string(),
+                description: "Template string (Handlebars syntax)".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "context".to_string(),
+                description: "JSON Context".to_string(),
+                mime_type: "application/json".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            // Any other input is registered as a partial under its input name ({{> name}}).
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "rendered".to_string(),
+                description: "Resulting text".to_string(),
+                mime_type: "text/plain".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let template_input = inputs.remove("template").ok_or_else(|| anyhow!("Missing template"))?;
+        let template_str = read_input_text(&template_input[0], &context).await?;
+
+        let context_input = inputs.remove("context").ok_or_else(|| anyhow!("Missing context"))?;
+        let context_str = read_input_text(&context_input[0], &context).await?;
+        let context_val: serde_json::Value = serde_json::from_str(&context_str).unwrap_or(serde_json::json!({}));
+
+        let mut registry = handlebars::Handlebars::new();
+        for (name, input_list) in &inputs {
+            let Some(input) = input_list.first() else { continue };
+            let partial = read_input_text(input, &context).await?;
+            registry.register_partial(name, partial)
+                .map_err(|e| anyhow!("Invalid Handlebars partial '{}': {}", name, e))?;
+        }
+        registry.register_template_string("template", template_str)
+            .map_err(|e| anyhow!("Invalid Handlebars template: {}", e))?;
+        let rendered = registry.render("template", &context_val)
+            .map_err(|e| anyhow!("Failed to render Handlebars template: {}", e))?;
+
+        let temp_path = context.file_manager.prepare_output("rendered.txt").await?;
+        tokio::fs::write(&temp_path, rendered).await?;
+        let artifact_uri = context.file_manager.commit_output("rendered_result.txt", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "rendered".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// Separator between key segments when `separator` isn't given.
+const DEFAULT_KEY_SEPARATOR: &str = ".";
+
+#[derive(Debug)]
+pub struct JsonFlatten;
+
+#[async_trait]
+impl Primitive for JsonFlatten {
+    fn name(&self) -> &str {
+        "JsonFlatten"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "json".to_string(),
+                description: "The JSON object or array to flatten".to_string(),
+                mime_type: "application/json".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "separator".to_string(),
+                description: "Joins key segments (default '.')".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_KEY_SEPARATOR.to_string()),
+            },
+            InputDef {
+                name: "max_depth".to_string(),
+                description: "Maximum key segments; anything nested deeper is kept as a JSON value".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: None,
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "flattened".to_string(),
+                description: "Single-level object keyed by joined paths (array elements by index)".to_string(),
+                mime_type: "application/json".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let json_input = inputs.get("json").and_then(|v| v.first()).ok_or_else(|| anyhow!("Missing json input"))?;
+        let document: serde_json::Value = serde_json::from_str(&read_input_text(json_input, &context).await?)
+            .map_err(|e| anyhow!("Input 'json' is not valid JSON: {}", e))?;
+        let separator = defaulted_value(&inputs, "separator")?;
+        let max_depth = match optional_value(&inputs, "max_depth")? {
+            None => usize::MAX,
+            Some(v) => match v.trim().parse::<usize>() {
+                Ok(depth) if depth > 0 => depth,
+                _ => return Err(anyhow!("Input 'max_depth' must be a positive integer, got '{}'", v)),
+            },
+        };
+        if !(document.is_object() || document.is_array()) {
+            return Err(anyhow!("JsonFlatten requires a JSON object or array"));
+        }
+
+        let mut flattened = serde_json::Map::new();
+        flatten_into(&mut flattened, None, document, &separator, max_depth);
+
+        let temp_path = context.file_manager.prepare_output("flattened.json").await?;
+        tokio::fs::write(&temp_path, serde_json::to_string(&flattened)?).await?;
+        let artifact_uri = context.file_manager.commit_output("flattened_result.json", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "flattened".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// Adds `value` to `out` under `prefix`, recursing into non-empty objects and arrays
+/// until keys have `depth_left` segments. Empty containers are kept as values.
+fn flatten_into(out: &mut serde_json::Map<String, serde_json::Value>, prefix: Option<String>, value: serde_json::Value, separator: &str, depth_left: usize) {
+    let key = |segment: String| match &prefix {
+        Some(p) => format!("{}{}{}", p, separator, segment),
+        None => segment,
+    };
+    let children: Vec<(String, serde_json::Value)> = match value {
+        serde_json::Value::Object(map) if !map.is_empty() && depth_left > 0 => map.into_iter().map(|(k, v)| (key(k), v)).collect(),
+        serde_json::Value::Array(items) if !items.is_empty() && depth_left > 0 => items.into_iter().enumerate().map(|(i, v)| (key(i.to_string()), v)).collect(),
+        value => {
+            // An empty top-level document flattens to an empty object
+            if let Some(prefix) = prefix {
+                out.insert(prefix, value);
+            }
+            return;
+        }
+    };
+    for (child_key, child) in children {
+        flatten_into(out, Some(child_key), child, separator, depth_left - 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonUnflatten;
+
+#[async_trait]
+impl Primitive for JsonUnflatten {
+    fn name(&self) -> &str {
+        "JsonUnflatten"
+    }
+
+    fn idempotent(&self) -> bool {
+        true
+    }
+
+    fn input_schema(&self) -> Vec<InputDef> {
+        vec![
+            InputDef {
+                name: "json".to_string(),
+                description: "Single-level JSON object keyed by joined paths".to_string(),
+                mime_type: "application/json".to_string(),
+                min_count: 1,
+                max_count: Some(1),
+                default: None,
+            },
+            InputDef {
+                name: "separator".to_string(),
+                description: "Splits keys into segments (default '.')".to_string(),
+                mime_type: "text/plain".to_string(),
+                min_count: 0,
+                max_count: Some(1),
+                default: Some(DEFAULT_KEY_SEPARATOR.to_string()),
+            }
+        ]
+    }
+
+    fn output_schema(&self) -> Vec<OutputDef> {
+        vec![
+            OutputDef {
+                name: "unflattened".to_string(),
+                description: "Nested document; objects keyed 0..n become arrays".to_string(),
+                mime_type: "application/json".to_string(),
+            }
+        ]
+    }
+
+    async fn execute(
+        &self,
+        mut inputs: HashMap<String, Vec<PrimitiveInput>>,
+        context: ExecutionContext<'_>,
+        _status_tx: Option<mpsc::Sender<PrimitiveStatus>>,
+    ) -> Result<Vec<PrimitiveOutput>> {
+        prepare_inputs(&self.input_schema(), &mut inputs)?;
+        let json_input = inputs.get("json").and_then(|v| v.first()).ok_or_else(|| anyhow!("Missing json input"))?;
+        let flat = match serde_json::from_str(&read_input_text(json_input, &context).await?) {
+            Ok(serde_json::Value::Object(map)) => map,
+            Ok(_) => return Err(anyhow!("JsonUnflatten requires a JSON object")),
+            Err(e) => return Err(anyhow!("Input 'json' is not valid JSON: {}", e)),
+        };
+        let separator = defaulted_value(&inputs, "separator")?;
+        if separator.is_empty() {
+            return Err(anyhow!("Input 'separator' must not be empty"));
+        }
+
+        let mut root = BTreeMap::new();
+        for (key, value) in flat {
+            let path: Vec<&str> = key.split(separator.as_str()).collect();
+            insert_path(&mut root, &key, &path, value)?;
+        }
+        let root = PathNode::Branch(root);
+
+        let temp_path = context.file_manager.prepare_output("unflattened.json").await?;
+        tokio::fs::write(&temp_path, serde_json::to_string(&root.into_value())?).await?;
+        let artifact_uri = context.file_manager.commit_output("unflattened_result.json", &temp_path).await?;
+
+        Ok(vec![
+            PrimitiveOutput {
+                name: "unflattened".to_string(),
+                artifact_path: artifact_uri,
+            }
+        ])
+    }
+}
+
+/// A document being rebuilt by `JsonUnflatten`: values from the input, and the
+/// containers implied by their keys.
+enum PathNode {
+    Leaf(serde_json::Value),
+    Branch(BTreeMap<String, PathNode>),
+}
+
+impl PathNode {
+    /// Branches keyed exactly 0..n become arrays; all others become objects.
+    fn into_value(self) -> serde_json::Value {
+        let children = match self {
+            PathNode::Leaf(value) => return value,
+            PathNode::Branch(children) => children,
+        };
+        let indices: Option<Vec<usize>> = children.keys()
+            .map(|k| k.parse::<usize>().ok().filter(|i| i.to_string() == *k))
+            .collect();
+        let is_array = match indices {
+            Some(mut indices) if !indices.is_empty() => {
+                indices.sort_unstable();
+                indices.iter().enumerate().all(|(pos, i)| pos == *i)
+            }
+            _ => false,
+        };
+        if is_array {
+            // BTreeMap orders "10" before "2", so order by the parsed index
+            let mut items: Vec<(usize, PathNode)> = children.into_iter()
+                .map(|(k, child)| (k.parse().unwrap_or_default(), child))
+                .collect();
+            items.sort_by_key(|(i, _)| *i);
+            serde_json::Value::Array(items.into_iter().map(|(_, child)| child.into_value()).collect())
+        } else {
+            serde_json::Value::Object(children.into_iter().map(|(k, child)| (k, child.into_value())).collect())
+        }
+    }
+}
+
+/// Places `value` at `path` below `children`; `key` is the flattened key, for errors.
+fn insert_path(children: &mut BTreeMap<String, PathNode>, key: &str, path: &[&str], value: serde_json::Value) -> Result<()> {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(());
+    };
+    let conflict = || anyhow!("Conflicting keys: '{}' is used both as a value and as an object", key);
+    if rest.is_empty() {
+        if children.contains_key(*first) {
+            return Err(conflict());
+        }
+        children.insert(first.to_string(), PathNode::Leaf(value));
+        return Ok(());
+    }
+    match children.entry(first.to_string()).or_insert_with(|| PathNode::Branch(BTreeMap::new())) {
+        PathNode::Branch(grandchildren) => insert_path(grandchildren, key, rest, value),
+        PathNode::Leaf(_) => Err(conflict()),
+    }
+}
@@ -0,0 +1,143 @@
+//! # Table Bootstrap
+//!
+//! Programmatic setup for the DynamoDB table backing `DependencyGraph`.
+//! Issues the Single Table Design schema (`pk`/`sk` + `gsi1`/`gsi2`) and waits until
+//! the table is usable, so deployments don't need to recreate it by hand.
+//!
+//! Tables created before `gsi2` existed are migrated in place: the index is added with
+//! `UpdateTable` and existing compute nodes are backfilled so they show up in it.
+
+use aws_sdk_dynamodb::{
+    types::{
+        AttributeDefinition, BillingMode, CreateGlobalSecondaryIndexAction, GlobalSecondaryIndex,
+        GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, KeyType, Projection, ProjectionType,
+        ProvisionedThroughput, ScalarAttributeType, TableStatus,
+    },
+    Client,
+};
+use crate::dependency_graph::DependencyGraph;
+use std::time::Duration;
+
+/// How long to wait for a newly created table to become `ACTIVE`.
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Creates the dependency graph table (pk/sk + gsi1/gsi2) and waits for it to become `ACTIVE`.
+/// If the table already exists, it waits for it to be ready, adding `gsi2` (and backfilling
+/// existing compute nodes into it) if the table predates that index.
+pub async fn create_graph_table(client: &Client, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let result = client.create_table()
+        .table_name(name)
+        .attribute_definitions(string_attribute("pk"))
+        .attribute_definitions(string_attribute("sk"))
+        .attribute_definitions(string_attribute("gsi1pk"))
+        .attribute_definitions(string_attribute("gsi1sk"))
+        .attribute_definitions(string_attribute("gsi2pk"))
+        .attribute_definitions(string_attribute("gsi2sk"))
+        .key_schema(key("pk", KeyType::Hash))
+        .key_schema(key("sk", KeyType::Range))
+        .global_secondary_indexes(GlobalSecondaryIndex::builder()
+            .index_name("gsi1")
+            .key_schema(key("gsi1pk", KeyType::Hash))
+            .key_schema(key("gsi1sk", KeyType::Range))
+            .projection(Projection::builder().projection_type(ProjectionType::All).build())
+            .build()
+            .unwrap()
+        )
+        // Compute nodes by type
+        .global_secondary_indexes(GlobalSecondaryIndex::builder()
+            .index_name("gsi2")
+            .key_schema(key("gsi2pk", KeyType::Hash))
+            .key_schema(key("gsi2sk", KeyType::Range))
+            .projection(Projection::builder().projection_type(ProjectionType::KeysOnly).build())
+            .build()
+            .unwrap()
+        )
+        .billing_mode(BillingMode::PayPerRequest)
+        .send()
+        .await;
+
+    if let Err(e) = result {
+        match aws_sdk_dynamodb::Error::from(e) {
+            aws_sdk_dynamodb::Error::ResourceInUseException(_) => {
+                tracing::info!("Table {} already exists", name);
+                wait_for_active(client, name).await?;
+                return add_type_index(client, name).await;
+            }
+            other => return Err(other.into()),
+        }
+    }
+
+    wait_for_active(client, name).await
+}
+
+/// Adds `gsi2` to an existing table that lacks it, waits for it to become `ACTIVE`, then
+/// backfills compute nodes written before it existed. Does nothing if the index exists.
+async fn add_type_index(client: &Client, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let resp = client.describe_table().table_name(name).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+    let Some(table) = resp.table else {
+        return Err(format!("Table {} has no description", name).into());
+    };
+    if table.global_secondary_indexes().iter().any(|gsi| gsi.index_name() == Some("gsi2")) {
+        return Ok(());
+    }
+
+    tracing::info!("Adding index gsi2 to table {}", name);
+    let mut index = CreateGlobalSecondaryIndexAction::builder()
+        .index_name("gsi2")
+        .key_schema(key("gsi2pk", KeyType::Hash))
+        .key_schema(key("gsi2sk", KeyType::Range))
+        .projection(Projection::builder().projection_type(ProjectionType::KeysOnly).build());
+    // Provisioned tables need capacity for the new index; match the table's own.
+    let on_demand = table.billing_mode_summary().and_then(|b| b.billing_mode()) == Some(&BillingMode::PayPerRequest);
+    if let Some(throughput) = table.provisioned_throughput().filter(|_| !on_demand) {
+        index = index.provisioned_throughput(ProvisionedThroughput::builder()
+            .read_capacity_units(throughput.read_capacity_units().unwrap_or(5))
+            .write_capacity_units(throughput.write_capacity_units().unwrap_or(5))
+            .build()
+            .unwrap());
+    }
+    client.update_table()
+        .table_name(name)
+        .attribute_definitions(string_attribute("gsi2pk"))
+        .attribute_definitions(string_attribute("gsi2sk"))
+        .global_secondary_index_updates(GlobalSecondaryIndexUpdate::builder().create(index.build().unwrap()).build())
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    wait_for_active(client, name).await?;
+
+    let backfilled = DependencyGraph::new(client.clone(), name.to_string()).backfill_type_index().await?;
+    tracing::info!("Backfilled {} compute nodes into gsi2 of table {}", backfilled, name);
+    Ok(())
+}
+
+/// Polls `DescribeTable` until the table and all of its GSIs report `ACTIVE`.
+pub async fn wait_for_active(client: &Client, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let deadline = tokio::time::Instant::now() + ACTIVE_TIMEOUT;
+    loop {
+        let resp = client.describe_table().table_name(name).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+        if let Some(table) = resp.table {
+            let table_active = table.table_status == Some(TableStatus::Active);
+            let indexes_active = table.global_secondary_indexes.unwrap_or_default()
+                .iter()
+                .all(|gsi| gsi.index_status.is_none() || gsi.index_status == Some(IndexStatus::Active));
+            if table_active && indexes_active {
+                return Ok(());
+            }
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Err(format!("Timed out waiting for table {} to become ACTIVE", name).into());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn string_attribute(name: &str) -> AttributeDefinition {
+    AttributeDefinition::builder().attribute_name(name).attribute_type(ScalarAttributeType::S).build().unwrap()
+}
+
+fn key(name: &str, key_type: KeyType) -> KeySchemaElement {
+    KeySchemaElement::builder().attribute_name(name).key_type(key_type).build().unwrap()
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use regex::Regex;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -72,6 +75,71 @@ pub struct ComputeNodeTemplate {
     pub type_name: String,
     pub id: String, // Template e.g. "analysis-{site_id}"
     pub inputs: Vec<InputTemplate>,
+    /// Primitives run in order to produce the node's outputs; a step may consume the
+    /// outputs of any step declared before it, so the steps form a small DAG.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepTemplate>,
+}
+
+impl ComputeNodeTemplate {
+    pub fn step(&self, name: &str) -> Option<&StepTemplate> {
+        self.steps.iter().find(|s| s.name == name)
+    }
+
+    /// Checks step names are unique and every step reference names an earlier step.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (i, step) in self.steps.iter().enumerate() {
+            let earlier = &self.steps[..i];
+            if earlier.iter().any(|s| s.name == step.name) {
+                return Err(format!("Compute node '{}': duplicate step name '{}'", self.type_name, step.name).into());
+            }
+            for dependency in step.dependencies() {
+                if !earlier.iter().any(|s| s.name == dependency) {
+                    let problem = if self.step(dependency).is_some() { "a later step" } else { "an unknown step" };
+                    return Err(format!("Compute node '{}': step '{}' refers to {} '{}'", self.type_name, step.name, problem, dependency).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One primitive invocation within a compute node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepTemplate {
+    pub name: String,
+    pub primitive: String,
+    /// Keyed by the primitive's input name.
+    #[serde(default)]
+    pub inputs: HashMap<String, StepInput>,
+}
+
+impl StepTemplate {
+    /// Names of the steps whose outputs this step consumes.
+    pub fn dependencies(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.inputs.values()
+            .filter_map(|input| match input {
+                StepInput::Step { step, .. } => Some(step.as_str()),
+                _ => None,
+            })
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source")]
+pub enum StepInput {
+    /// The artifact that triggered the workflow.
+    #[serde(rename = "self")]
+    SelfArtifact,
+    /// A named output of an earlier step.
+    #[serde(rename = "step")]
+    Step { step: String, output: String },
+    #[serde(rename = "literal")]
+    Literal { value: String },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -94,13 +162,16 @@ pub struct ArtifactMatch {
 impl CurioConfig {
     pub fn from_yaml(content: &str) -> Result<Self, serde_yaml::Error> {
         let mut config: CurioConfig = serde_yaml::from_str(content)?;
-        // Compile regexes
+        // Compile regexes. A bad pattern is reported like any other parse error, so a
+        // reloaded config with a typo can be rejected instead of taking the process down.
+        let compile = |pattern: &str| Regex::new(pattern)
+            .map_err(|e| <serde_yaml::Error as serde::de::Error>::custom(format!("Invalid regex '{}': {}", pattern, e)));
         for rule in &mut config.artifacts {
-            rule.regex = Some(Regex::new(&rule.match_pattern).expect("Invalid Regex in config"));
+            rule.regex = Some(compile(&rule.match_pattern)?);
         }
         for rule in &mut config.external_inputs {
              // Extract field names from regex
-             let regex = Regex::new(&rule.match_pattern).expect("Invalid Regex in external input config");
+             let regex = compile(&rule.match_pattern)?;
              let mut fields = Vec::new();
              for name in regex.capture_names().flatten() {
                  fields.push(name.to_string());
@@ -108,9 +179,18 @@ impl CurioConfig {
              rule.fields = fields; // We need to add this field to ExternalInputRule
              rule.regex = Some(regex);
         }
+        config.validate().map_err(<serde_yaml::Error as serde::de::Error>::custom)?;
         Ok(config)
     }
 
+    /// Checks the step declarations of every workflow; see `ComputeNodeTemplate::validate`.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for workflow in &self.workflows {
+            workflow.compute_node.validate()?;
+        }
+        Ok(())
+    }
+
     /// Matches an S3 path against artifact rules.
     /// Returns the first match with captured variables.
     pub fn match_artifact(&self, path: &str) -> Option<ArtifactMatch> {
@@ -140,6 +220,91 @@ impl CurioConfig {
     }
 }
 
+/// Where a `ConfigWatcher` reads `curio.yaml` from.
+#[derive(Debug, Clone)]
+pub enum ConfigSource {
+    S3 { client: aws_sdk_s3::Client, bucket: String, key: String },
+    File(PathBuf),
+}
+
+impl ConfigSource {
+    /// Fetches the content with a version tag: the ETag for S3, an MD5 of the bytes for files.
+    async fn fetch(&self) -> Result<(String, String), ConfigError> {
+        match self {
+            ConfigSource::S3 { client, bucket, key } => {
+                let resp = client.get_object().bucket(bucket).key(key).send().await?;
+                let etag = resp.e_tag().unwrap_or_default().to_string();
+                let data = resp.body.collect().await?.into_bytes();
+                Ok((String::from_utf8(data.to_vec())?, etag))
+            }
+            ConfigSource::File(path) => {
+                let content = tokio::fs::read_to_string(path).await?;
+                let hash = format!("{:x}", md5::compute(&content));
+                Ok((content, hash))
+            }
+        }
+    }
+}
+
+pub type ConfigError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Keeps a `CurioConfig` current for long-running processes by re-fetching its source
+/// and swapping in the new config when the content changes. Readers take a cheap
+/// `Arc` snapshot via `current`; a config that fails to parse is logged and skipped,
+/// leaving the previous one in place.
+pub struct ConfigWatcher {
+    source: ConfigSource,
+    current: RwLock<Arc<CurioConfig>>,
+    /// Version tag of the last content fetched, whether or not it parsed.
+    version: Mutex<String>,
+}
+
+impl ConfigWatcher {
+    /// Loads the initial config, which must parse.
+    pub async fn new(source: ConfigSource) -> Result<Self, ConfigError> {
+        let (content, version) = source.fetch().await?;
+        let config = CurioConfig::from_yaml(&content)?;
+        Ok(Self { source, current: RwLock::new(Arc::new(config)), version: Mutex::new(version) })
+    }
+
+    /// The config in effect now. Holders keep their snapshot across later reloads.
+    pub fn current(&self) -> Arc<CurioConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Re-fetches the source once. Returns whether a new config was swapped in; unchanged
+    /// content is not re-parsed, and content that fails to parse is returned as an error
+    /// (and not retried until it changes again).
+    pub async fn reload(&self) -> Result<bool, ConfigError> {
+        let (content, version) = self.source.fetch().await?;
+        {
+            let mut last = self.version.lock().unwrap();
+            if *last == version {
+                return Ok(false);
+            }
+            *last = version.clone();
+        }
+        let config = CurioConfig::from_yaml(&content)?;
+        *self.current.write().unwrap() = Arc::new(config);
+        tracing::info!("Reloaded config (version {})", version);
+        Ok(true)
+    }
+
+    /// Calls `reload` every `interval` until the returned task is aborted.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload().await {
+                    tracing::warn!("Config reload failed; keeping the current config: {}", e);
+                }
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -8,8 +8,10 @@
 //! ### Entities
 //! *   **Artifact (`ARTIFACT#{Checksum}`)**: Represents a file or data object.
 //!     *   `is_external`: Boolean. If true, managed by user (source files). If false, generated by compute (build outputs).
+//!     *   `generation`: Optional version/content hash, for artifacts updated in place under a stable ID.
 //! *   **Compute Node (`COMPUTE#{Id}`)**: Represents a transformation step.
 //!     *   `inputs`: List of Artifact IDs.
+//!     *   `input_generations`: Map of input Artifact ID -> generation at creation time.
 //!     *   `outputs`: List of Artifact IDs.
 //!     *   `type`: String (e.g., "Compile", "Link").
 //!
@@ -17,6 +19,12 @@
 //! To efficiently answer "Who depends on Artifact A?", we store Edge items:
 //! *   **PK**: `ARTIFACT#{A}`, **SK**: `COMPUTE#{C}`.
 //!
+//! "Who produces Artifact B?" is answered the same way, by Producer Edge items written
+//! alongside a node's `outputs`:
+//! *   **PK**: `ARTIFACT#{B}`, **SK**: `PRODUCER#{C}`.
+//!
+//! Tables written before producer edges existed need `backfill_producer_edges` run once.
+//!
 //! ## Workflows
 //!
 //! ### 1. New Build Flow
@@ -25,6 +33,8 @@
 //!      |                  |                                   |
 //!   (Artifact A)      (Compute C IS DIRTY)               (Artifact B, C IS CLEAN)
 //! ```
+//! `register_and_create` does the first two steps in one transaction, so a crash can't
+//! leave registered inputs without their node (or a node with unregistered inputs).
 //!
 //! ### 2. Update Flow (Input Change `A` -> `A'`)
 //! Triggered when an external artifact changes.
@@ -32,13 +42,20 @@
 //! 1.  **Register New**: User registers `A'` (new checksum).
 //! 2.  **Identify Downstream**: Query `get_downstream_compute_nodes(A)` -> Found `C_old`.
 //! 3.  **Reconstruct**: Get details of `C_old` (Type="Compile", Inputs=[A]).
-//! 4.  **Remove Old**: `remove_compute_node(C_old)`.
+//! 4.  **Remove Old**: `remove_compute_node(C_old, true)`.
 //!     *   Marks `C_old` outputs (e.g., `B`) as `STATUS#ORPHAN`.
+//!     *   With `hard = false` the node is tombstoned instead (`STATUS#DELETED`) and
+//!         hard-deleted later by `purge_deleted(retention)`.
 //! 5.  **Create New**: User creates `C_new` with inputs `[A']`.
 //!     *   `C_new` is DIRTY.
 //! 6.  **Run & Output**: `C_new` runs, produces `B'`.
 //!     *   `set_compute_node_outputs(C_new, [B'])`.
 //!
+//! If `A` is instead updated in place (same ID), re-register it with a new `generation`;
+//! `is_compute_node_stale(C)` then reports that `C` was built against an older one.
+//! To re-run `A`'s consumers without any change (e.g. after fixing a bug downstream),
+//! `touch_artifact(A)` marks them DIRTY again.
+//!
 //! ### 3. Garbage Collection (GC)
 //! Artifacts are reference-counted implicitly by the graph structure.
 //!
@@ -48,59 +65,389 @@
 //!     *   Internal: When `set_compute_node_outputs` changes outputs, old ones become ORPHANS.
 //! *   **Cleanup (`cleanup_orphans`)**:
 //!     *   Scans `STATUS#ORPHAN` index.
-//!     *   **Safe Check**: For each orphan, verifies `count(downstream_edges) == 0`.
+//!     *   **Repair**: Drops consumer edges that point at compute nodes which no longer exist.
+//!     *   **Safe Check**: For each orphan, verifies `count(downstream_edges) == 0` and that
+//!         its producer edges lead to no live compute node.
 //!     *   If safe, DELETE artifact.
 //!
+//! `stats()` summarises artifact, compute node, dirty and orphan counts.
+//! `export_graph` and `import_graph` move a graph between tables as a `GraphDescription`
+//! (e.g. JSON), such as to seed a test environment.
+//! `with_details_cache` keeps `get_compute_node_details` results in process for repeated
+//! reads during a build run.
+//! For UIs, `get_dirty_compute_nodes_page` and `get_downstream_compute_nodes_page` return
+//! a `Page` at a time with an opaque cursor for the next one.
+//!
 //! ## DynamoDB Schema
 //! | Entity       |     PK               | SK             | GSI1PK         | GSI1SK           | Notes               |
 //! |--------------|----------------------|----------------|----------------|------------------|---------------------|
 //! | **Artifact** | `ARTIFACT#{Sum}`     | `META`         | `STATUS#ORPHAN`| `ARTIFACT#{Sum}` | Meta info           |
 //! | **Compute**  | `COMPUTE#{Id}`       | `META`         | `STATUS#DIRTY` | `COMPUTE#{Id}`   | Inputs/Outputs list |
+//! | **Tombstone**| `COMPUTE#{Id}`       | `META`         | `STATUS#DELETED`| `{DeletedAt}#COMPUTE#{Id}` | Soft-deleted node |
 //! | **Edge**     | `ARTIFACT#{Sum}`     | `COMPUTE#{Id}` | -              | -                | Reverse lookup      |
+//! | **Producer** | `ARTIFACT#{Sum}`     | `PRODUCER#{Id}`| -              | -                | Output lookup       |
 //!
 
 use aws_sdk_dynamodb::{
-    types::{AttributeValue, TransactWriteItem, Put},
+    types::{AttributeValue, ConditionCheck, Delete, KeysAndAttributes, TransactWriteItem, Put},
     Client,
 };
+use crate::storage::{ArtifactMetadata, ArtifactStorage, ProducerMetadata};
 use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::pin::Pin;
+use std::sync::Mutex;
 
 pub type ArtifactId = String;
 pub type ComputeNodeId = String;
 
+/// Most `BatchGetItem` requests made for one chunk of keys while some stay unprocessed.
+const BATCH_GET_MAX_ATTEMPTS: u32 = 8;
+/// Wait before the first retry of unprocessed keys; doubled for each one after.
+const BATCH_GET_BACKOFF: std::time::Duration = std::time::Duration::from_millis(25);
+
+/// `(node_id, depth)` pairs yielded by `get_transitive_downstream`.
+pub type DownstreamStream = Pin<Box<dyn Stream<Item = Result<(ComputeNodeId, usize), aws_sdk_dynamodb::Error>> + Send>>;
+
+/// One page of results from a `*_page` query. Pass `next_cursor` back to get the
+/// following page; `None` means there are no more results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Opaque, URL-safe encoding of DynamoDB's `LastEvaluatedKey`.
+    pub next_cursor: Option<String>,
+}
+
+/// Error type of graph operations that can fail for reasons other than DynamoDB
+/// (a malformed page cursor, unknown inputs); `downcast_ref` to tell them apart.
+pub type GraphError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Returned by `create_compute_node_strict` (or `register_and_create`) when some inputs
+/// are not registered artifacts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownInputs(pub Vec<ArtifactId>);
+
+impl std::fmt::Display for UnknownInputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown input artifacts: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownInputs {}
+
+/// Returned when an operation targets a compute node that doesn't exist (or was deleted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeNotFound(pub ComputeNodeId);
+
+impl std::fmt::Display for NodeNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Compute node not found: {}", self.0)
+    }
+}
+
+impl std::error::Error for NodeNotFound {}
+
+/// Hex-encodes a `LastEvaluatedKey` (all of this table's key attributes are strings).
+fn encode_cursor(key: &HashMap<String, AttributeValue>) -> Option<String> {
+    let key: BTreeMap<&String, &String> = key.iter()
+        .filter_map(|(k, v)| v.as_s().ok().map(|s| (k, s)))
+        .collect();
+    let json = serde_json::to_vec(&key).ok()?;
+    Some(json.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn decode_cursor(cursor: &str) -> Result<HashMap<String, AttributeValue>, GraphError> {
+    let bytes = (0..cursor.len()).step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("Malformed page cursor")?;
+    let key: HashMap<String, String> = serde_json::from_slice(&bytes).map_err(|_| "Malformed page cursor")?;
+    Ok(key.into_iter().map(|(k, v)| (k, AttributeValue::S(v))).collect())
+}
+
+/// DynamoDB's `Limit` for a `*_page` request of `limit` items. DynamoDB rejects a limit of
+/// 0, so that is reported here; limits past `i32::MAX` are clamped rather than wrapped.
+fn page_limit(limit: usize) -> Result<i32, GraphError> {
+    if limit == 0 {
+        return Err("Page limit must be at least 1".into());
+    }
+    Ok(i32::try_from(limit).unwrap_or(i32::MAX))
+}
+
+/// Summary counts returned by `DependencyGraph::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    pub artifacts: usize,
+    /// Live compute nodes; soft-deleted tombstones are not counted.
+    pub compute_nodes: usize,
+    pub dirty: usize,
+    pub orphans: usize,
+}
+
+/// What `DependencyGraph::rebuild_from_storage` restored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RebuildSummary {
+    pub artifacts: usize,
+    pub compute_nodes: usize,
+    /// artifact.yaml files that could not be parsed and were skipped.
+    pub skipped: usize,
+}
+
+/// What `DependencyGraph::reconcile` found (and, unless `dry_run`, acted on).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Artifacts with content in storage but no graph entry; their objects are deleted.
+    pub storage_only: Vec<ArtifactId>,
+    /// Internal artifacts in the graph with no content in storage; they are marked
+    /// orphaned so `cleanup_orphans` removes them once nothing consumes them.
+    pub graph_only: Vec<ArtifactId>,
+    /// Storage-only artifacts that are left in place because they lack their own
+    /// directory (see `ArtifactStorage::has_own_directory`), so can't be deleted safely.
+    pub undeletable: Vec<ArtifactId>,
+    pub dry_run: bool,
+}
+
+/// Point-in-time state of every live compute node, from `DependencyGraph::snapshot`.
+/// Serializable so it can be persisted between pipeline runs and diffed later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphSnapshot {
+    /// RFC 3339 time the scan started.
+    pub taken_at: String,
+    /// Node id -> whether it was dirty.
+    pub nodes: BTreeMap<ComputeNodeId, bool>,
+}
+
+/// Node ids that changed between two snapshots, each sorted. Nodes that were added
+/// are only listed in `added`, whatever their state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    pub added: Vec<ComputeNodeId>,
+    pub removed: Vec<ComputeNodeId>,
+    pub newly_dirty: Vec<ComputeNodeId>,
+    pub newly_clean: Vec<ComputeNodeId>,
+}
+
+impl GraphSnapshot {
+    /// Compares an earlier snapshot with a later one.
+    pub fn diff(before: &GraphSnapshot, after: &GraphSnapshot) -> GraphDiff {
+        let mut diff = GraphDiff {
+            removed: before.nodes.keys().filter(|id| !after.nodes.contains_key(*id)).cloned().collect(),
+            ..Default::default()
+        };
+        for (id, &dirty) in &after.nodes {
+            match before.nodes.get(id) {
+                None => diff.added.push(id.clone()),
+                Some(false) if dirty => diff.newly_dirty.push(id.clone()),
+                Some(true) if !dirty => diff.newly_clean.push(id.clone()),
+                Some(_) => {}
+            }
+        }
+        diff
+    }
+}
+
+/// Portable description of a graph, read by `DependencyGraph::import_graph` and produced
+/// by `DependencyGraph::export_graph`. Serializable, e.g. to seed test tables from JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphDescription {
+    #[serde(default)]
+    pub artifacts: Vec<ArtifactDescription>,
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>,
+    /// A node's inputs and outputs are listed in the order of its edges here.
+    #[serde(default)]
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactDescription {
+    pub id: ArtifactId,
+    #[serde(default)]
+    pub is_external: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub id: ComputeNodeId,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    /// Defaults to true, as for a newly created node. A clean node has its outputs set,
+    /// even if it has no output edges.
+    #[serde(default = "default_dirty")]
+    pub dirty: bool,
+}
+
+fn default_dirty() -> bool {
+    true
+}
+
+/// `{"kind": "input", "artifact": .., "node": ..}` or the same with `"kind": "output"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GraphEdge {
+    /// `artifact` is an input of `node`.
+    Input { artifact: ArtifactId, node: ComputeNodeId },
+    /// `node` produced `artifact`.
+    Output { node: ComputeNodeId, artifact: ArtifactId },
+}
+
+/// `(node_type, inputs, input_generations)`, as returned by `get_compute_node_details`.
+pub type NodeDetails = (String, Vec<ArtifactId>, HashMap<ArtifactId, String>);
+
+/// In-process cache of compute node details, bounded to `capacity` nodes; the oldest
+/// entry is evicted first.
+struct DetailsCache {
+    capacity: usize,
+    state: Mutex<(HashMap<ComputeNodeId, NodeDetails>, VecDeque<ComputeNodeId>)>,
+}
+
+impl DetailsCache {
+    fn get(&self, id: &str) -> Option<NodeDetails> {
+        self.state.lock().unwrap().0.get(id).cloned()
+    }
+
+    fn insert(&self, id: ComputeNodeId, details: NodeDetails) {
+        let (entries, order) = &mut *self.state.lock().unwrap();
+        if entries.insert(id.clone(), details).is_none() {
+            order.push_back(id);
+        }
+        while entries.len() > self.capacity {
+            let Some(oldest) = order.pop_front() else { break };
+            entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        let (entries, order) = &mut *self.state.lock().unwrap();
+        if entries.remove(id).is_some() {
+            order.retain(|cached| cached != id);
+        }
+    }
+}
+
 pub struct DependencyGraph {
     client: Client,
     table_name: String,
+    details_cache: Option<DetailsCache>,
 }
 
 impl DependencyGraph {
     pub fn new(client: Client, table_name: String) -> Self {
-        Self { client, table_name }
+        Self { client, table_name, details_cache: None }
+    }
+
+    /// Caches up to `capacity` results of `get_compute_node_details` in this process.
+    /// Writes through this graph to a node drop its entry; writes by other processes are
+    /// not seen, so use this for the span of one build run.
+    pub fn with_details_cache(mut self, capacity: usize) -> Self {
+        self.details_cache = Some(DetailsCache { capacity, state: Mutex::new(Default::default()) });
+        self
+    }
+
+    fn forget_details(&self, id: &str) {
+        if let Some(cache) = &self.details_cache {
+            cache.remove(id);
+        }
     }
 
     fn pk_artifact(id: &str) -> String { format!("ARTIFACT#{}", id) }
     fn pk_compute(id: &str) -> String { format!("COMPUTE#{}", id) }
     fn sk_meta() -> String { "META".to_string() }
     fn sk_edge_compute(id: &str) -> String { format!("COMPUTE#{}", id) }
+    fn sk_edge_producer(id: &str) -> String { format!("PRODUCER#{}", id) }
     
     // GSI1
     fn gsi1_dirty_pk() -> String { "STATUS#DIRTY".to_string() }
     fn gsi1_orphan_pk() -> String { "STATUS#ORPHAN".to_string() }
+    fn gsi1_deleted_pk() -> String { "STATUS#DELETED".to_string() }
+    fn gsi1_deleted_sk(deleted_at: &str, id: &str) -> String { format!("{}#COMPUTE#{}", deleted_at, id) }
     fn gsi1_compute_sk(id: &str) -> String { format!("COMPUTE#{}", id) }
     fn gsi1_artifact_sk(id: &str) -> String { format!("ARTIFACT#{}", id) }
 
+    // GSI2: compute nodes by type
+    fn gsi2_type_pk(node_type: &str) -> String { format!("TYPE#{}", node_type) }
+
     /// Registers an artifact existence. ID is the checksum.
-    pub async fn register_artifact(&self, id: ArtifactId, is_external: bool) -> Result<(), aws_sdk_dynamodb::Error> {
-        self.client.put_item()
+    /// `generation` identifies the version of an artifact updated in place under the same ID
+    /// (e.g. a content hash); re-registering with a new one makes dependent nodes stale.
+    pub async fn register_artifact(&self, id: ArtifactId, is_external: bool, generation: Option<String>) -> Result<(), aws_sdk_dynamodb::Error> {
+        let mut put = self.client.put_item()
             .table_name(&self.table_name)
             .item("pk", AttributeValue::S(Self::pk_artifact(&id)))
             .item("sk", AttributeValue::S(Self::sk_meta()))
             .item("created_at", AttributeValue::S(chrono::Utc::now().to_rfc3339()))
-            .item("is_external", AttributeValue::Bool(is_external))
+            .item("is_external", AttributeValue::Bool(is_external));
+        if let Some(generation) = generation {
+            put = put.item("generation", AttributeValue::S(generation));
+        }
+        put.send().await?;
+        Ok(())
+    }
+
+    /// Returns the current generation of an artifact, if it was registered with one.
+    pub async fn get_artifact_generation(&self, id: ArtifactId) -> Result<Option<String>, aws_sdk_dynamodb::Error> {
+        let resp = self.client.get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_artifact(&id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .projection_expression("generation")
             .send()
             .await?;
-        Ok(())
+        Ok(resp.item.and_then(|item| item.get("generation").and_then(|av| av.as_s().ok()).cloned()))
+    }
+
+    /// Returns the ids in `ids` that have no artifact META item, in their original order.
+    async fn find_unregistered(&self, ids: &[ArtifactId]) -> Result<Vec<ArtifactId>, GraphError> {
+        let unique: BTreeSet<&ArtifactId> = ids.iter().collect();
+        let keys = unique.into_iter().map(|id| HashMap::from([
+            ("pk".to_string(), AttributeValue::S(Self::pk_artifact(id))),
+            ("sk".to_string(), AttributeValue::S(Self::sk_meta())),
+        ])).collect();
+        let found: HashSet<String> = self.batch_get(keys, "pk", false).await?
+            .iter()
+            .filter_map(|item| item.get("pk").and_then(|av| av.as_s().ok()))
+            .map(|pk| pk.trim_start_matches("ARTIFACT#").to_string())
+            .collect();
+
+        Ok(ids.iter().filter(|id| !found.contains(*id)).cloned().collect())
+    }
+
+    /// Fetches the items at `keys` with `BatchGetItem`, at most 100 keys per request.
+    /// Keys that come back unprocessed (when throttled) are re-requested with exponential
+    /// backoff, failing after `BATCH_GET_MAX_ATTEMPTS` requests for the same chunk.
+    async fn batch_get(&self, keys: Vec<HashMap<String, AttributeValue>>, projection: &str, consistent_read: bool) -> Result<Vec<HashMap<String, AttributeValue>>, GraphError> {
+        let mut items = Vec::new();
+        for chunk in keys.chunks(100) {
+            let mut request = Some(KeysAndAttributes::builder()
+                .set_keys(Some(chunk.to_vec()))
+                .projection_expression(projection)
+                .consistent_read(consistent_read)
+                .build()
+                .map_err(aws_sdk_dynamodb::Error::from)?);
+
+            let mut attempts = 0;
+            while let Some(keys) = request.take() {
+                if attempts == BATCH_GET_MAX_ATTEMPTS {
+                    return Err(format!("BatchGetItem left {} keys unprocessed after {} attempts", keys.keys().len(), attempts).into());
+                }
+                if attempts > 0 {
+                    tokio::time::sleep(BATCH_GET_BACKOFF * 2u32.pow(attempts - 1)).await;
+                }
+                attempts += 1;
+
+                let resp = self.client.batch_get_item()
+                    .request_items(&self.table_name, keys)
+                    .send()
+                    .await
+                    .map_err(aws_sdk_dynamodb::Error::from)?;
+                items.extend(resp.responses.and_then(|mut r| r.remove(&self.table_name)).unwrap_or_default());
+                request = resp.unprocessed_keys.and_then(|mut u| u.remove(&self.table_name));
+            }
+        }
+        Ok(items)
     }
 
     /// Explicitly marks an artifact as STATUS#ORPHAN.
@@ -117,73 +464,245 @@ impl DependencyGraph {
         Ok(())
     }
 
+    /// Forces re-processing of an artifact's direct consumers without changing the artifact:
+    /// every live compute node consuming `id` is marked dirty, as if newly created.
+    /// Returns the number of nodes marked.
+    pub async fn touch_artifact(&self, id: ArtifactId) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let mut marked = 0;
+        for node_id in Self::query_consumers(&self.client, &self.table_name, &id).await? {
+            if self.mark_compute_node_dirty(&node_id).await? {
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+
+    /// Puts a compute node back on STATUS#DIRTY. Returns false if the node doesn't exist
+    /// or was deleted, in which case nothing is written.
+    async fn mark_compute_node_dirty(&self, id: &str) -> Result<bool, aws_sdk_dynamodb::Error> {
+        let result = self.client.update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_compute(id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .update_expression("SET gsi1pk = :pk, gsi1sk = :sk")
+            .condition_expression("attribute_exists(pk) AND attribute_not_exists(deleted_at)")
+            .expression_attribute_values(":pk", AttributeValue::S(Self::gsi1_dirty_pk()))
+            .expression_attribute_values(":sk", AttributeValue::S(Self::gsi1_compute_sk(id)))
+            .send()
+            .await;
+        self.forget_details(id);
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Creates a new compute node.
     /// ID is derived by caller.
-    /// Marks as is_dirty = true initially.
-    pub async fn create_compute_node(&self, id: ComputeNodeId, inputs: Vec<ArtifactId>, node_type: String) -> Result<(), aws_sdk_dynamodb::Error> {
+    /// Marks as is_dirty = true initially, and records the current generation of each input.
+    pub async fn create_compute_node(&self, id: ComputeNodeId, inputs: Vec<ArtifactId>, node_type: String) -> Result<(), GraphError> {
+        let input_generations = self.input_generations(&inputs).await?;
+        let transaction = self.compute_node_writes(&id, &inputs, node_type, input_generations, None, true);
+
+        let result = self.client.transact_write_items()
+            .set_transact_items(Some(transaction))
+            .send()
+            .await;
+        self.forget_details(&id);
+        result.map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// Like `create_compute_node`, but fails with `UnknownInputs` (creating nothing) if any
+    /// input isn't a registered artifact.
+    pub async fn create_compute_node_strict(&self, id: ComputeNodeId, inputs: Vec<ArtifactId>, node_type: String) -> Result<(), GraphError> {
+        let unknown = self.find_unregistered(&inputs).await?;
+        if !unknown.is_empty() {
+            return Err(UnknownInputs(unknown).into());
+        }
+        self.create_compute_node(id, inputs, node_type).await
+    }
+
+    /// Registers `artifacts` (id, is_external) and creates compute node `id` in a single
+    /// transaction: either everything is written or nothing is. Inputs not among
+    /// `artifacts` must already be registered, or it fails with `UnknownInputs`.
+    /// A transaction holds at most 100 writes: one per artifact, two per input, plus the node.
+    /// If an artifact is listed more than once, its last entry wins.
+    pub async fn register_and_create(&self, artifacts: Vec<(ArtifactId, bool)>, id: ComputeNodeId, inputs: Vec<ArtifactId>, node_type: String) -> Result<(), GraphError> {
+        // One transaction can't write the same item twice
+        let artifacts: BTreeMap<ArtifactId, bool> = artifacts.into_iter().collect();
+        let registering: HashSet<&ArtifactId> = artifacts.keys().collect();
+        let existing: Vec<ArtifactId> = inputs.iter()
+            .filter(|input| !registering.contains(input))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        // Artifacts registered here have no generation yet
+        let input_generations = self.input_generations(&existing).await?;
+        let created_at = chrono::Utc::now().to_rfc3339();
+        let mut transaction = Vec::new();
+        for (artifact_id, is_external) in &artifacts {
+            let put = Put::builder()
+                .table_name(&self.table_name)
+                .item("pk", AttributeValue::S(Self::pk_artifact(artifact_id)))
+                .item("sk", AttributeValue::S(Self::sk_meta()))
+                .item("created_at", AttributeValue::S(created_at.clone()))
+                .item("is_external", AttributeValue::Bool(*is_external))
+                .build()
+                .unwrap();
+            transaction.push(TransactWriteItem::builder().put(put).build());
+        }
+        // Inputs registered earlier must still be there when the node is written
+        for input_id in &existing {
+            let check = ConditionCheck::builder()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(Self::pk_artifact(input_id)))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .condition_expression("attribute_exists(pk)")
+                .build()
+                .unwrap();
+            transaction.push(TransactWriteItem::builder().condition_check(check).build());
+        }
+        transaction.extend(self.compute_node_writes(&id, &inputs, node_type, input_generations, None, true));
+        if transaction.len() > 100 {
+            return Err(format!("register_and_create needs {} transaction items, over DynamoDB's limit of 100", transaction.len()).into());
+        }
+
+        let result = self.client.transact_write_items()
+            .set_transact_items(Some(transaction))
+            .send()
+            .await;
+        self.forget_details(&id);
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_transaction_canceled_exception()) => {
+                let unknown = self.find_unregistered(&existing).await?;
+                if unknown.is_empty() {
+                    Err(aws_sdk_dynamodb::Error::from(e).into())
+                } else {
+                    Err(UnknownInputs(unknown).into())
+                }
+            }
+            Err(e) => Err(aws_sdk_dynamodb::Error::from(e).into()),
+        }
+    }
+
+    /// The current generation of each input that has one, as recorded on a new node.
+    async fn input_generations(&self, inputs: &[ArtifactId]) -> Result<HashMap<ArtifactId, AttributeValue>, GraphError> {
+        // BatchGetItem rejects duplicate keys
+        let unique: BTreeSet<&ArtifactId> = inputs.iter().collect();
+        let keys = unique.into_iter().map(|id| HashMap::from([
+            ("pk".to_string(), AttributeValue::S(Self::pk_artifact(id))),
+            ("sk".to_string(), AttributeValue::S(Self::sk_meta())),
+        ])).collect();
+        Ok(self.batch_get(keys, "pk, generation", false).await?
+            .into_iter()
+            .filter_map(|mut item| {
+                let id = item.get("pk")?.as_s().ok()?.strip_prefix("ARTIFACT#")?.to_string();
+                Some((id, item.remove("generation")?))
+            })
+            .collect())
+    }
+
+    /// Transaction items writing a compute node with its input and producer edges. A new
+    /// node is dirty with no outputs (`None, true`); `import_graph` writes the described
+    /// state instead.
+    fn compute_node_writes(&self, id: &str, inputs: &[ArtifactId], node_type: String, input_generations: HashMap<ArtifactId, AttributeValue>, outputs: Option<&[ArtifactId]>, dirty: bool) -> Vec<TransactWriteItem> {
         let mut transaction = Vec::new();
 
         // 1. Put Compute Node (Meta + Dirty Status)
         let mut compute_put = Put::builder()
             .table_name(&self.table_name)
-            .item("pk", AttributeValue::S(Self::pk_compute(&id)))
+            .item("pk", AttributeValue::S(Self::pk_compute(id)))
             .item("sk", AttributeValue::S(Self::sk_meta()))
-            .item("gsi1pk", AttributeValue::S(Self::gsi1_dirty_pk())) // Mark Dirty
-            .item("gsi1sk", AttributeValue::S(Self::gsi1_compute_sk(&id)))
+            .item("gsi2pk", AttributeValue::S(Self::gsi2_type_pk(&node_type)))
+            .item("gsi2sk", AttributeValue::S(Self::gsi1_compute_sk(id)))
             .item("type", AttributeValue::S(node_type));
+        if dirty {
+            compute_put = compute_put
+                .item("gsi1pk", AttributeValue::S(Self::gsi1_dirty_pk())) // Mark Dirty
+                .item("gsi1sk", AttributeValue::S(Self::gsi1_compute_sk(id)));
+        }
 
         let input_attrs: Vec<AttributeValue> = inputs.iter().map(|i| AttributeValue::S(i.clone())).collect();
         compute_put = compute_put.item("inputs", AttributeValue::L(input_attrs));
+        compute_put = compute_put.item("input_generations", AttributeValue::M(input_generations));
+        if let Some(outputs) = outputs {
+            let output_attrs: Vec<AttributeValue> = outputs.iter().map(|o| AttributeValue::S(o.clone())).collect();
+            compute_put = compute_put.item("outputs", AttributeValue::L(output_attrs));
+        }
 
         transaction.push(TransactWriteItem::builder().put(compute_put.build().unwrap()).build());
 
         // 2. Put Edges (Reverse Index: Artifact -> ComputeNode)
-        for input_id in &inputs {
+        for input_id in inputs.iter().collect::<BTreeSet<_>>() {
             let edge_put = Put::builder()
                 .table_name(&self.table_name)
                 .item("pk", AttributeValue::S(Self::pk_artifact(input_id)))
-                .item("sk", AttributeValue::S(Self::sk_edge_compute(&id)))
+                .item("sk", AttributeValue::S(Self::sk_edge_compute(id)))
                 .build()
                 .unwrap();
             transaction.push(TransactWriteItem::builder().put(edge_put).build());
         }
 
-        self.client.transact_write_items()
-            .set_transact_items(Some(transaction))
-            .send()
-            .await?;
+        // 3. Producer edges for the outputs, if any
+        for output in outputs.unwrap_or_default().iter().collect::<BTreeSet<_>>() {
+            transaction.push(self.producer_edge_put(output, id));
+        }
 
-        Ok(())
+        transaction
+    }
+
+    /// Transaction item recording that compute node `node` produces `artifact`.
+    fn producer_edge_put(&self, artifact: &str, node: &str) -> TransactWriteItem {
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(Self::pk_artifact(artifact)))
+            .item("sk", AttributeValue::S(Self::sk_edge_producer(node)))
+            .build()
+            .unwrap();
+        TransactWriteItem::builder().put(put).build()
+    }
+
+    /// Transaction item dropping the record that compute node `node` produces `artifact`.
+    fn producer_edge_delete(&self, artifact: &str, node: &str) -> TransactWriteItem {
+        let delete = Delete::builder()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_artifact(artifact)))
+            .key("sk", AttributeValue::S(Self::sk_edge_producer(node)))
+            .build()
+            .unwrap();
+        TransactWriteItem::builder().delete(delete).build()
     }
 
     /// Updates the node's sorted outputs.
     /// Side Effect: Sets is_dirty = false (marks as clean).
     /// Marks old outputs as STATUS#ORPHAN.
-    pub async fn set_compute_node_outputs(&self, id: ComputeNodeId, outputs: Vec<ArtifactId>) -> Result<(), aws_sdk_dynamodb::Error> {
+    /// Fails with `NodeNotFound` if the node doesn't exist or was deleted. Setting the
+    /// outputs a clean node already has is a no-op.
+    pub async fn set_compute_node_outputs(&self, id: ComputeNodeId, outputs: Vec<ArtifactId>) -> Result<(), GraphError> {
         // 1. Get current outputs to identify orphans
-        let (_, _old_outputs) = self.get_compute_node_details(id.clone()).await.unwrap_or_default();
-        let (_, old_actual_outputs) = {
-            // Need to fetch outputs specifically, current get_compute_node_details returns inputs.
-            // Let's rely on update_express logic or fetching properly.
-            // Actually get_compute_node_details implementation fetches "type" and "inputs".
-            // We need to fetch "outputs" too.
-            // Let's update get_compute_node_details signature or logic first?
-            // For now, let's fetch the item directly here to be safe.
-            let resp = self.client.get_item()
-                .table_name(&self.table_name)
-                .key("pk", AttributeValue::S(Self::pk_compute(&id)))
-                .key("sk", AttributeValue::S(Self::sk_meta()))
-                .send()
-                .await?;
-            if let Some(item) = resp.item {
-                 let outputs = item.get("outputs").and_then(|av| av.as_l().ok())
-                    .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
-                    .unwrap_or_default();
-                 (String::new(), outputs)
-            } else {
-                (String::new(), vec![])
-            }
+        let resp = self.client.get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_compute(&id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .consistent_read(true)
+            .send()
+            .await?;
+        let Some(item) = resp.item.filter(|item| !item.contains_key("deleted_at")) else {
+            return Err(NodeNotFound(id).into());
         };
+        let old_actual_outputs: Vec<ArtifactId> = item.get("outputs").and_then(|av| av.as_l().ok())
+            .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
+            .unwrap_or_default();
+        let dirty = item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_dirty_pk());
+        if !dirty && item.contains_key("outputs") && old_actual_outputs == outputs {
+            return Ok(());
+        }
 
         // 2. TransactWrite: Update Compute Node + Mark Orphans
         let mut transaction = Vec::new();
@@ -197,14 +716,20 @@ impl DependencyGraph {
             .key("pk", AttributeValue::S(Self::pk_compute(&id)))
             .key("sk", AttributeValue::S(Self::sk_meta()))
             .update_expression("SET outputs = :o REMOVE gsi1pk, gsi1sk")
+            // Guards against the node being removed since it was read
+            .condition_expression("attribute_exists(pk) AND attribute_not_exists(deleted_at)")
             .expression_attribute_values(":o", AttributeValue::L(output_attrs))
             .build()
             .unwrap();
         transaction.push(TransactWriteItem::builder().update(update_compute).build());
+        for output in outputs.iter().collect::<BTreeSet<_>>() {
+            transaction.push(self.producer_edge_put(output, &id));
+        }
 
         // Mark Orphans
-        for old in old_actual_outputs {
+        for old in old_actual_outputs.into_iter().collect::<BTreeSet<_>>() {
             if !outputs.contains(&old) {
+                transaction.push(self.producer_edge_delete(&old, &id));
                  let update_orphan = aws_sdk_dynamodb::types::Update::builder()
                     .table_name(&self.table_name)
                     .key("pk", AttributeValue::S(Self::pk_artifact(&old)))
@@ -218,17 +743,184 @@ impl DependencyGraph {
             }
         }
         
-        self.client.transact_write_items()
+        let result = self.client.transact_write_items()
             .set_transact_items(Some(transaction))
             .send()
-            .await?;
+            .await;
+        self.forget_details(&id);
+        result?;
         
         Ok(())
     }
 
-    /// Deletes a compute node and its input edges.
-    /// Marks output artifacts as STATUS#ORPHAN.
-    pub async fn remove_compute_node(&self, id: ComputeNodeId) -> Result<(), aws_sdk_dynamodb::Error> {
+    /// Reconstructs the graph from the artifact metadata persisted in `storage`.
+    /// Every artifact is re-registered (external unless it names a producer), and each
+    /// producer is recreated with its recorded inputs and set clean with its outputs.
+    /// Existing items are overwritten rather than duplicated, so this is safe to re-run.
+    pub async fn rebuild_from_storage(&self, storage: &ArtifactStorage) -> Result<RebuildSummary, GraphError> {
+        let mut summary = RebuildSummary::default();
+        let mut producers: BTreeMap<ComputeNodeId, (ProducerMetadata, Vec<ArtifactId>)> = BTreeMap::new();
+
+        // 1. Artifacts first, so node creation below records their generations
+        for yaml in storage.list_artifact_metadata().await? {
+            let metadata = match ArtifactMetadata::from_yaml(&yaml) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    tracing::warn!("Skipping unreadable artifact metadata: {}", e);
+                    summary.skipped += 1;
+                    continue;
+                }
+            };
+            self.register_artifact(metadata.id.clone(), metadata.producer.is_none(), metadata.generation).await?;
+            summary.artifacts += 1;
+            if let Some(producer) = metadata.producer {
+                producers.entry(producer.id.clone()).or_insert_with(|| (producer, Vec::new())).1.push(metadata.id);
+            }
+        }
+
+        // 2. Producing nodes and their input edges, then their outputs in one batch
+        let mut updates = Vec::with_capacity(producers.len());
+        for (id, (producer, mut outputs)) in producers {
+            self.create_compute_node(id.clone(), producer.inputs, producer.node_type).await?;
+            outputs.sort();
+            updates.push((id, outputs));
+        }
+        summary.compute_nodes = updates.len();
+        self.set_many_outputs(updates).await?;
+
+        Ok(summary)
+    }
+
+    /// Sets the outputs of several compute nodes at once, clearing their dirty flags.
+    ///
+    /// Current outputs are fetched with `BatchGetItem`, and the node updates plus any
+    /// orphan markings are written in as few `TransactWriteItems` calls as possible
+    /// (each call is limited to 100 items, so large batches are not atomic as a whole).
+    /// As with `set_compute_node_outputs`, it fails with `NodeNotFound` if a node doesn't
+    /// exist or was deleted, and nodes that are already clean with these outputs are
+    /// skipped. If a node is listed more than once, its last entry wins.
+    pub async fn set_many_outputs(&self, updates: Vec<(ComputeNodeId, Vec<ArtifactId>)>) -> Result<(), GraphError> {
+        let mut latest: HashMap<ComputeNodeId, Vec<ArtifactId>> = HashMap::new();
+        let mut order = Vec::new();
+        for (id, outputs) in updates {
+            if latest.insert(id.clone(), outputs).is_none() {
+                order.push(id);
+            }
+        }
+        if order.is_empty() {
+            return Ok(());
+        }
+
+        // 1. Fetch current state of every node to find missing ones, no-ops and orphans
+        let keys = order.iter().map(|id| HashMap::from([
+            ("pk".to_string(), AttributeValue::S(Self::pk_compute(id))),
+            ("sk".to_string(), AttributeValue::S(Self::sk_meta())),
+        ])).collect();
+        let mut current: HashMap<ComputeNodeId, HashMap<String, AttributeValue>> = HashMap::new();
+        for item in self.batch_get(keys, "pk, outputs, gsi1pk, deleted_at", true).await? {
+            let Some(pk) = item.get("pk").and_then(|av| av.as_s().ok()) else { continue };
+            current.insert(pk.trim_start_matches("COMPUTE#").to_string(), item);
+        }
+
+        let mut changed = Vec::new();
+        let mut old_outputs: HashMap<&ComputeNodeId, Vec<ArtifactId>> = HashMap::new();
+        for id in &order {
+            let Some(item) = current.get(id).filter(|item| !item.contains_key("deleted_at")) else {
+                return Err(NodeNotFound(id.clone()).into());
+            };
+            let old: Vec<ArtifactId> = item.get("outputs").and_then(|av| av.as_l().ok())
+                .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
+                .unwrap_or_default();
+            let dirty = item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_dirty_pk());
+            if !dirty && item.contains_key("outputs") && old == latest[id] {
+                continue;
+            }
+            changed.push(id);
+            old_outputs.insert(id, old);
+        }
+
+        // An artifact dropped by one node but produced by another in this batch is not an orphan.
+        let all_new: HashSet<&ArtifactId> = latest.values().flatten().collect();
+
+        // 2. Build the write set: node updates, then each orphan once. `targets` names the
+        // node behind each item so a failed condition can be reported against it.
+        let mut transaction = Vec::new();
+        let mut targets = Vec::new();
+        let mut orphans = BTreeSet::new();
+        for id in changed {
+            let output_attrs: Vec<AttributeValue> = latest[id].iter().map(|i| AttributeValue::S(i.clone())).collect();
+            let update_compute = aws_sdk_dynamodb::types::Update::builder()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(Self::pk_compute(id)))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .update_expression("SET outputs = :o REMOVE gsi1pk, gsi1sk")
+                // Guards against the node being removed since it was read
+                .condition_expression("attribute_exists(pk) AND attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":o", AttributeValue::L(output_attrs))
+                .build()
+                .unwrap();
+            transaction.push(TransactWriteItem::builder().update(update_compute).build());
+            targets.push(Some(id.clone()));
+            for output in latest[id].iter().collect::<BTreeSet<_>>() {
+                transaction.push(self.producer_edge_put(output, id));
+                targets.push(None);
+            }
+
+            for old in old_outputs[id].iter().filter(|old| !latest[id].contains(old)).collect::<BTreeSet<_>>() {
+                transaction.push(self.producer_edge_delete(old, id));
+                targets.push(None);
+                if !all_new.contains(old) {
+                    orphans.insert(old.clone());
+                }
+            }
+        }
+        for old in orphans {
+            let update_orphan = aws_sdk_dynamodb::types::Update::builder()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(Self::pk_artifact(&old)))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .update_expression("SET gsi1pk = :pk, gsi1sk = :sk")
+                .expression_attribute_values(":pk", AttributeValue::S(Self::gsi1_orphan_pk()))
+                .expression_attribute_values(":sk", AttributeValue::S(Self::gsi1_artifact_sk(&old)))
+                .build()
+                .unwrap();
+            transaction.push(TransactWriteItem::builder().update(update_orphan).build());
+            targets.push(None);
+        }
+
+        // 3. TransactWriteItems accepts at most 100 items per call. Cached details are
+        // dropped once each chunk is written, so a read in between can't re-cache old outputs.
+        for (chunk, chunk_targets) in transaction.chunks(100).zip(targets.chunks(100)) {
+            let result = self.client.transact_write_items()
+                .set_transact_items(Some(chunk.to_vec()))
+                .send()
+                .await;
+            for id in chunk_targets.iter().flatten() {
+                self.forget_details(id);
+            }
+            let Err(e) = result else { continue };
+            let removed = e.as_service_error()
+                .and_then(|e| match e {
+                    aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError::TransactionCanceledException(c) => Some(c.cancellation_reasons()),
+                    _ => None,
+                })
+                .and_then(|reasons| reasons.iter().zip(chunk_targets)
+                    .find(|(reason, _)| reason.code() == Some("ConditionalCheckFailed"))
+                    .and_then(|(_, target)| target.clone()));
+            return Err(match removed {
+                Some(id) => NodeNotFound(id).into(),
+                None => aws_sdk_dynamodb::Error::from(e).into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Removes a compute node.
+    /// * `hard = true`: Deletes the node and its input edges, marks output artifacts as STATUS#ORPHAN.
+    /// * `hard = false`: Tombstones the node (STATUS#DELETED) and its input edges so it drops out of
+    ///   dirty/downstream queries but can still be inspected. See `purge_deleted`.
+    pub async fn remove_compute_node(&self, id: ComputeNodeId, hard: bool) -> Result<(), aws_sdk_dynamodb::Error> {
         // 1. Fetch details to find inputs (to delete edges) and outputs (to orphan)
         let resp = self.client.get_item()
                 .table_name(&self.table_name)
@@ -246,9 +938,15 @@ impl DependencyGraph {
                 .unwrap_or_default();
              (inputs, outputs)
         } else {
-            return Ok(()); // Already gone?
+            // Already gone, but a cached copy may outlive it
+            self.forget_details(&id);
+            return Ok(());
         };
 
+        if !hard {
+            return self.tombstone_compute_node(&id, &inputs).await;
+        }
+
         let mut transaction = Vec::new();
 
         // 1. Delete Compute Node
@@ -273,8 +971,9 @@ impl DependencyGraph {
             ).build());
         }
 
-        // 3. Mark Outputs as ORPHAN
-        for output in outputs {
+        // 3. Mark Outputs as ORPHAN, dropping their producer edges
+        for output in outputs.into_iter().collect::<BTreeSet<_>>() {
+            transaction.push(self.producer_edge_delete(&output, &id));
              let update_orphan = aws_sdk_dynamodb::types::Update::builder()
                 .table_name(&self.table_name)
                 .key("pk", AttributeValue::S(Self::pk_artifact(&output)))
@@ -287,16 +986,95 @@ impl DependencyGraph {
             transaction.push(TransactWriteItem::builder().update(update_orphan).build());
         }
 
-        self.client.transact_write_items()
+        let result = self.client.transact_write_items()
             .set_transact_items(Some(transaction))
             .send()
-            .await?;
+            .await;
+        self.forget_details(&id);
+        result?;
+
+        Ok(())
+    }
+
+    /// Marks a compute node and its input edges as deleted without removing them.
+    /// The node moves from STATUS#DIRTY (if dirty) to STATUS#DELETED, keyed by deletion time.
+    async fn tombstone_compute_node(&self, id: &str, inputs: &[ArtifactId]) -> Result<(), aws_sdk_dynamodb::Error> {
+        let deleted_at = chrono::Utc::now().to_rfc3339();
+        let mut transaction = Vec::new();
+
+        let update_compute = aws_sdk_dynamodb::types::Update::builder()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_compute(id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .update_expression("SET deleted_at = :d, gsi1pk = :pk, gsi1sk = :sk REMOVE gsi2pk, gsi2sk")
+            .expression_attribute_values(":d", AttributeValue::S(deleted_at.clone()))
+            .expression_attribute_values(":pk", AttributeValue::S(Self::gsi1_deleted_pk()))
+            .expression_attribute_values(":sk", AttributeValue::S(Self::gsi1_deleted_sk(&deleted_at, id)))
+            .build()
+            .unwrap();
+        transaction.push(TransactWriteItem::builder().update(update_compute).build());
+
+        for input in inputs {
+            let update_edge = aws_sdk_dynamodb::types::Update::builder()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(Self::pk_artifact(input)))
+                .key("sk", AttributeValue::S(Self::sk_edge_compute(id)))
+                .update_expression("SET deleted_at = :d")
+                .expression_attribute_values(":d", AttributeValue::S(deleted_at.clone()))
+                .build()
+                .unwrap();
+            transaction.push(TransactWriteItem::builder().update(update_edge).build());
+        }
+
+        let result = self.client.transact_write_items()
+            .set_transact_items(Some(transaction))
+            .send()
+            .await;
+        self.forget_details(id);
+        result?;
 
         Ok(())
     }
 
-    /// Scans STATUS#ORPHAN index and deletes artifacts if they have no downstream consumers.
-    /// Returns count of deleted items.
+    /// Hard-deletes tombstoned compute nodes deleted more than `older_than` ago.
+    /// Returns count of purged nodes.
+    pub async fn purge_deleted(&self, older_than: std::time::Duration) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap_or(chrono::Duration::zero());
+        let mut purged = 0;
+
+        // gsi1sk starts with the RFC3339 deletion time, so a range condition selects expired tombstones.
+        let mut expired = Vec::new();
+        let mut start_key = None;
+        loop {
+            let resp = self.client.query()
+                .table_name(&self.table_name)
+                .index_name("gsi1")
+                .key_condition_expression("gsi1pk = :pk AND gsi1sk < :cutoff")
+                .expression_attribute_values(":pk", AttributeValue::S(Self::gsi1_deleted_pk()))
+                .expression_attribute_values(":cutoff", AttributeValue::S(cutoff.to_rfc3339()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+            expired.extend(resp.items.unwrap_or_default().into_iter()
+                .filter_map(|item| item.get("pk").and_then(|av| av.as_s().ok())
+                    .map(|pk| pk.trim_start_matches("COMPUTE#").to_string())));
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        for id in expired {
+            self.remove_compute_node(id.clone(), true).await?;
+            self.forget_details(&id);
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    /// Scans STATUS#ORPHAN index and deletes artifacts if they have no downstream consumers
+    /// and no live producer. Returns count of deleted items.
     pub async fn cleanup_orphans(&self) -> Result<usize, aws_sdk_dynamodb::Error> {
         let mut deleted_count = 0;
         
@@ -309,44 +1087,169 @@ impl DependencyGraph {
             .send()
             .await?;
 
-        if let Some(items) = resp.items {
-            for item in items {
-                // Ensure we have the PK (Artifact ID)
-                // GSI projection ALL includes PK.
-                // In STD, main table PK is "ARTIFACT#<id>".
-                if let Some(pk_attr) = item.get("pk") {
-                    if let Ok(pk) = pk_attr.as_s() {
-                         let _artifact_id = pk.trim_start_matches("ARTIFACT#").to_string();
-                         
-                         // 2. Check downstream edges
-                         // We have methods for this, but they are async stream.
-                         // Let's just do a quick count query.
-                         let edges = self.client.query()
-                            .table_name(&self.table_name)
-                            .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
-                            .expression_attribute_values(":pk", AttributeValue::S(pk.clone()))
-                            .expression_attribute_values(":prefix", AttributeValue::S("COMPUTE#".to_string()))
-                            .select(aws_sdk_dynamodb::types::Select::Count)
-                            .send()
-                            .await?;
-                        
-                        if edges.count == 0 {
-                            // 3. Safe to Delete
-                            self.client.delete_item()
-                                .table_name(&self.table_name)
-                                .key("pk", AttributeValue::S(pk.clone()))
-                                .key("sk", AttributeValue::S(Self::sk_meta()))
-                                .send()
-                                .await?;
-                            deleted_count += 1;
-                        }
-                    }
-                }
-            }
+        let orphans: Vec<String> = resp.items.unwrap_or_default().iter()
+            .filter_map(|item| item.get("pk").and_then(|av| av.as_s().ok()).cloned())
+            .collect();
+
+        for pk in orphans {
+            let artifact_id = pk.trim_start_matches("ARTIFACT#").to_string();
+
+            // 2. Check downstream edges, ignoring (and removing) any whose
+            // compute node no longer exists so they can't pin the orphan forever.
+            let live_edges = self.prune_dangling_edges(&pk).await?;
+
+            // A live node still listing the artifact as an output means the orphan mark is
+            // stale. Tombstoned producers don't keep it alive.
+            let producers = self.find_producers(&pk).await?;
+            if live_edges > 0 || producers.iter().any(|(_, _, deleted)| !deleted) {
+                continue;
+            }
+
+            // 3. Safe to Delete, along with the tombstones' now dangling output entries
+            for (node_pk, outputs, _) in producers {
+                let remaining: Vec<AttributeValue> = outputs.into_iter()
+                    .filter(|output| *output != artifact_id)
+                    .map(AttributeValue::S)
+                    .collect();
+                tracing::info!("Removing dangling output {} from {}", artifact_id, node_pk);
+                self.client.update_item()
+                    .table_name(&self.table_name)
+                    .key("pk", AttributeValue::S(node_pk.clone()))
+                    .key("sk", AttributeValue::S(Self::sk_meta()))
+                    .update_expression("SET outputs = :o")
+                    .condition_expression("attribute_exists(deleted_at)")
+                    .expression_attribute_values(":o", AttributeValue::L(remaining))
+                    .send()
+                    .await?;
+                self.client.delete_item()
+                    .table_name(&self.table_name)
+                    .key("pk", AttributeValue::S(pk.clone()))
+                    .key("sk", AttributeValue::S(Self::sk_edge_producer(node_pk.trim_start_matches("COMPUTE#"))))
+                    .send()
+                    .await?;
+            }
+            self.client.delete_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(pk.clone()))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .send()
+                .await?;
+            deleted_count += 1;
         }
         Ok(deleted_count)
     }
 
+    /// Compute nodes (including tombstones) that produce the artifact item `pk`, found
+    /// through its producer edges, as `(node pk, outputs, is tombstoned)`. Edges whose node
+    /// is gone or no longer lists the artifact are deleted rather than returned.
+    async fn find_producers(&self, pk: &str) -> Result<Vec<(String, Vec<ArtifactId>, bool)>, aws_sdk_dynamodb::Error> {
+        let artifact_id = pk.trim_start_matches("ARTIFACT#");
+        let mut edges = Vec::new();
+        let mut start_key = None;
+        loop {
+            let resp = self.client.query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                .expression_attribute_values(":pk", AttributeValue::S(pk.to_string()))
+                .expression_attribute_values(":prefix", AttributeValue::S("PRODUCER#".to_string()))
+                .projection_expression("sk")
+                .set_exclusive_start_key(start_key)
+                .consistent_read(true)
+                .send()
+                .await?;
+            edges.extend(resp.items.unwrap_or_default().into_iter()
+                .filter_map(|edge| edge.get("sk").and_then(|av| av.as_s().ok()).cloned()));
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        let mut producers = Vec::new();
+        for sk in edges {
+            let node_pk = Self::pk_compute(sk.trim_start_matches("PRODUCER#"));
+            let node = self.client.get_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(node_pk.clone()))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .projection_expression("outputs, deleted_at")
+                .consistent_read(true)
+                .send()
+                .await?;
+            let producer = node.item.map(|item| {
+                let outputs: Vec<ArtifactId> = item.get("outputs").and_then(|av| av.as_l().ok())
+                    .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
+                    .unwrap_or_default();
+                (node_pk, outputs, item.contains_key("deleted_at"))
+            });
+            match producer {
+                Some(producer) if producer.1.iter().any(|output| output == artifact_id) => producers.push(producer),
+                _ => {
+                    tracing::info!("Removing stale producer edge {} -> {}", pk, sk);
+                    self.client.delete_item()
+                        .table_name(&self.table_name)
+                        .key("pk", AttributeValue::S(pk.to_string()))
+                        .key("sk", AttributeValue::S(sk))
+                        .send()
+                        .await?;
+                }
+            }
+        }
+        Ok(producers)
+    }
+
+    /// Deletes the consumer edges under artifact item `pk` whose compute node no longer
+    /// exists (e.g. the node item was removed without its edges). Tombstoned nodes still
+    /// exist, so their edges are kept until `purge_deleted`. Returns the remaining edge count.
+    async fn prune_dangling_edges(&self, pk: &str) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let mut edges = Vec::new();
+        let mut start_key = None;
+        loop {
+            let resp = self.client.query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                .expression_attribute_values(":pk", AttributeValue::S(pk.to_string()))
+                .expression_attribute_values(":prefix", AttributeValue::S("COMPUTE#".to_string()))
+                .projection_expression("sk")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+            edges.extend(resp.items.unwrap_or_default().into_iter()
+                .filter_map(|edge| edge.get("sk").and_then(|av| av.as_s().ok()).cloned()));
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        let mut live = 0;
+        for sk in edges {
+            // The edge's sort key is the consuming node's partition key.
+            let node = self.client.get_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(sk.clone()))
+                .key("sk", AttributeValue::S(Self::sk_meta()))
+                .projection_expression("pk")
+                .consistent_read(true)
+                .send()
+                .await?;
+            if node.item.is_some() {
+                live += 1;
+                continue;
+            }
+            tracing::info!("Removing dangling edge {} -> {}", pk, sk);
+            self.client.delete_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(pk.to_string()))
+                .key("sk", AttributeValue::S(sk))
+                .send()
+                .await?;
+        }
+        Ok(live)
+    }
+
     /// Finds all compute nodes that use this artifact as an input.
     pub fn get_downstream_compute_nodes(&self, artifact_id: ArtifactId) -> Pin<Box<dyn Stream<Item = Result<ComputeNodeId, aws_sdk_dynamodb::Error>> + Send>> {
         let pk = Self::pk_artifact(&artifact_id);
@@ -358,6 +1261,7 @@ impl DependencyGraph {
             let resp = client.query()
                 .table_name(table)
                 .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                .filter_expression("attribute_not_exists(deleted_at)")
                 .expression_attribute_values(":pk", AttributeValue::S(pk))
                 .expression_attribute_values(":prefix", AttributeValue::S("COMPUTE#".to_string()))
                 .send()
@@ -378,8 +1282,116 @@ impl DependencyGraph {
         Box::pin(stream)
     }
 
-    /// Returns (node_type, inputs).
-    pub async fn get_compute_node_details(&self, id: ComputeNodeId) -> Result<(String, Vec<ArtifactId>), aws_sdk_dynamodb::Error> {
+    /// Paged variant of `get_downstream_compute_nodes`. Tombstones are filtered after
+    /// DynamoDB applies `limit`, so a page can hold fewer than `limit` items.
+    pub async fn get_downstream_compute_nodes_page(&self, artifact_id: ArtifactId, cursor: Option<&str>, limit: usize) -> Result<Page<ComputeNodeId>, GraphError> {
+        let resp = self.client.query()
+            .table_name(&self.table_name)
+            .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+            .filter_expression("attribute_not_exists(deleted_at)")
+            .expression_attribute_values(":pk", AttributeValue::S(Self::pk_artifact(&artifact_id)))
+            .expression_attribute_values(":prefix", AttributeValue::S("COMPUTE#".to_string()))
+            .limit(page_limit(limit)?)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
+            .send()
+            .await?;
+
+        let items = resp.items.unwrap_or_default().iter()
+            .filter_map(|item| item.get("sk").and_then(|sk| sk.as_s().ok()))
+            .map(|sk| sk.trim_start_matches("COMPUTE#").to_string())
+            .collect();
+        Ok(Page { items, next_cursor: resp.last_evaluated_key.as_ref().and_then(encode_cursor) })
+    }
+
+    /// Walks the graph breadth-first from an artifact, following consumer edges to compute nodes
+    /// and their outputs to the next level of consumers.
+    /// Yields `(node_id, depth)` where immediate consumers are depth 1.
+    /// Each node is yielded once (at its shallowest depth); cycles are not re-entered.
+    /// `max_depth` of `None` walks the full transitive closure.
+    pub fn get_transitive_downstream(&self, artifact_id: ArtifactId, max_depth: Option<usize>) -> DownstreamStream {
+        let client = self.client.clone();
+        let table = self.table_name.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut visited_artifacts = HashSet::new();
+            let mut visited_nodes = HashSet::new();
+            let mut frontier = VecDeque::new();
+
+            visited_artifacts.insert(artifact_id.clone());
+            frontier.push_back((artifact_id, 0usize));
+
+            while let Some((artifact, depth)) = frontier.pop_front() {
+                let next_depth = depth + 1;
+                if max_depth.is_some_and(|max| next_depth > max) {
+                    continue;
+                }
+
+                for node_id in Self::query_consumers(&client, &table, &artifact).await? {
+                    if !visited_nodes.insert(node_id.clone()) {
+                        continue;
+                    }
+                    for output in Self::query_outputs(&client, &table, &node_id).await? {
+                        if visited_artifacts.insert(output.clone()) {
+                            frontier.push_back((output, next_depth));
+                        }
+                    }
+                    yield (node_id, next_depth);
+                }
+            }
+        };
+        Box::pin(stream)
+    }
+
+    /// Every live compute node consuming `artifact_id`, reading all pages of the edge query
+    /// (tombstoned edges still count toward each page until they are purged).
+    async fn query_consumers(client: &Client, table: &str, artifact_id: &str) -> Result<Vec<ComputeNodeId>, aws_sdk_dynamodb::Error> {
+        let mut consumers = Vec::new();
+        let mut start_key = None;
+        loop {
+            let resp = client.query()
+                .table_name(table)
+                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                .filter_expression("attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":pk", AttributeValue::S(Self::pk_artifact(artifact_id)))
+                .expression_attribute_values(":prefix", AttributeValue::S("COMPUTE#".to_string()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            consumers.extend(resp.items.unwrap_or_default().iter()
+                .filter_map(|item| item.get("sk").and_then(|av| av.as_s().ok()))
+                .map(|sk| sk.trim_start_matches("COMPUTE#").to_string()));
+
+            match resp.last_evaluated_key {
+                Some(key) => start_key = Some(key),
+                None => break,
+            }
+        }
+        Ok(consumers)
+    }
+
+    async fn query_outputs(client: &Client, table: &str, node_id: &str) -> Result<Vec<ArtifactId>, aws_sdk_dynamodb::Error> {
+        let resp = client.get_item()
+            .table_name(table)
+            .key("pk", AttributeValue::S(Self::pk_compute(node_id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .send()
+            .await?;
+
+        Ok(resp.item.and_then(|item| item.get("outputs").and_then(|av| av.as_l().ok())
+                .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect()))
+            .unwrap_or_default())
+    }
+
+    /// Returns (node_type, inputs, input_generations).
+    /// `input_generations` holds the generation of each input the node was created against;
+    /// inputs registered without a generation are absent.
+    /// Served from the details cache when one is enabled (see `with_details_cache`).
+    pub async fn get_compute_node_details(&self, id: ComputeNodeId) -> Result<NodeDetails, aws_sdk_dynamodb::Error> {
+        if let Some(details) = self.details_cache.as_ref().and_then(|cache| cache.get(&id)) {
+            return Ok(details);
+        }
+
         let resp = self.client.get_item()
             .table_name(&self.table_name)
             .key("pk", AttributeValue::S(Self::pk_compute(&id)))
@@ -392,15 +1404,582 @@ impl DependencyGraph {
             let inputs = item.get("inputs").and_then(|av| av.as_l().ok())
                 .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
                 .unwrap_or_default();
+            let input_generations = item.get("input_generations").and_then(|av| av.as_m().ok())
+                .map(|m| m.iter().filter_map(|(k, av)| av.as_s().ok().map(|g| (k.clone(), g.clone()))).collect())
+                .unwrap_or_default();
             
-            Ok((node_type, inputs))
+            let details = (node_type, inputs, input_generations);
+            if let Some(cache) = &self.details_cache {
+                cache.insert(id, details.clone());
+            }
+            Ok(details)
         } else {
             // Or return error if not found? For now empty default or error logic
-             Ok(("".to_string(), vec![]))
+             Ok(("".to_string(), vec![], HashMap::new()))
              // In real app, might want specific NotFound error
         }
     }
 
+    /// True if the node exists and hasn't been soft-deleted. `get_compute_node_details` still
+    /// returns tombstones, so callers that only want live nodes check this first.
+    pub async fn is_compute_node_live(&self, id: ComputeNodeId) -> Result<bool, aws_sdk_dynamodb::Error> {
+        let resp = self.client.get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(Self::pk_compute(&id)))
+            .key("sk", AttributeValue::S(Self::sk_meta()))
+            .projection_expression("pk, deleted_at")
+            .send()
+            .await?;
+        Ok(resp.item.is_some_and(|item| !item.contains_key("deleted_at")))
+    }
+
+    /// True if any input's current generation differs from the one the node was created against.
+    pub async fn is_compute_node_stale(&self, id: ComputeNodeId) -> Result<bool, aws_sdk_dynamodb::Error> {
+        let (_, inputs, built_against) = self.get_compute_node_details(id).await?;
+        for input_id in inputs {
+            let current = self.get_artifact_generation(input_id.clone()).await?;
+            if current.as_ref() != built_against.get(&input_id) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns a health snapshot of the graph.
+    /// Dirty and orphan counts come from `Select::Count` queries on GSI1. There is no index
+    /// by entity type, so totals need one scan, projected down to `pk` and filtered to META items.
+    pub async fn stats(&self) -> Result<GraphStats, aws_sdk_dynamodb::Error> {
+        let mut stats = GraphStats {
+            dirty: self.count_gsi1(Self::gsi1_dirty_pk()).await?,
+            orphans: self.count_gsi1(Self::gsi1_orphan_pk()).await?,
+            ..Default::default()
+        };
+
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .projection_expression("pk")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                match item.get("pk").and_then(|av| av.as_s().ok()) {
+                    Some(pk) if pk.starts_with("ARTIFACT#") => stats.artifacts += 1,
+                    Some(pk) if pk.starts_with("COMPUTE#") => stats.compute_nodes += 1,
+                    _ => {}
+                }
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Captures every live compute node and whether it is dirty, for `GraphSnapshot::diff`.
+    /// This is a full table scan, so it is meant for occasional CI checkpoints.
+    pub async fn snapshot(&self) -> Result<GraphSnapshot, aws_sdk_dynamodb::Error> {
+        let mut snapshot = GraphSnapshot {
+            taken_at: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
+        };
+
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND begins_with(pk, :compute) AND attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .expression_attribute_values(":compute", AttributeValue::S("COMPUTE#".to_string()))
+                .projection_expression("pk, gsi1pk")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                let Some(pk) = item.get("pk").and_then(|av| av.as_s().ok()) else { continue };
+                let dirty = item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_dirty_pk());
+                snapshot.nodes.insert(pk.trim_start_matches("COMPUTE#").to_string(), dirty);
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the graph in `description`: artifacts first, then each node with its input
+    /// edges, outputs and dirty state (a node is given outputs if it has output edges or
+    /// is clean). Writes go out in transactions of up to 100 items, each holding whole
+    /// nodes, so the import as a whole is not atomic. Re-importing replaces what an earlier
+    /// import wrote: nodes lose input edges no longer described, and outputs they no longer
+    /// produce are marked orphaned, as with `set_compute_node_outputs`. Repeated edges are
+    /// written once. Fails before writing anything if an edge names a node not in
+    /// `description.nodes`.
+    pub async fn import_graph(&self, description: GraphDescription) -> Result<(), GraphError> {
+        let mut inputs: BTreeMap<&ComputeNodeId, Vec<ArtifactId>> = BTreeMap::new();
+        let mut outputs: BTreeMap<&ComputeNodeId, Vec<ArtifactId>> = BTreeMap::new();
+        let nodes: BTreeMap<&ComputeNodeId, &NodeDescription> = description.nodes.iter().map(|node| (&node.id, node)).collect();
+        for edge in &description.edges {
+            let (node, artifact, list) = match edge {
+                GraphEdge::Input { artifact, node } => (node, artifact, &mut inputs),
+                GraphEdge::Output { node, artifact } => (node, artifact, &mut outputs),
+            };
+            let Some((id, _)) = nodes.get_key_value(node) else {
+                return Err(format!("Edge to {} names a compute node not in the description", node).into());
+            };
+            let artifacts = list.entry(*id).or_default();
+            if !artifacts.contains(artifact) {
+                artifacts.push(artifact.clone());
+            }
+        }
+
+        // Inputs and outputs the nodes have now, if they were written before
+        let keys = nodes.keys().map(|id| HashMap::from([
+            ("pk".to_string(), AttributeValue::S(Self::pk_compute(id))),
+            ("sk".to_string(), AttributeValue::S(Self::sk_meta())),
+        ])).collect();
+        let mut existing: HashMap<ComputeNodeId, (Vec<ArtifactId>, Vec<ArtifactId>)> = HashMap::new();
+        for item in self.batch_get(keys, "pk, inputs, outputs", true).await? {
+            let Some(pk) = item.get("pk").and_then(|av| av.as_s().ok()) else { continue };
+            let list = |name: &str| item.get(name).and_then(|av| av.as_l().ok())
+                .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
+                .unwrap_or_default();
+            existing.insert(pk.trim_start_matches("COMPUTE#").to_string(), (list("inputs"), list("outputs")));
+        }
+
+        // 1. Artifacts, one item each (a repeated id keeps its last description)
+        let artifacts: BTreeMap<&ArtifactId, &ArtifactDescription> = description.artifacts.iter().map(|a| (&a.id, a)).collect();
+        let created_at = chrono::Utc::now().to_rfc3339();
+        let mut groups = Vec::new();
+        for artifact in artifacts.values() {
+            let mut put = Put::builder()
+                .table_name(&self.table_name)
+                .item("pk", AttributeValue::S(Self::pk_artifact(&artifact.id)))
+                .item("sk", AttributeValue::S(Self::sk_meta()))
+                .item("created_at", AttributeValue::S(created_at.clone()))
+                .item("is_external", AttributeValue::Bool(artifact.is_external));
+            if let Some(generation) = &artifact.generation {
+                put = put.item("generation", AttributeValue::S(generation.clone()));
+            }
+            groups.push(vec![TransactWriteItem::builder().put(put.build().unwrap()).build()]);
+        }
+        self.write_groups(groups).await?;
+
+        // 2. Nodes in their described state with their input edges, recording the
+        // generations just imported, and dropping edges from inputs they no longer have
+        let all_outputs: HashSet<&ArtifactId> = outputs.values().flatten().collect();
+        let mut orphans = BTreeSet::new();
+        let mut groups = Vec::new();
+        for (id, node) in &nodes {
+            let node_inputs = inputs.remove(id).unwrap_or_default();
+            let node_outputs = outputs.get(id).map(Vec::as_slice).or(if node.dirty { None } else { Some(&[]) });
+            let mut input_generations = HashMap::new();
+            for input_id in &node_inputs {
+                let generation = match artifacts.get(input_id) {
+                    Some(artifact) => artifact.generation.clone(),
+                    None => self.get_artifact_generation(input_id.clone()).await?,
+                };
+                if let Some(generation) = generation {
+                    input_generations.insert(input_id.clone(), AttributeValue::S(generation));
+                }
+            }
+            let mut group = self.compute_node_writes(id, &node_inputs, node.node_type.clone(), input_generations, node_outputs, node.dirty);
+            if let Some((old_inputs, old_outputs)) = existing.get(*id) {
+                for old in old_inputs.iter().filter(|old| !node_inputs.contains(old)) {
+                    let delete = Delete::builder()
+                        .table_name(&self.table_name)
+                        .key("pk", AttributeValue::S(Self::pk_artifact(old)))
+                        .key("sk", AttributeValue::S(Self::sk_edge_compute(id)))
+                        .build()
+                        .unwrap();
+                    group.push(TransactWriteItem::builder().delete(delete).build());
+                }
+                let node_outputs = node_outputs.unwrap_or_default();
+                for old in old_outputs.iter().filter(|old| !node_outputs.contains(old)) {
+                    group.push(self.producer_edge_delete(old, id));
+                }
+                orphans.extend(old_outputs.iter().filter(|old| !all_outputs.contains(old)).cloned());
+            }
+            groups.push(group);
+        }
+        self.write_groups(groups).await?;
+        for id in nodes.keys() {
+            self.forget_details(id);
+        }
+
+        // 3. Outputs the re-imported nodes no longer produce
+        for id in orphans {
+            self.mark_artifact_orphaned(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `groups` of transaction items in as few transactions as possible without
+    /// splitting a group across two.
+    async fn write_groups(&self, groups: Vec<Vec<TransactWriteItem>>) -> Result<(), GraphError> {
+        let mut transaction: Vec<TransactWriteItem> = Vec::new();
+        for group in groups {
+            if group.len() > 100 {
+                return Err(format!("A single compute node needs {} transaction items, over DynamoDB's limit of 100", group.len()).into());
+            }
+            if transaction.len() + group.len() > 100 {
+                self.client.transact_write_items()
+                    .set_transact_items(Some(std::mem::take(&mut transaction)))
+                    .send()
+                    .await
+                    .map_err(aws_sdk_dynamodb::Error::from)?;
+            }
+            transaction.extend(group);
+        }
+        if !transaction.is_empty() {
+            self.client.transact_write_items()
+                .set_transact_items(Some(transaction))
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+        }
+        Ok(())
+    }
+
+    /// Describes every artifact and live compute node, for `import_graph` into another
+    /// table. Orphan marks are not included. This is a full table scan.
+    pub async fn export_graph(&self) -> Result<GraphDescription, aws_sdk_dynamodb::Error> {
+        let mut artifacts = BTreeMap::new();
+        let mut nodes = BTreeMap::new();
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                let Some(pk) = item.get("pk").and_then(|av| av.as_s().ok()) else { continue };
+                let string_list = |name: &str| -> Vec<ArtifactId> {
+                    item.get(name).and_then(|av| av.as_l().ok())
+                        .map(|l| l.iter().filter_map(|av| av.as_s().ok().cloned()).collect())
+                        .unwrap_or_default()
+                };
+                if let Some(id) = pk.strip_prefix("ARTIFACT#") {
+                    artifacts.insert(id.to_string(), ArtifactDescription {
+                        id: id.to_string(),
+                        is_external: item.get("is_external").and_then(|av| av.as_bool().ok()).copied().unwrap_or(false),
+                        generation: item.get("generation").and_then(|av| av.as_s().ok()).cloned(),
+                    });
+                } else if let Some(id) = pk.strip_prefix("COMPUTE#") {
+                    let node = NodeDescription {
+                        id: id.to_string(),
+                        node_type: item.get("type").and_then(|av| av.as_s().ok()).cloned().unwrap_or_default(),
+                        dirty: item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_dirty_pk()),
+                    };
+                    nodes.insert(id.to_string(), (node, string_list("inputs"), string_list("outputs")));
+                }
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        let mut description = GraphDescription { artifacts: artifacts.into_values().collect(), ..Default::default() };
+        for (node, inputs, outputs) in nodes.into_values() {
+            description.edges.extend(inputs.into_iter().map(|artifact| GraphEdge::Input { artifact, node: node.id.clone() }));
+            description.edges.extend(outputs.into_iter().map(|artifact| GraphEdge::Output { node: node.id.clone(), artifact }));
+            description.nodes.push(node);
+        }
+        Ok(description)
+    }
+
+    async fn count_gsi1(&self, gsi1pk: String) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let mut count = 0;
+        let mut start_key = None;
+        loop {
+            let resp = self.client.query()
+                .table_name(&self.table_name)
+                .index_name("gsi1")
+                .key_condition_expression("gsi1pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(gsi1pk.clone()))
+                .select(aws_sdk_dynamodb::types::Select::Count)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+            count += resp.count as usize;
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Streams internal (non-external) artifacts that no live compute node lists as an
+    /// output and that aren't already marked orphaned for GC. These indicate a graph
+    /// integrity problem. The whole table is scanned before the first id is yielded.
+    pub fn find_dangling_artifacts(&self) -> Pin<Box<dyn Stream<Item = Result<ArtifactId, aws_sdk_dynamodb::Error>> + Send>> {
+        let client = self.client.clone();
+        let table = self.table_name.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut internal = BTreeSet::new();
+            let mut produced = HashSet::new();
+            let mut start_key = None;
+            loop {
+                let resp = client.scan()
+                    .table_name(&table)
+                    .filter_expression("sk = :meta AND attribute_not_exists(deleted_at)")
+                    .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                    .projection_expression("pk, is_external, outputs, gsi1pk")
+                    .set_exclusive_start_key(start_key)
+                    .send()
+                    .await?;
+
+                for item in resp.items.unwrap_or_default() {
+                    let Some(pk) = item.get("pk").and_then(|av| av.as_s().ok()) else { continue };
+                    if let Some(id) = pk.strip_prefix("ARTIFACT#") {
+                        let external = item.get("is_external").and_then(|av| av.as_bool().ok()).copied().unwrap_or(false);
+                        let orphaned = item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_orphan_pk());
+                        if !external && !orphaned {
+                            internal.insert(id.to_string());
+                        }
+                    } else if let Some(outputs) = item.get("outputs").and_then(|av| av.as_l().ok()) {
+                        produced.extend(outputs.iter().filter_map(|av| av.as_s().ok().cloned()));
+                    }
+                }
+
+                start_key = resp.last_evaluated_key;
+                if start_key.is_none() {
+                    break;
+                }
+            }
+
+            for id in internal {
+                if !produced.contains(&id) {
+                    yield id;
+                }
+            }
+        };
+        Box::pin(stream)
+    }
+
+    /// Compares the artifacts in `storage` with those registered in the graph and cleans up
+    /// both sides: storage-only artifacts are deleted from storage, and graph-only internal
+    /// artifacts are marked orphaned. External artifacts may live outside `storage`, so
+    /// they are never reported as graph-only. Storage-only artifacts whose objects can't be
+    /// told apart from others' are reported as `undeletable` instead. With `dry_run`,
+    /// nothing is changed.
+    ///
+    /// Saving an artifact and registering it are separate writes, so an artifact being
+    /// produced right now looks like it is on one side only. Only artifacts written (or
+    /// registered) more than `older_than` ago are considered; set it well above the
+    /// longest build step, since a too-short window can delete the output of one in flight.
+    pub async fn reconcile(&self, storage: &ArtifactStorage, older_than: std::time::Duration, dry_run: bool) -> Result<ReconcileReport, GraphError> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap_or(chrono::Duration::zero());
+        let mut stored = BTreeSet::new();
+        let mut recent = BTreeSet::new();
+        for (yaml, modified) in storage.list_artifact_metadata_with_times().await? {
+            match ArtifactMetadata::from_yaml(&yaml) {
+                Ok(metadata) => {
+                    if modified >= cutoff {
+                        recent.insert(metadata.id.clone());
+                    }
+                    stored.insert(metadata.id);
+                }
+                Err(e) => tracing::warn!("Skipping unreadable artifact metadata: {}", e),
+            }
+        }
+
+        let mut registered = BTreeSet::new();
+        let mut internal = BTreeSet::new();
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND begins_with(pk, :artifact) AND attribute_not_exists(deleted_at)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .expression_attribute_values(":artifact", AttributeValue::S("ARTIFACT#".to_string()))
+                .projection_expression("pk, is_external, gsi1pk, created_at")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                let Some(id) = item.get("pk").and_then(|av| av.as_s().ok()).and_then(|pk| pk.strip_prefix("ARTIFACT#")) else { continue };
+                let external = item.get("is_external").and_then(|av| av.as_bool().ok()).copied().unwrap_or(false);
+                let orphaned = item.get("gsi1pk").and_then(|av| av.as_s().ok()) == Some(&Self::gsi1_orphan_pk());
+                let created_at = item.get("created_at").and_then(|av| av.as_s().ok())
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok());
+                registered.insert(id.to_string());
+                if created_at.is_some_and(|t| t >= cutoff) {
+                    recent.insert(id.to_string());
+                }
+                if !external && !orphaned {
+                    internal.insert(id.to_string());
+                }
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        let (storage_only, undeletable) = stored.difference(&registered)
+            .filter(|id| !recent.contains(*id))
+            .cloned()
+            .partition(|id| storage.has_own_directory(id));
+        let report = ReconcileReport {
+            storage_only,
+            graph_only: internal.difference(&stored).filter(|id| !recent.contains(*id)).cloned().collect(),
+            undeletable,
+            dry_run,
+        };
+        if !dry_run {
+            for id in &report.storage_only {
+                storage.delete_artifact(id).await?;
+            }
+            for id in &report.graph_only {
+                self.mark_artifact_orphaned(id.clone()).await?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Sets the `gsi2` keys on live compute nodes written before that index existed, so
+    /// `get_compute_nodes_by_type` lists them. Returns the number of nodes updated.
+    /// `bootstrap::create_graph_table` runs this when it adds the index to an older table.
+    pub async fn backfill_type_index(&self) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let mut updated = 0;
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND begins_with(pk, :compute) AND attribute_not_exists(deleted_at) AND attribute_not_exists(gsi2pk)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .expression_attribute_values(":compute", AttributeValue::S("COMPUTE#".to_string()))
+                .projection_expression("pk, #type")
+                .expression_attribute_names("#type", "type")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                let Some(id) = item.get("pk").and_then(|av| av.as_s().ok()).and_then(|pk| pk.strip_prefix("COMPUTE#")) else { continue };
+                let Some(node_type) = item.get("type").and_then(|av| av.as_s().ok()) else { continue };
+                let result = self.client.update_item()
+                    .table_name(&self.table_name)
+                    .key("pk", AttributeValue::S(Self::pk_compute(id)))
+                    .key("sk", AttributeValue::S(Self::sk_meta()))
+                    .update_expression("SET gsi2pk = :pk, gsi2sk = :sk")
+                    // A node deleted since the scan stays out of the index
+                    .condition_expression("attribute_exists(pk) AND attribute_not_exists(deleted_at)")
+                    .expression_attribute_values(":pk", AttributeValue::S(Self::gsi2_type_pk(node_type)))
+                    .expression_attribute_values(":sk", AttributeValue::S(Self::gsi1_compute_sk(id)))
+                    .send()
+                    .await;
+                match result {
+                    Ok(_) => updated += 1,
+                    Err(e) if e.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Writes the producer edges for compute nodes (tombstones included) whose outputs were
+    /// set before those edges existed, so `cleanup_orphans` sees their producers. Existing
+    /// edges are rewritten unchanged, so this is safe to re-run. Returns the number of edges
+    /// written.
+    pub async fn backfill_producer_edges(&self) -> Result<usize, aws_sdk_dynamodb::Error> {
+        let mut written = 0;
+        let mut start_key = None;
+        loop {
+            let resp = self.client.scan()
+                .table_name(&self.table_name)
+                .filter_expression("sk = :meta AND begins_with(pk, :compute) AND attribute_exists(outputs)")
+                .expression_attribute_values(":meta", AttributeValue::S(Self::sk_meta()))
+                .expression_attribute_values(":compute", AttributeValue::S("COMPUTE#".to_string()))
+                .projection_expression("pk, outputs")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in resp.items.unwrap_or_default() {
+                let Some(id) = item.get("pk").and_then(|av| av.as_s().ok()).and_then(|pk| pk.strip_prefix("COMPUTE#")) else { continue };
+                let outputs: BTreeSet<&String> = item.get("outputs").and_then(|av| av.as_l().ok())
+                    .map(|l| l.iter().filter_map(|av| av.as_s().ok()).collect())
+                    .unwrap_or_default();
+                for output in outputs {
+                    self.client.put_item()
+                        .table_name(&self.table_name)
+                        .item("pk", AttributeValue::S(Self::pk_artifact(output)))
+                        .item("sk", AttributeValue::S(Self::sk_edge_producer(id)))
+                        .send()
+                        .await?;
+                    written += 1;
+                }
+            }
+
+            start_key = resp.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Streams the ids of live compute nodes of `node_type`, via `gsi2`.
+    /// Nodes written before the index existed are listed once `backfill_type_index` has run.
+    pub fn get_compute_nodes_by_type(&self, node_type: String) -> Pin<Box<dyn Stream<Item = Result<ComputeNodeId, aws_sdk_dynamodb::Error>> + Send>> {
+        let client = self.client.clone();
+        let table = self.table_name.clone();
+
+        let stream = async_stream::try_stream! {
+            let mut start_key = None;
+            loop {
+                let resp = client.query()
+                    .table_name(&table)
+                    .index_name("gsi2")
+                    .key_condition_expression("gsi2pk = :pk")
+                    .expression_attribute_values(":pk", AttributeValue::S(Self::gsi2_type_pk(&node_type)))
+                    .set_exclusive_start_key(start_key)
+                    .send()
+                    .await?;
+
+                for item in resp.items.unwrap_or_default() {
+                    if let Some(id) = item.get("pk").and_then(|av| av.as_s().ok()).and_then(|pk| pk.strip_prefix("COMPUTE#")) {
+                        yield id.to_string();
+                    }
+                }
+
+                start_key = resp.last_evaluated_key;
+                if start_key.is_none() {
+                    break;
+                }
+            }
+        };
+        Box::pin(stream)
+    }
+
     /// Returns all compute nodes that need execution.
     pub fn get_dirty_compute_nodes(&self) -> Pin<Box<dyn Stream<Item = Result<ComputeNodeId, aws_sdk_dynamodb::Error>> + Send>> {
         let client = self.client.clone();
@@ -429,4 +2008,23 @@ impl DependencyGraph {
         };
         Box::pin(stream)
     }
+
+    /// Paged variant of `get_dirty_compute_nodes`, for listing `limit` nodes at a time.
+    pub async fn get_dirty_compute_nodes_page(&self, cursor: Option<&str>, limit: usize) -> Result<Page<ComputeNodeId>, GraphError> {
+        let resp = self.client.query()
+            .table_name(&self.table_name)
+            .index_name("gsi1")
+            .key_condition_expression("gsi1pk = :pk")
+            .expression_attribute_values(":pk", AttributeValue::S(Self::gsi1_dirty_pk()))
+            .limit(page_limit(limit)?)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
+            .send()
+            .await?;
+
+        let items = resp.items.unwrap_or_default().iter()
+            .filter_map(|item| item.get("gsi1sk").and_then(|sk| sk.as_s().ok()))
+            .map(|sk| sk.trim_start_matches("COMPUTE#").to_string())
+            .collect();
+        Ok(Page { items, next_cursor: resp.last_evaluated_key.as_ref().and_then(encode_cursor) })
+    }
 }
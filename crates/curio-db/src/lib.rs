@@ -1,16 +1,25 @@
 use aws_sdk_dynamodb::{Client, config::Region};
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_dynamodb::types::AttributeValue;
+use futures::stream::Stream;
+use std::collections::HashMap;
 use std::env;
+use std::pin::Pin;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub mod dependency_graph;
 pub use dependency_graph::DependencyGraph;
 
 pub mod storage;
-pub use storage::ArtifactStorage;
+pub use storage::{AccessEvent, AccessLogger, ArtifactMetadata, ArtifactStorage, IdScheme, ProducerMetadata};
 
 pub mod config;
-pub use config::{CurioConfig, ExternalInputRule};
+pub use config::{ConfigSource, ConfigWatcher, CurioConfig, ExternalInputRule};
+
+pub mod bootstrap;
+
+/// `(id, data)` pairs yielded by `CurioDbClient::scan_records`.
+pub type RecordStream = Pin<Box<dyn Stream<Item = Result<(String, String), aws_sdk_dynamodb::Error>> + Send>>;
 
 pub struct CurioDbClient {
     client: Client,
@@ -35,30 +44,107 @@ impl CurioDbClient {
         Self { client, table_name }
     }
 
-    pub async fn save_record(&self, id: &str, data: &str) -> Result<(), aws_sdk_dynamodb::Error> {
+    /// Stores `attrs` as the record `id`, replacing any existing one. The key attribute
+    /// `id` is always set from `id`, overriding any `id` in `attrs`.
+    pub async fn save_item(&self, id: &str, mut attrs: HashMap<String, AttributeValue>) -> Result<(), aws_sdk_dynamodb::Error> {
+        attrs.insert("id".to_string(), AttributeValue::S(id.to_string()));
         self.client.put_item()
             .table_name(&self.table_name)
-            .item("id", AttributeValue::S(id.to_string()))
-            .item("data", AttributeValue::S(data.to_string()))
+            .set_item(Some(attrs))
             .send()
             .await?;
         Ok(())
     }
 
-    pub async fn get_record(&self, id: &str) -> Result<Option<String>, aws_sdk_dynamodb::Error> {
+    /// The attributes of record `id`, without the `id` key attribute itself.
+    pub async fn get_item(&self, id: &str) -> Result<Option<HashMap<String, AttributeValue>>, aws_sdk_dynamodb::Error> {
         let resp = self.client.get_item()
             .table_name(&self.table_name)
             .key("id", AttributeValue::S(id.to_string()))
             .send()
             .await?;
-        
-        if let Some(item) = resp.item {
-            if let Some(data) = item.get("data") {
-                if let Ok(s) = data.as_s() {
-                    return Ok(Some(s.clone()));
+
+        Ok(resp.item.map(|mut item| {
+            item.remove("id");
+            item
+        }))
+    }
+
+    pub async fn save_record(&self, id: &str, data: &str) -> Result<(), aws_sdk_dynamodb::Error> {
+        self.save_item(id, HashMap::from([("data".to_string(), AttributeValue::S(data.to_string()))])).await
+    }
+
+    pub async fn get_record(&self, id: &str) -> Result<Option<String>, aws_sdk_dynamodb::Error> {
+        let item = self.get_item(id).await?;
+        Ok(item.and_then(|item| item.get("data").and_then(|data| data.as_s().ok()).cloned()))
+    }
+
+    fn processed_key(key: &str) -> String { format!("PROCESSED#{}", key) }
+
+    /// Records that `key` (e.g. an SQS message id) has been handled.
+    /// The marker carries an `expires_at` epoch so DynamoDB TTL removes it after `ttl`.
+    pub async fn mark_processed(&self, key: &str, ttl: Duration) -> Result<(), aws_sdk_dynamodb::Error> {
+        let expires_at = (SystemTime::now() + ttl).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.client.put_item()
+            .table_name(&self.table_name)
+            .item("id", AttributeValue::S(Self::processed_key(key)))
+            .item("expires_at", AttributeValue::N(expires_at.to_string()))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// True if `mark_processed` was called for `key` and the marker has not expired.
+    /// TTL deletion is lazy, so expiry is checked here as well.
+    pub async fn is_processed(&self, key: &str) -> Result<bool, aws_sdk_dynamodb::Error> {
+        let resp = self.client.get_item()
+            .table_name(&self.table_name)
+            .key("id", AttributeValue::S(Self::processed_key(key)))
+            .consistent_read(true)
+            .send()
+            .await?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Ok(resp.item
+            .and_then(|item| item.get("expires_at").and_then(|av| av.as_n().ok()).and_then(|n| n.parse::<u64>().ok()))
+            .is_some_and(|expires_at| expires_at > now))
+    }
+
+    /// Pages through every record with `Scan`, yielding `(id, data)` pairs.
+    /// With `id_prefix`, only records whose id begins with it are returned
+    /// (filtered server-side, so every page is still read).
+    pub fn scan_records(&self, id_prefix: Option<&str>) -> RecordStream {
+        let client = self.client.clone();
+        let table = self.table_name.clone();
+        let prefix = id_prefix.map(str::to_string);
+
+        let stream = async_stream::try_stream! {
+            let mut start_key = None;
+            loop {
+                let mut req = client.scan()
+                    .table_name(&table)
+                    .set_exclusive_start_key(start_key.take());
+                if let Some(prefix) = &prefix {
+                    req = req
+                        .filter_expression("begins_with(id, :prefix)")
+                        .expression_attribute_values(":prefix", AttributeValue::S(prefix.clone()));
+                }
+                let resp = req.send().await?;
+
+                for item in resp.items.unwrap_or_default() {
+                    let id = item.get("id").and_then(|av| av.as_s().ok());
+                    let data = item.get("data").and_then(|av| av.as_s().ok());
+                    if let (Some(id), Some(data)) = (id, data) {
+                        yield (id.clone(), data.clone());
+                    }
+                }
+
+                match resp.last_evaluated_key {
+                    Some(key) => start_key = Some(key),
+                    None => break,
                 }
             }
-        }
-        Ok(None)
+        };
+        Box::pin(stream)
     }
 }
@@ -1,10 +1,79 @@
 use aws_sdk_s3::Client;
 use aws_sdk_s3::primitives::ByteStream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// How an artifact id is turned into the checksum used for path fan-out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdScheme {
+    /// The id is already a hex-ish checksum and is used as-is.
+    #[default]
+    Raw,
+    /// The id is arbitrary (UUID, message id) and is MD5-hashed first.
+    Hash,
+}
+
+impl IdScheme {
+    fn checksum(&self, id: &str) -> String {
+        match self {
+            IdScheme::Raw => id.to_string(),
+            IdScheme::Hash => format!("{:x}", md5::compute(id)),
+        }
+    }
+}
+
+/// The graph-relevant fields of an artifact's `artifact.yaml`; any other fields are ignored.
+/// This is what `DependencyGraph::rebuild_from_storage` reconstructs the graph from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactMetadata {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<String>,
+    /// The compute node that produced the artifact; `None` for external inputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer: Option<ProducerMetadata>,
+}
+
+/// The producing compute node, as recorded on each of its output artifacts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProducerMetadata {
+    pub id: String,
+    pub node_type: String,
+    #[serde(default)]
+    pub inputs: Vec<String>,
+}
+
+impl ArtifactMetadata {
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+}
+
+/// A successful read of one of an artifact's objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessEvent {
+    pub id: String,
+    /// `artifact.yaml` or the content file's name.
+    pub file: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Receives `AccessEvent`s from an `ArtifactStorage` (e.g. to feed usage analysis).
+pub trait AccessLogger: Send + Sync {
+    fn log(&self, event: AccessEvent);
+}
 
 pub struct ArtifactStorage {
     client: Client,
     bucket: String,
     prefix: String,
+    id_scheme: IdScheme,
+    access_logger: Option<Arc<dyn AccessLogger>>,
 }
 
 impl ArtifactStorage {
@@ -13,14 +82,36 @@ impl ArtifactStorage {
             client,
             bucket,
             prefix: prefix.unwrap_or_else(|| "curio-data".to_string()),
+            id_scheme: IdScheme::Raw,
+            access_logger: None,
+        }
+    }
+
+    /// Sets how artifact ids are mapped to storage paths (default `IdScheme::Raw`).
+    pub fn with_id_scheme(mut self, id_scheme: IdScheme) -> Self {
+        self.id_scheme = id_scheme;
+        self
+    }
+
+    /// Reports every artifact read to `logger`.
+    pub fn with_access_logger(mut self, logger: Arc<dyn AccessLogger>) -> Self {
+        self.access_logger = Some(logger);
+        self
+    }
+
+    fn log_access(&self, id: &str, file: &str) {
+        if let Some(logger) = &self.access_logger {
+            logger.log(AccessEvent { id: id.to_string(), file: file.to_string(), at: chrono::Utc::now() });
         }
     }
 
     /// Helper to construct the hashed path for an artifact.
     /// Input: "1234567890ABCDEF"
     /// Output: "{prefix}/artifacts/1/2/3/4/5/6/1234567890ABCDEF/"
-    fn get_artifact_path(&self, checksum: &str) -> String {
-        if checksum.len() < 6 {
+    /// With `IdScheme::Hash` the id is hashed first and the hash is used in its place.
+    fn get_artifact_path(&self, id: &str) -> String {
+        let checksum = self.id_scheme.checksum(id);
+        if !self.has_own_directory(id) {
             // Fallback or error? For now simple fallback to root of artifacts
             return format!("{}/artifacts/{}", self.prefix, checksum);
         }
@@ -32,69 +123,213 @@ impl ArtifactStorage {
         )
     }
     
+    /// Whether the artifact gets its own directory. Checksums shorter than 6 characters
+    /// can't be sharded, so their keys are joined straight onto `artifacts/` and share a
+    /// prefix with any longer checksum that starts the same way (`abc`, `abcd`).
+    pub fn has_own_directory(&self, id: &str) -> bool {
+        self.id_scheme.checksum(id).len() >= 6
+    }
+
+    /// Whether `key` is an artifact's own `artifact.yaml`, directly under its
+    /// `get_artifact_path`, rather than a content file whose name merely ends the same
+    /// way (`report_artifact.yaml`).
+    fn is_metadata_key(&self, key: &str) -> bool {
+        let Some(rest) = key.strip_prefix(&format!("{}/artifacts/", self.prefix)) else {
+            return false;
+        };
+        // Checksums too short to shard are joined straight onto artifacts/, with no slash
+        if let Some(checksum) = rest.strip_suffix("artifact.yaml").filter(|c| !c.contains('/')) {
+            return checksum.len() < 6;
+        }
+        let Some(dir) = rest.strip_suffix("/artifact.yaml") else {
+            return false;
+        };
+        // `c0/c1/c2/c3/c4/c5/checksum`, where c0..c5 are the checksum's first characters
+        let mut shards = String::new();
+        let mut chars = dir.chars();
+        for _ in 0..6 {
+            match (chars.next(), chars.next()) {
+                (Some(c), Some('/')) => shards.push(c),
+                _ => return false,
+            }
+        }
+        chars.as_str().starts_with(&shards)
+    }
+
+    /// Key of a compute node type written before versioning; read when no `latest` exists.
     fn get_type_path(&self, type_name: &str) -> String {
         format!("{}/compute_node_types/{}.yaml", self.prefix, type_name)
     }
 
-    /// Stores a compute node type definition (YAML).
-    pub async fn store_compute_node_type(&self, type_name: &str, content: &str) -> Result<(), aws_sdk_s3::Error> {
-        let key = self.get_type_path(type_name);
-        
+    fn get_type_version_path(&self, type_name: &str, version: u32) -> String {
+        format!("{}/compute_node_types/{}/v{}.yaml", self.prefix, type_name, version)
+    }
+
+    /// Holds the newest version number of a type, as plain text.
+    fn get_type_latest_path(&self, type_name: &str) -> String {
+        format!("{}/compute_node_types/{}/latest", self.prefix, type_name)
+    }
+
+    /// Reads an object, returning `None` if the key doesn't exist.
+    async fn get_object_if_exists(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let resp = match self.client.get_object().bucket(&self.bucket).key(key).send().await {
+            Ok(resp) => resp,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let data = resp.body.collect().await?;
+        Ok(Some(data.into_bytes().to_vec()))
+    }
+
+    async fn latest_type_version(&self, type_name: &str) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+        match self.get_object_if_exists(&self.get_type_latest_path(type_name)).await? {
+            Some(data) => Ok(Some(String::from_utf8(data)?.trim().parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a compute node type definition (YAML) as a new version and points `latest` at it.
+    /// Versions start at 1 and are never overwritten. If `content` is identical to the latest
+    /// version, nothing is written. Returns the version that now holds `content`.
+    ///
+    /// `latest` is written after the version, so it can lag behind (a concurrent writer, or
+    /// a failed second put). Versions that already exist past it are skipped, or reused if
+    /// they hold `content`, and `latest` is brought up to date.
+    pub async fn store_compute_node_type(&self, type_name: &str, content: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        let latest = self.latest_type_version(type_name).await?;
+        if let Some(version) = latest {
+            if self.get_compute_node_type(type_name, Some(version)).await? == content {
+                return Ok(version);
+            }
+        }
+
+        let mut version = latest.map_or(1, |v| v + 1);
+        loop {
+            // if-none-match keeps a concurrent writer from replacing a version we didn't read.
+            let result = self.client.put_object()
+                .bucket(&self.bucket)
+                .key(self.get_type_version_path(type_name, version))
+                .if_none_match("*")
+                .body(ByteStream::from(content.as_bytes().to_vec()))
+                .send()
+                .await;
+            match result {
+                Ok(_) => break,
+                Err(e) if e.raw_response().is_some_and(|r| r.status().as_u16() == 412) => {
+                    if self.get_compute_node_type(type_name, Some(version)).await? == content {
+                        break;
+                    }
+                    version += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
         self.client.put_object()
             .bucket(&self.bucket)
-            .key(key)
-            .body(ByteStream::from(content.as_bytes().to_vec()))
+            .key(self.get_type_latest_path(type_name))
+            .body(ByteStream::from(version.to_string().into_bytes()))
             .send()
             .await?;
-        
-        Ok(())
+
+        Ok(version)
     }
 
-    /// Retrieves a compute node type definition.
-    pub async fn get_compute_node_type(&self, type_name: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let key = self.get_type_path(type_name);
-        let resp = self.client.get_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
-            .await?;
-        
-        let data = resp.body.collect().await?;
-        Ok(String::from_utf8(data.into_bytes().to_vec())?)
+    /// Retrieves a compute node type definition: the given `version`, or the latest one.
+    /// Types stored before versioning are still found when no version is requested.
+    pub async fn get_compute_node_type(&self, type_name: &str, version: Option<u32>) -> Result<String, Box<dyn std::error::Error>> {
+        let key = match version {
+            Some(version) => self.get_type_version_path(type_name, version),
+            None => match self.latest_type_version(type_name).await? {
+                Some(latest) => self.get_type_version_path(type_name, latest),
+                None => self.get_type_path(type_name),
+            },
+        };
+        let data = self.get_object_if_exists(&key).await?
+            .ok_or_else(|| format!("Compute node type '{}' not found at {}", type_name, key))?;
+        Ok(String::from_utf8(data)?)
     }
 
     /// Saves an artifact's metadata and content files.
     /// `files` is a list of (filename, content_bytes).
+    /// Content files are uploaded first and `artifact.yaml` last, so its presence marks a
+    /// complete artifact. If any upload fails, the objects this call created are deleted
+    /// before the error is returned, so the call can simply be retried. Objects that already
+    /// existed (when re-saving an artifact) are never deleted, so a failed re-save can't
+    /// destroy the committed version, though files it overwrote keep their new content.
     pub async fn save_artifact(&self, id: &str, metadata_yaml: &str, files: Vec<(String, Vec<u8>)>) -> Result<(), aws_sdk_s3::Error> {
         let base_path = self.get_artifact_path(id);
-        
-        // 1. Save artifact.yaml
         let meta_key = format!("{}artifact.yaml", base_path);
-        self.client.put_object()
-            .bucket(&self.bucket)
-            .key(meta_key)
-            .body(ByteStream::from(metadata_yaml.as_bytes().to_vec()))
-            .send()
-            .await?;
-        
-        // 2. Save content files
+        let mut existing = HashSet::new();
+        for key in files.iter().map(|(name, _)| format!("{}{}", base_path, name)).chain([meta_key.clone()]) {
+            if self.exists(&key).await? {
+                existing.insert(key);
+            }
+        }
+        let mut created = Vec::with_capacity(files.len() + 1);
+
+        // 1. Save content files
         for (name, data) in files {
             let file_key = format!("{}{}", base_path, name);
-            self.client.put_object()
-                .bucket(&self.bucket)
-                .key(file_key)
-                .body(ByteStream::from(data))
-                .send()
-                .await?;
+            let result = self.put(&file_key, data).await;
+            // A failed put may still have landed (e.g. a timeout after upload)
+            if !existing.contains(&file_key) {
+                created.push(file_key);
+            }
+            if let Err(e) = result {
+                self.delete_quietly(&created).await;
+                return Err(e);
+            }
         }
 
+        // 2. Save artifact.yaml (the commit marker)
+        if let Err(e) = self.put(&meta_key, metadata_yaml.as_bytes().to_vec()).await {
+            if !existing.contains(&meta_key) {
+                created.push(meta_key);
+            }
+            self.delete_quietly(&created).await;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, aws_sdk_s3::Error> {
+        match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), aws_sdk_s3::Error> {
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(data))
+            .send()
+            .await?;
         Ok(())
     }
 
+    /// Best-effort rollback of a partial `save_artifact`; failures are only logged.
+    async fn delete_quietly(&self, keys: &[String]) {
+        for key in keys {
+            if let Err(e) = self.client.delete_object().bucket(&self.bucket).key(key).send().await {
+                tracing::warn!("Failed to roll back partial upload s3://{}/{}: {}", self.bucket, key, e);
+            }
+        }
+    }
+
     /// Retrieves artifact.yaml metadata.
     pub async fn get_artifact_metadata(&self, id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let data = self.get_artifact_file(id, "artifact.yaml").await?;
+        Ok(String::from_utf8(data)?)
+    }
+
+    /// Retrieves one of an artifact's files by name.
+    pub async fn get_artifact_file(&self, id: &str, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         let base_path = self.get_artifact_path(id);
-        let key = format!("{}artifact.yaml", base_path);
+        let key = format!("{}{}", base_path, name);
         
         let resp = self.client.get_object()
             .bucket(&self.bucket)
@@ -103,6 +338,89 @@ impl ArtifactStorage {
             .await?;
             
         let data = resp.body.collect().await?;
-        Ok(String::from_utf8(data.into_bytes().to_vec())?)
+        self.log_access(id, name);
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// Retrieves bytes `start..end` (end exclusive) of one of an artifact's files with a
+    /// ranged GET, e.g. for a preview of a large file. A window running past the end of the
+    /// file is clamped to it; one starting at or after the end yields no bytes.
+    pub async fn get_artifact_file_range(&self, id: &str, name: &str, start: u64, end: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let key = format!("{}{}", self.get_artifact_path(id), name);
+
+        // HTTP ranges are inclusive; S3 itself clamps the end to the object size.
+        let resp = match self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .range(format!("bytes={}-{}", start, end - 1))
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) if e.raw_response().is_some_and(|r| r.status().as_u16() == 416) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let data = resp.body.collect().await?;
+        self.log_access(id, name);
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// Lists the raw artifact.yaml of every artifact under the prefix.
+    pub async fn list_artifact_metadata(&self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.list_artifact_metadata_with_times().await?.into_iter().map(|(yaml, _)| yaml).collect())
+    }
+
+    /// Like `list_artifact_metadata`, with each artifact.yaml's last-modified time.
+    pub async fn list_artifact_metadata_with_times(&self) -> Result<Vec<(String, chrono::DateTime<chrono::Utc>)>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut pages = self.client.list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(format!("{}/artifacts/", self.prefix))
+            .into_paginator()
+            .send();
+
+        let mut metadata = Vec::new();
+        while let Some(page) = pages.next().await {
+            for object in page?.contents() {
+                let Some(key) = object.key().filter(|k| self.is_metadata_key(k)) else { continue };
+                let modified = object.last_modified()
+                    .and_then(|t| chrono::DateTime::from_timestamp(t.secs(), t.subsec_nanos()))
+                    .unwrap_or_default();
+                let resp = self.client.get_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .send()
+                    .await?;
+                let data = resp.body.collect().await?;
+                metadata.push((String::from_utf8(data.into_bytes().to_vec())?, modified));
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Deletes every object stored under an artifact's path, returning how many were removed.
+    /// Artifacts without `has_own_directory` are left alone (returning 0), since listing
+    /// their path would also match other artifacts' objects.
+    pub async fn delete_artifact(&self, id: &str) -> Result<usize, aws_sdk_s3::Error> {
+        if !self.has_own_directory(id) {
+            return Ok(0);
+        }
+        let mut pages = self.client.list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(self.get_artifact_path(id))
+            .into_paginator()
+            .send();
+
+        let mut deleted = 0;
+        while let Some(page) = pages.next().await {
+            for key in page?.contents().iter().filter_map(|object| object.key()) {
+                self.client.delete_object().bucket(&self.bucket).key(key).send().await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
     }
 }
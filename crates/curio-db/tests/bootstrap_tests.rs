@@ -0,0 +1,97 @@
+use curio_db::bootstrap::create_graph_table;
+use curio_db::dependency_graph::DependencyGraph;
+use std::env;
+use futures::StreamExt;
+
+#[tokio::test]
+async fn test_create_graph_table() {
+    let table_name = "test-bootstrap-graph";
+    let region_provider = aws_config::meta::region::RegionProviderChain::default_provider().or_else(aws_sdk_dynamodb::config::Region::new("us-east-1"));
+    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).region(region_provider).load().await;
+
+    let client = if let Ok(endpoint) = env::var("DYNAMODB_ENDPOINT") {
+        let conf = aws_sdk_dynamodb::config::Builder::from(&shared_config)
+            .endpoint_url(endpoint)
+            .build();
+        aws_sdk_dynamodb::Client::from_conf(conf)
+    } else {
+        panic!("DYNAMODB_ENDPOINT must be set for bootstrap tests");
+    };
+
+    create_graph_table(&client, table_name).await.expect("bootstrap failed");
+    // Re-running against an existing table is a no-op
+    create_graph_table(&client, table_name).await.expect("second bootstrap failed");
+
+    let desc = client.describe_table().table_name(table_name).send().await.unwrap();
+    let table = desc.table.expect("table missing");
+    assert_eq!(table.table_status, Some(aws_sdk_dynamodb::types::TableStatus::Active));
+
+    // Table is usable by the graph, including the gsi1 dirty index
+    let graph = DependencyGraph::new(client, table_name.to_string());
+    graph.register_artifact("bootstrap_in".to_string(), true, None).await.unwrap();
+    graph.create_compute_node("bootstrap_node".to_string(), vec!["bootstrap_in".to_string()], "Compile".to_string()).await.unwrap();
+
+    let mut dirty = graph.get_dirty_compute_nodes();
+    let mut found = false;
+    while let Some(res) = dirty.next().await {
+        if res.unwrap() == "bootstrap_node" {
+            found = true;
+        }
+    }
+    assert!(found, "Node should be queryable through gsi1");
+}
+
+#[tokio::test]
+async fn test_create_graph_table_adds_type_index() {
+    use aws_sdk_dynamodb::types::{AttributeDefinition, AttributeValue, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection, ProjectionType, ScalarAttributeType};
+
+    let table_name = format!("test-bootstrap-legacy-{}", uuid::Uuid::new_v4());
+    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).load().await;
+    let endpoint = env::var("DYNAMODB_ENDPOINT").expect("DYNAMODB_ENDPOINT must be set for bootstrap tests");
+    let client = aws_sdk_dynamodb::Client::from_conf(aws_sdk_dynamodb::config::Builder::from(&shared_config).endpoint_url(endpoint).build());
+
+    // A table from before gsi2, holding a node written without its keys
+    let attribute = |name: &str| AttributeDefinition::builder().attribute_name(name).attribute_type(ScalarAttributeType::S).build().unwrap();
+    let key = |name: &str, key_type| KeySchemaElement::builder().attribute_name(name).key_type(key_type).build().unwrap();
+    client.create_table()
+        .table_name(&table_name)
+        .attribute_definitions(attribute("pk"))
+        .attribute_definitions(attribute("sk"))
+        .attribute_definitions(attribute("gsi1pk"))
+        .attribute_definitions(attribute("gsi1sk"))
+        .key_schema(key("pk", KeyType::Hash))
+        .key_schema(key("sk", KeyType::Range))
+        .global_secondary_indexes(GlobalSecondaryIndex::builder()
+            .index_name("gsi1")
+            .key_schema(key("gsi1pk", KeyType::Hash))
+            .key_schema(key("gsi1sk", KeyType::Range))
+            .projection(Projection::builder().projection_type(ProjectionType::All).build())
+            .build()
+            .unwrap())
+        .billing_mode(BillingMode::PayPerRequest)
+        .send()
+        .await
+        .unwrap();
+    client.put_item()
+        .table_name(&table_name)
+        .item("pk", AttributeValue::S("COMPUTE#legacy_node".to_string()))
+        .item("sk", AttributeValue::S("META".to_string()))
+        .item("type", AttributeValue::S("Compile".to_string()))
+        .item("inputs", AttributeValue::L(vec![]))
+        .send()
+        .await
+        .unwrap();
+
+    create_graph_table(&client, &table_name).await.expect("migration failed");
+    let desc = client.describe_table().table_name(&table_name).send().await.unwrap();
+    let indexes: Vec<_> = desc.table.unwrap().global_secondary_indexes().iter().filter_map(|gsi| gsi.index_name().map(str::to_string)).collect();
+    assert!(indexes.contains(&"gsi2".to_string()), "gsi2 missing: {:?}", indexes);
+
+    let graph = DependencyGraph::new(client.clone(), table_name.clone());
+    let nodes: Vec<String> = graph.get_compute_nodes_by_type("Compile".to_string()).map(|r| r.unwrap()).collect().await;
+    assert_eq!(nodes, vec!["legacy_node".to_string()]);
+
+    // Once migrated, a re-run has nothing left to do
+    create_graph_table(&client, &table_name).await.expect("second bootstrap failed");
+    assert_eq!(graph.backfill_type_index().await.unwrap(), 0);
+}
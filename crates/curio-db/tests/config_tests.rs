@@ -51,3 +51,79 @@ workflows:
         panic!("Expected Artifact input");
     }
 }
+
+#[tokio::test]
+async fn test_config_watcher_reload() {
+    use curio_db::config::{ConfigSource, ConfigWatcher};
+    let config = |artifact_type: &str| format!("artifacts:\n  - type: \"{}\"\n    match: \"docs/.*\"\nworkflows: []\n", artifact_type);
+
+    let path = std::env::temp_dir().join(format!("curio_watch_{}.yaml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, config("document")).unwrap();
+    let watcher = ConfigWatcher::new(ConfigSource::File(path.clone())).await.unwrap();
+    let original = watcher.current();
+    assert!(!watcher.reload().await.unwrap(), "unchanged content should not reload");
+
+    std::fs::write(&path, config("report")).unwrap();
+    assert!(watcher.reload().await.unwrap());
+    assert_eq!(watcher.current().match_artifact("docs/a").unwrap().type_name, "report");
+    // Snapshots taken before the swap are unaffected
+    assert_eq!(original.match_artifact("docs/a").unwrap().type_name, "document");
+
+    // Neither bad YAML nor a bad regex replaces the working config
+    for bad in ["artifacts: [", "artifacts:\n  - type: \"x\"\n    match: \"(\"\nworkflows: []\n"] {
+        std::fs::write(&path, bad).unwrap();
+        assert!(watcher.reload().await.is_err());
+        assert_eq!(watcher.current().match_artifact("docs/a").unwrap().type_name, "report");
+    }
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_workflow_steps() {
+    use curio_db::config::StepInput;
+    let config = |second_step_source: &str| format!(r#"
+artifacts:
+  - type: "document"
+    match: "docs/(?P<doc_id>[^/]+)\\.json"
+workflows:
+  - trigger: "document"
+    compute_node:
+      type: "summarize"
+      id: "summary-{{doc_id}}"
+      inputs:
+        - source: "self"
+      steps:
+        - name: "extract"
+          primitive: "JsonSelect"
+          inputs:
+            json: {{ source: "self" }}
+            query: {{ source: "literal", value: "{{title: title, body: body}}" }}
+        - name: "render"
+          primitive: "TemplateRender"
+          inputs:
+            template: {{ source: "literal", value: "{{{{ title }}}}: {{{{ body }}}}" }}
+            context: {{ source: "step", step: "{}", output: "result" }}
+"#, second_step_source);
+
+    let parsed = CurioConfig::from_yaml(&config("extract")).expect("Failed to parse YAML");
+    let node = &parsed.get_workflows_for_type("document")[0].compute_node;
+    assert_eq!(node.steps.len(), 2);
+    assert_eq!(node.step("extract").unwrap().inputs["json"], StepInput::SelfArtifact);
+    assert_eq!(node.step("extract").unwrap().inputs["query"], StepInput::Literal { value: "{title: title, body: body}".to_string() });
+
+    let render = node.step("render").unwrap();
+    assert_eq!(render.primitive, "TemplateRender");
+    assert_eq!(render.inputs["context"], StepInput::Step { step: "extract".to_string(), output: "result".to_string() });
+    assert_eq!(render.dependencies(), vec!["extract"]);
+    assert!(node.step(render.dependencies()[0]).is_some());
+
+    // References must resolve to an earlier step
+    let err = CurioConfig::from_yaml(&config("missing")).unwrap_err();
+    assert!(err.to_string().contains("unknown step 'missing'"), "unexpected error: {}", err);
+    let err = CurioConfig::from_yaml(&config("render")).unwrap_err();
+    assert!(err.to_string().contains("a later step 'render'"), "unexpected error: {}", err);
+
+    // Workflows without steps are unchanged
+    assert!(CurioConfig::from_yaml("artifacts: []\nworkflows:\n  - trigger: t\n    compute_node: {type: x, id: y, inputs: []}\n").unwrap().workflows[0].compute_node.steps.is_empty());
+}
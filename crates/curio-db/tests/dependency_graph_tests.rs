@@ -1,10 +1,24 @@
-use curio_db::dependency_graph::DependencyGraph;
+use curio_db::dependency_graph::{DependencyGraph, GraphDescription, GraphDiff, GraphSnapshot, GraphStats, NodeNotFound, Page, RebuildSummary, ReconcileReport, UnknownInputs};
 use std::env;
 use aws_sdk_dynamodb::types::{AttributeDefinition, KeySchemaElement, KeyType, ScalarAttributeType, BillingMode, GlobalSecondaryIndex, Projection, ProjectionType, ProvisionedThroughput};
 use futures::StreamExt;
+use aws_sdk_dynamodb::config::{interceptors::{BeforeDeserializationInterceptorContextMut, BeforeTransmitInterceptorContextRef}, ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_dynamodb::error::BoxError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 async fn get_client() -> DependencyGraph {
-    let table_name = "test-dependency-graph";
+    get_client_for_table("test-dependency-graph").await
+}
+
+async fn get_client_for_table(table_name: &str) -> DependencyGraph {
+    let client = dynamo_client().await;
+    create_table(&client, table_name).await;
+    DependencyGraph::new(client, table_name.to_string())
+}
+
+/// Raw DynamoDB client, for tests that need to corrupt the graph behind its back.
+async fn dynamo_client() -> aws_sdk_dynamodb::Client {
     let region_provider = aws_config::meta::region::RegionProviderChain::default_provider().or_else(aws_sdk_dynamodb::config::Region::new("us-east-1"));
     let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).region(region_provider).load().await;
     
@@ -16,6 +30,23 @@ async fn get_client() -> DependencyGraph {
     } else {
         panic!("DYNAMODB_ENDPOINT must be set for build graph tests");
     };
+    client
+}
+
+/// Artifact storage on the local S3 endpoint, under a fresh `{prefix}-{uuid}` prefix.
+async fn test_storage(prefix: &str) -> curio_db::storage::ArtifactStorage {
+    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).test_credentials().load().await;
+    let s3_config = aws_sdk_s3::config::Builder::from(&shared_config)
+        .region(aws_sdk_s3::config::Region::new("us-east-1"))
+        .endpoint_url("http://localhost:5000")
+        .force_path_style(true)
+        .build();
+    let s3 = aws_sdk_s3::Client::from_conf(s3_config);
+    let _ = s3.create_bucket().bucket("test-bucket").send().await;
+    curio_db::storage::ArtifactStorage::new(s3, "test-bucket".to_string(), Some(format!("{}-{}", prefix, uuid::Uuid::new_v4())))
+}
+
+async fn create_table(client: &aws_sdk_dynamodb::Client, table_name: &str) {
 
     // Create Table with Schema if it doesn't exist
     let _ = client.create_table()
@@ -24,6 +55,8 @@ async fn get_client() -> DependencyGraph {
         .attribute_definitions(AttributeDefinition::builder().attribute_name("sk").attribute_type(ScalarAttributeType::S).build().unwrap())
         .attribute_definitions(AttributeDefinition::builder().attribute_name("gsi1pk").attribute_type(ScalarAttributeType::S).build().unwrap())
         .attribute_definitions(AttributeDefinition::builder().attribute_name("gsi1sk").attribute_type(ScalarAttributeType::S).build().unwrap())
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("gsi2pk").attribute_type(ScalarAttributeType::S).build().unwrap())
+        .attribute_definitions(AttributeDefinition::builder().attribute_name("gsi2sk").attribute_type(ScalarAttributeType::S).build().unwrap())
         .key_schema(KeySchemaElement::builder().attribute_name("pk").key_type(KeyType::Hash).build().unwrap())
         .key_schema(KeySchemaElement::builder().attribute_name("sk").key_type(KeyType::Range).build().unwrap())
         // GSI1
@@ -35,14 +68,21 @@ async fn get_client() -> DependencyGraph {
             .provisioned_throughput(ProvisionedThroughput::builder().read_capacity_units(5).write_capacity_units(5).build().unwrap())
             .build().unwrap()
         )
+        // GSI2
+        .global_secondary_indexes(GlobalSecondaryIndex::builder()
+            .index_name("gsi2")
+            .key_schema(KeySchemaElement::builder().attribute_name("gsi2pk").key_type(KeyType::Hash).build().unwrap())
+            .key_schema(KeySchemaElement::builder().attribute_name("gsi2sk").key_type(KeyType::Range).build().unwrap())
+            .projection(Projection::builder().projection_type(ProjectionType::KeysOnly).build())
+            .provisioned_throughput(ProvisionedThroughput::builder().read_capacity_units(5).write_capacity_units(5).build().unwrap())
+            .build().unwrap()
+        )
         .billing_mode(BillingMode::PayPerRequest)
         .send()
         .await;
 
     // Small delay to ensure table is ready (DynamoDB Local is fast but technically async)
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-    DependencyGraph::new(client, table_name.to_string())
 }
 
 #[tokio::test]
@@ -51,7 +91,7 @@ async fn test_case_1_new_flow() {
     let artifact_id = "checksum_A".to_string();
     let compute_id = "compute_1_new_flow".to_string(); // Unique ID for this test run
 
-    client.register_artifact(artifact_id.clone(), true).await.unwrap();
+    client.register_artifact(artifact_id.clone(), true, None).await.unwrap();
     client.create_compute_node(compute_id.clone(), vec![artifact_id], "Compile".to_string()).await.unwrap();
 
     // Verify Dirty
@@ -72,7 +112,7 @@ async fn test_case_2_update_flow_queries() {
     let artifact_id_a = "checksum_A_update".to_string();
     let compute_id_1 = "compute_1_update".to_string();
 
-    client.register_artifact(artifact_id_a.clone(), true).await.unwrap();
+    client.register_artifact(artifact_id_a.clone(), true, None).await.unwrap();
     client.create_compute_node(compute_id_1.clone(), vec![artifact_id_a.clone()], "Compile".to_string()).await.unwrap();
 
     // 1. Get downstream of A -> should be compute_1
@@ -81,7 +121,7 @@ async fn test_case_2_update_flow_queries() {
     assert_eq!(first, compute_id_1);
 
     // 2. Get details
-    let (node_type, inputs) = client.get_compute_node_details(compute_id_1.clone()).await.unwrap();
+    let (node_type, inputs, _) = client.get_compute_node_details(compute_id_1.clone()).await.unwrap();
     assert_eq!(node_type, "Compile");
     assert_eq!(inputs.len(), 1);
     assert_eq!(inputs[0], "checksum_A_update");
@@ -106,9 +146,9 @@ async fn test_gc_flow() {
     let output_1 = "out_1".to_string();
     
     // 1. Setup A -> C1 -> B
-    client.register_artifact(external_input.clone(), true).await.unwrap();
+    client.register_artifact(external_input.clone(), true, None).await.unwrap();
     // Register output too (internal)
-    client.register_artifact(output_1.clone(), false).await.unwrap(); 
+    client.register_artifact(output_1.clone(), false, None).await.unwrap(); 
     
     client.create_compute_node(compute_1.clone(), vec![external_input.clone()], "Compile".to_string()).await.unwrap();
     client.set_compute_node_outputs(compute_1.clone(), vec![output_1.clone()]).await.unwrap();
@@ -116,11 +156,11 @@ async fn test_gc_flow() {
     // 2. Replace C1 with C2 (which produces B2, making B orphaned)
     let compute_2 = "comp_2".to_string();
     let output_2 = "out_2".to_string();
-    client.register_artifact(output_2.clone(), false).await.unwrap();
+    client.register_artifact(output_2.clone(), false, None).await.unwrap();
     
     // Simulate replacement:
     // Remove C1
-    client.remove_compute_node(compute_1.clone()).await.unwrap();
+    client.remove_compute_node(compute_1.clone(), true).await.unwrap();
     // Create C2
     client.create_compute_node(compute_2.clone(), vec![external_input.clone()], "Compile".to_string()).await.unwrap();
     client.set_compute_node_outputs(compute_2.clone(), vec![output_2.clone()]).await.unwrap();
@@ -136,3 +176,900 @@ async fn test_gc_flow() {
     // 5. Verify out_1 is gone (optional, but good)
     // could verify register_artifact fails or something, but deletion is key.
 }
+
+#[tokio::test]
+async fn test_cleanup_orphans_prunes_dangling_edges() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    let table = format!("test-graph-dangling-{}", uuid::Uuid::new_v4());
+    let client = get_client_for_table(&table).await;
+    let artifact = "dangling_in".to_string();
+    let live_artifact = "dangling_live_in".to_string();
+
+    client.register_artifact(artifact.clone(), false, None).await.unwrap();
+    client.register_artifact(live_artifact.clone(), false, None).await.unwrap();
+    client.create_compute_node("dangling_gone".to_string(), vec![artifact.clone()], "Compile".to_string()).await.unwrap();
+    client.create_compute_node("dangling_live".to_string(), vec![live_artifact.clone()], "Compile".to_string()).await.unwrap();
+
+    // Delete the node item directly, leaving its consumer edge behind
+    dynamo_client().await.delete_item()
+        .table_name(&table)
+        .key("pk", AttributeValue::S("COMPUTE#dangling_gone".to_string()))
+        .key("sk", AttributeValue::S("META".to_string()))
+        .send()
+        .await
+        .unwrap();
+    let consumers: Vec<_> = client.get_downstream_compute_nodes(artifact.clone()).collect().await;
+    assert_eq!(consumers.len(), 1, "stale edge should still be visible before cleanup");
+
+    client.mark_artifact_orphaned(artifact.clone()).await.unwrap();
+    client.mark_artifact_orphaned(live_artifact.clone()).await.unwrap();
+    assert_eq!(client.cleanup_orphans().await.unwrap(), 1, "only the artifact with a stale edge should be collected");
+
+    let item = dynamo_client().await.get_item()
+        .table_name(&table)
+        .key("pk", AttributeValue::S(format!("ARTIFACT#{}", artifact)))
+        .key("sk", AttributeValue::S("META".to_string()))
+        .send()
+        .await
+        .unwrap();
+    assert!(item.item.is_none(), "orphan should be deleted");
+    assert!(client.get_downstream_compute_nodes(artifact).collect::<Vec<_>>().await.is_empty());
+    // An edge to a node that still exists keeps the artifact alive
+    let consumers: Vec<_> = client.get_downstream_compute_nodes(live_artifact).collect().await;
+    assert_eq!(consumers.len(), 1);
+}
+
+#[tokio::test]
+async fn test_touch_artifact() {
+    let client = get_client_for_table(&format!("test-graph-touch-{}", uuid::Uuid::new_v4())).await;
+    let input = "touch_in".to_string();
+    client.register_artifact(input.clone(), true, Some("gen-1".to_string())).await.unwrap();
+    client.register_artifact("touch_out".to_string(), false, None).await.unwrap();
+    client.create_compute_node("touch_node".to_string(), vec![input.clone()], "Compile".to_string()).await.unwrap();
+    client.create_compute_node("touch_gone".to_string(), vec![input.clone()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("touch_node".to_string(), vec!["touch_out".to_string()]).await.unwrap();
+    client.remove_compute_node("touch_gone".to_string(), false).await.unwrap();
+    assert_eq!(client.stats().await.unwrap().dirty, 0);
+
+    // Only the live consumer is re-dirtied; the tombstoned one stays deleted
+    assert_eq!(client.touch_artifact(input.clone()).await.unwrap(), 1);
+    let dirty: Vec<_> = client.get_dirty_compute_nodes().collect::<Vec<_>>().await.into_iter().map(Result::unwrap).collect();
+    assert_eq!(dirty, vec!["touch_node".to_string()]);
+
+    // The artifact itself is unchanged, and re-running the node cleans it again
+    assert_eq!(client.get_artifact_generation(input.clone()).await.unwrap(), Some("gen-1".to_string()));
+    client.set_compute_node_outputs("touch_node".to_string(), vec!["touch_out".to_string()]).await.unwrap();
+    assert_eq!(client.stats().await.unwrap().dirty, 0);
+}
+
+#[tokio::test]
+async fn test_register_and_create() {
+    let table = format!("test-graph-register-create-{}", uuid::Uuid::new_v4());
+    let raw = dynamo_client().await;
+    create_table(&raw, &table).await;
+    let client = DependencyGraph::new(raw.clone(), table.clone());
+    let exists = |pk: String| {
+        let (raw, table) = (raw.clone(), table.clone());
+        async move {
+            raw.get_item().table_name(table)
+                .key("pk", aws_sdk_dynamodb::types::AttributeValue::S(pk))
+                .key("sk", aws_sdk_dynamodb::types::AttributeValue::S("META".to_string()))
+                .send().await.unwrap().item.is_some()
+        }
+    };
+
+    client.register_artifact("rc_lib".to_string(), true, Some("gen-1".to_string())).await.unwrap();
+    client.register_and_create(vec![("rc_src".to_string(), true)], "rc_node".to_string(), vec!["rc_src".to_string(), "rc_lib".to_string()], "Compile".to_string()).await.unwrap();
+    assert!(exists("ARTIFACT#rc_src".to_string()).await);
+    let (node_type, inputs, generations) = client.get_compute_node_details("rc_node".to_string()).await.unwrap();
+    assert_eq!((node_type.as_str(), inputs.len()), ("Compile", 2));
+    assert_eq!(generations.get("rc_lib").map(String::as_str), Some("gen-1"));
+    let consumers: Vec<_> = client.get_downstream_compute_nodes("rc_src".to_string()).collect().await;
+    assert_eq!(consumers.len(), 1);
+
+    // An unregistered input cancels the whole transaction: no node, no new artifacts
+    let err = client.register_and_create(
+        vec![("rc_new".to_string(), true)],
+        "rc_failed".to_string(),
+        vec!["rc_new".to_string(), "rc_missing".to_string()],
+        "Compile".to_string(),
+    ).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownInputs>(), Some(&UnknownInputs(vec!["rc_missing".to_string()])));
+    assert!(!exists("COMPUTE#rc_failed".to_string()).await);
+    assert!(!exists("ARTIFACT#rc_new".to_string()).await);
+    let consumers: Vec<_> = client.get_downstream_compute_nodes("rc_new".to_string()).collect().await;
+    assert!(consumers.is_empty());
+
+    // Repeated artifacts and inputs are written once; the last entry for an artifact wins
+    client.register_and_create(
+        vec![("rc_dup".to_string(), false), ("rc_dup".to_string(), true)],
+        "rc_dup_node".to_string(),
+        vec!["rc_dup".to_string(), "rc_lib".to_string(), "rc_dup".to_string()],
+        "Compile".to_string(),
+    ).await.unwrap();
+    let dup = raw.get_item().table_name(&table)
+        .key("pk", aws_sdk_dynamodb::types::AttributeValue::S("ARTIFACT#rc_dup".to_string()))
+        .key("sk", aws_sdk_dynamodb::types::AttributeValue::S("META".to_string()))
+        .send().await.unwrap().item.unwrap();
+    assert_eq!(dup["is_external"].as_bool(), Ok(&true));
+    let consumers: Vec<_> = client.get_downstream_compute_nodes("rc_dup".to_string()).collect().await;
+    assert_eq!(consumers.len(), 1);
+}
+
+/// Counts the GetItem calls made through a client.
+#[derive(Debug, Default)]
+struct GetItemCounter(Arc<AtomicUsize>);
+
+impl Intercept for GetItemCounter {
+    fn name(&self) -> &'static str {
+        "GetItemCounter"
+    }
+
+    fn read_before_transmit(&self, context: &BeforeTransmitInterceptorContextRef<'_>, _: &RuntimeComponents, _: &mut ConfigBag) -> Result<(), BoxError> {
+        if context.request().headers().get("x-amz-target").is_some_and(|target| target.ends_with(".GetItem")) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_details_cache() {
+    let table = format!("test-graph-details-cache-{}", uuid::Uuid::new_v4());
+    let raw = dynamo_client().await;
+    create_table(&raw, &table).await;
+    let get_items = Arc::new(AtomicUsize::new(0));
+    let counted = aws_sdk_dynamodb::Client::from_conf(raw.config().to_builder().interceptor(GetItemCounter(get_items.clone())).build());
+    let client = DependencyGraph::new(counted, table).with_details_cache(16);
+
+    client.create_compute_node("cached_node".to_string(), vec!["cached_in".to_string()], "Compile".to_string()).await.unwrap();
+    get_items.store(0, Ordering::SeqCst);
+    let first = client.get_compute_node_details("cached_node".to_string()).await.unwrap();
+    let second = client.get_compute_node_details("cached_node".to_string()).await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(get_items.load(Ordering::SeqCst), 1);
+
+    // Re-creating the node through the graph drops the cached entry
+    client.create_compute_node("cached_node".to_string(), vec!["cached_in".to_string(), "cached_in_2".to_string()], "Link".to_string()).await.unwrap();
+    get_items.store(0, Ordering::SeqCst);
+    let (node_type, inputs, _) = client.get_compute_node_details("cached_node".to_string()).await.unwrap();
+    assert_eq!(node_type, "Link");
+    assert_eq!(inputs.len(), 2);
+    assert_eq!(get_items.load(Ordering::SeqCst), 1);
+
+    // Missing nodes aren't cached
+    client.get_compute_node_details("cached_missing".to_string()).await.unwrap();
+    client.get_compute_node_details("cached_missing".to_string()).await.unwrap();
+    assert_eq!(get_items.load(Ordering::SeqCst), 3);
+
+    // Purging a cached tombstone drops its entry
+    client.remove_compute_node("cached_node".to_string(), false).await.unwrap();
+    let (node_type, _, _) = client.get_compute_node_details("cached_node".to_string()).await.unwrap();
+    assert_eq!(node_type, "Link");
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    assert_eq!(client.purge_deleted(std::time::Duration::ZERO).await.unwrap(), 1);
+    let (node_type, inputs, _) = client.get_compute_node_details("cached_node".to_string()).await.unwrap();
+    assert_eq!(node_type, "");
+    assert!(inputs.is_empty());
+}
+
+#[tokio::test]
+async fn test_transitive_downstream() {
+    let client = get_client().await;
+    // A -> C1 -> B -> C2 -> D -> C3 -> A (cycle back to the root)
+    let a = "trans_A".to_string();
+    let b = "trans_B".to_string();
+    let d = "trans_D".to_string();
+    let c1 = "trans_C1".to_string();
+    let c2 = "trans_C2".to_string();
+    let c3 = "trans_C3".to_string();
+
+    client.register_artifact(a.clone(), true, None).await.unwrap();
+    client.register_artifact(b.clone(), false, None).await.unwrap();
+    client.register_artifact(d.clone(), false, None).await.unwrap();
+
+    client.create_compute_node(c1.clone(), vec![a.clone()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs(c1.clone(), vec![b.clone()]).await.unwrap();
+    client.create_compute_node(c2.clone(), vec![b.clone()], "Link".to_string()).await.unwrap();
+    client.set_compute_node_outputs(c2.clone(), vec![d.clone()]).await.unwrap();
+    client.create_compute_node(c3.clone(), vec![d.clone()], "Package".to_string()).await.unwrap();
+    client.set_compute_node_outputs(c3.clone(), vec![a.clone()]).await.unwrap();
+
+    // Full closure terminates despite the cycle
+    let all: Vec<(String, usize)> = client.get_transitive_downstream(a.clone(), None)
+        .map(|r| r.unwrap())
+        .collect()
+        .await;
+    assert_eq!(all, vec![(c1.clone(), 1), (c2.clone(), 2), (c3.clone(), 3)]);
+
+    // Depth-limited walk
+    let limited: Vec<(String, usize)> = client.get_transitive_downstream(a.clone(), Some(2))
+        .map(|r| r.unwrap())
+        .collect()
+        .await;
+    assert_eq!(limited, vec![(c1, 1), (c2, 2)]);
+}
+
+#[tokio::test]
+async fn test_soft_delete_excluded_from_queries() {
+    let client = get_client().await;
+    let input = "soft_in".to_string();
+    let compute = "soft_compute".to_string();
+
+    client.register_artifact(input.clone(), true, None).await.unwrap();
+    client.create_compute_node(compute.clone(), vec![input.clone()], "Compile".to_string()).await.unwrap();
+    assert!(client.is_compute_node_live(compute.clone()).await.unwrap());
+    client.remove_compute_node(compute.clone(), false).await.unwrap();
+
+    let mut dirty_stream = client.get_dirty_compute_nodes();
+    while let Some(res) = dirty_stream.next().await {
+        assert_ne!(res.unwrap(), compute, "Soft-deleted node should not be dirty");
+    }
+
+    let mut downstream = client.get_downstream_compute_nodes(input);
+    while let Some(res) = downstream.next().await {
+        assert_ne!(res.unwrap(), compute, "Soft-deleted node should not be downstream");
+    }
+
+    // Tombstone is still inspectable, but no longer live
+    let (node_type, _, _) = client.get_compute_node_details(compute.clone()).await.unwrap();
+    assert_eq!(node_type, "Compile");
+    assert!(!client.is_compute_node_live(compute).await.unwrap());
+    assert!(!client.is_compute_node_live("soft_never_created".to_string()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_purge_deleted() {
+    let client = get_client().await;
+    let input = "purge_in".to_string();
+    let compute = "purge_compute".to_string();
+
+    client.register_artifact(input.clone(), true, None).await.unwrap();
+    client.create_compute_node(compute.clone(), vec![input.clone()], "Compile".to_string()).await.unwrap();
+    client.remove_compute_node(compute.clone(), false).await.unwrap();
+
+    // Still within the retention window
+    client.purge_deleted(std::time::Duration::from_secs(3600)).await.unwrap();
+    let (node_type, _, _) = client.get_compute_node_details(compute.clone()).await.unwrap();
+    assert_eq!(node_type, "Compile", "Recent tombstone should survive purge");
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let purged = client.purge_deleted(std::time::Duration::ZERO).await.unwrap();
+    assert!(purged >= 1);
+    let (node_type, inputs, _) = client.get_compute_node_details(compute).await.unwrap();
+    assert_eq!(node_type, "");
+    assert!(inputs.is_empty());
+}
+
+#[tokio::test]
+async fn test_purge_deleted_pages() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    let table = format!("test-graph-purge-pages-{}", uuid::Uuid::new_v4());
+    let client = get_client_for_table(&table).await;
+    let raw = dynamo_client().await;
+    for i in 0..4 {
+        let id = format!("purge_page_{}", i);
+        client.create_compute_node(id.clone(), vec!["purge_page_in".to_string()], "Compile".to_string()).await.unwrap();
+        client.remove_compute_node(id.clone(), false).await.unwrap();
+        // Padding so the tombstones span more than one 1MB query page
+        raw.update_item().table_name(&table)
+            .key("pk", AttributeValue::S(format!("COMPUTE#{}", id)))
+            .key("sk", AttributeValue::S("META".to_string()))
+            .update_expression("SET padding = :p")
+            .expression_attribute_values(":p", AttributeValue::S("x".repeat(300_000)))
+            .send().await.unwrap();
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    assert_eq!(client.purge_deleted(std::time::Duration::ZERO).await.unwrap(), 4);
+    for i in 0..4 {
+        let (node_type, _, _) = client.get_compute_node_details(format!("purge_page_{}", i)).await.unwrap();
+        assert_eq!(node_type, "");
+    }
+}
+
+#[tokio::test]
+async fn test_consumers_span_pages() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    let table = format!("test-graph-consumer-pages-{}", uuid::Uuid::new_v4());
+    let client = get_client_for_table(&table).await;
+    let raw = dynamo_client().await;
+    let input = "consumer_page_in".to_string();
+    client.register_artifact(input.clone(), true, None).await.unwrap();
+    for i in 0..5 {
+        let id = format!("consumer_page_{}", i);
+        client.create_compute_node(id.clone(), vec![input.clone()], "Compile".to_string()).await.unwrap();
+        // Padding so the edges span more than one 1MB query page
+        raw.update_item().table_name(&table)
+            .key("pk", AttributeValue::S(format!("ARTIFACT#{}", input)))
+            .key("sk", AttributeValue::S(format!("COMPUTE#{}", id)))
+            .update_expression("SET padding = :p")
+            .expression_attribute_values(":p", AttributeValue::S("x".repeat(300_000)))
+            .send().await.unwrap();
+    }
+    // A tombstoned edge still takes up room on its page
+    client.remove_compute_node("consumer_page_0".to_string(), false).await.unwrap();
+
+    let mut downstream: Vec<String> = client.get_transitive_downstream(input.clone(), None)
+        .map(|r| r.unwrap().0)
+        .collect()
+        .await;
+    downstream.sort();
+    assert_eq!(downstream, (1..5).map(|i| format!("consumer_page_{}", i)).collect::<Vec<_>>());
+    assert_eq!(client.touch_artifact(input).await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn test_artifact_generations_staleness() {
+    let client = get_client().await;
+    let source = "checksum_generations_src".to_string();
+    let compute = "compute_generations".to_string();
+
+    client.register_artifact(source.clone(), true, Some("gen-1".to_string())).await.unwrap();
+    client.create_compute_node(compute.clone(), vec![source.clone()], "Compile".to_string()).await.unwrap();
+
+    let (_, _, built_against) = client.get_compute_node_details(compute.clone()).await.unwrap();
+    assert_eq!(built_against.get(&source).map(String::as_str), Some("gen-1"));
+    assert!(!client.is_compute_node_stale(compute.clone()).await.unwrap());
+
+    // Same generation re-registered: still fresh
+    client.register_artifact(source.clone(), true, Some("gen-1".to_string())).await.unwrap();
+    assert!(!client.is_compute_node_stale(compute.clone()).await.unwrap());
+
+    // Updated in place
+    client.register_artifact(source.clone(), true, Some("gen-2".to_string())).await.unwrap();
+    assert_eq!(client.get_artifact_generation(source).await.unwrap().as_deref(), Some("gen-2"));
+    assert!(client.is_compute_node_stale(compute).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_stats() {
+    // Own table, since the shared one accumulates items from every test
+    let client = get_client_for_table(&format!("test-graph-stats-{}", uuid::Uuid::new_v4())).await;
+
+    client.register_artifact("stats_src".to_string(), true, None).await.unwrap();
+    client.register_artifact("stats_out".to_string(), false, None).await.unwrap();
+    client.register_artifact("stats_orphan".to_string(), false, None).await.unwrap();
+    client.mark_artifact_orphaned("stats_orphan".to_string()).await.unwrap();
+
+    client.create_compute_node("stats_clean".to_string(), vec!["stats_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("stats_clean".to_string(), vec!["stats_out".to_string()]).await.unwrap();
+    client.create_compute_node("stats_dirty".to_string(), vec!["stats_out".to_string()], "Link".to_string()).await.unwrap();
+
+    let stats = client.stats().await.unwrap();
+    assert_eq!(stats, GraphStats { artifacts: 3, compute_nodes: 2, dirty: 1, orphans: 1 });
+}
+
+#[tokio::test]
+async fn test_dirty_compute_nodes_paging() {
+    let client = get_client_for_table(&format!("test-graph-paging-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("paging_src".to_string(), true, None).await.unwrap();
+    let mut expected = Vec::new();
+    for i in 0..5 {
+        let id = format!("paging_node_{}", i);
+        client.create_compute_node(id.clone(), vec!["paging_src".to_string()], "Compile".to_string()).await.unwrap();
+        expected.push(id);
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let page = client.get_dirty_compute_nodes_page(cursor.as_deref(), 2).await.unwrap();
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.clone());
+        pages += 1;
+
+        // The cursor survives a round trip through a client
+        let page: Page<String> = serde_json::from_str(&serde_json::to_string(&page).unwrap()).unwrap();
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+        assert!(pages < 10, "paging did not terminate");
+    }
+
+    seen.sort();
+    assert_eq!(seen, expected);
+    assert!(pages >= 3);
+
+    let downstream = client.get_downstream_compute_nodes_page("paging_src".to_string(), None, 2).await.unwrap();
+    assert_eq!(downstream.items.len(), 2);
+    assert!(downstream.next_cursor.is_some());
+
+    // Limits past i32::MAX are clamped rather than wrapped
+    assert_eq!(client.get_dirty_compute_nodes_page(None, usize::MAX).await.unwrap().items.len(), 5);
+    assert_eq!(client.get_downstream_compute_nodes_page("paging_src".to_string(), None, 1 << 32).await.unwrap().items.len(), 5);
+
+    assert!(client.get_dirty_compute_nodes_page(Some("not-a-cursor"), 2).await.is_err());
+
+    // A zero limit is rejected up front instead of reaching DynamoDB
+    let err = client.get_dirty_compute_nodes_page(None, 0).await.unwrap_err();
+    assert_eq!(err.to_string(), "Page limit must be at least 1");
+    let err = client.get_downstream_compute_nodes_page("paging_src".to_string(), None, 0).await.unwrap_err();
+    assert_eq!(err.to_string(), "Page limit must be at least 1");
+}
+
+#[tokio::test]
+async fn test_strict_create_rejects_unknown_inputs() {
+    let client = get_client().await;
+    let known = format!("strict_known_{}", uuid::Uuid::new_v4());
+    let missing = format!("strict_missing_{}", uuid::Uuid::new_v4());
+    let compute = format!("strict_node_{}", uuid::Uuid::new_v4());
+    client.register_artifact(known.clone(), true, None).await.unwrap();
+
+    let err = client.create_compute_node_strict(compute.clone(), vec![known.clone(), missing.clone()], "Compile".to_string()).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownInputs>(), Some(&UnknownInputs(vec![missing.clone()])));
+
+    // Nothing was created
+    let mut downstream = client.get_downstream_compute_nodes(known.clone());
+    assert!(downstream.next().await.is_none());
+
+    // Non-strict keeps the old, permissive behaviour; strict passes once inputs exist
+    client.create_compute_node(compute.clone(), vec![missing.clone()], "Compile".to_string()).await.unwrap();
+    client.create_compute_node_strict(format!("{}_ok", compute), vec![known], "Compile".to_string()).await.unwrap();
+}
+
+/// Answers every BatchGetItem as if DynamoDB had throttled all of its keys, counting them.
+#[derive(Debug, Default)]
+struct ThrottleBatchGets {
+    requests: Arc<AtomicUsize>,
+    request_items: std::sync::Mutex<Option<serde_json::Value>>,
+}
+
+impl Intercept for ThrottleBatchGets {
+    fn name(&self) -> &'static str {
+        "ThrottleBatchGets"
+    }
+
+    fn read_before_transmit(&self, context: &BeforeTransmitInterceptorContextRef<'_>, _: &RuntimeComponents, _: &mut ConfigBag) -> Result<(), BoxError> {
+        if context.request().headers().get("x-amz-target").is_some_and(|target| target.ends_with(".BatchGetItem")) {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let body: serde_json::Value = serde_json::from_slice(context.request().body().bytes().unwrap_or_default())?;
+            *self.request_items.lock().unwrap() = body.get("RequestItems").cloned();
+        }
+        Ok(())
+    }
+
+    fn modify_before_deserialization(&self, context: &mut BeforeDeserializationInterceptorContextMut<'_>, _: &RuntimeComponents, _: &mut ConfigBag) -> Result<(), BoxError> {
+        if let Some(items) = self.request_items.lock().unwrap().take() {
+            *context.response_mut().body_mut() = serde_json::json!({ "Responses": {}, "UnprocessedKeys": items }).to_string().into();
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_strict_create_gives_up_on_unprocessed_keys() {
+    let table = format!("test-graph-throttled-{}", uuid::Uuid::new_v4());
+    let raw = dynamo_client().await;
+    create_table(&raw, &table).await;
+    let throttle = ThrottleBatchGets::default();
+    let requests = throttle.requests.clone();
+    let client = DependencyGraph::new(aws_sdk_dynamodb::Client::from_conf(raw.config().to_builder().interceptor(throttle).build()), table);
+
+    let err = client.create_compute_node_strict("throttled_node".to_string(), vec!["throttled_in".to_string()], "Compile".to_string()).await.unwrap_err();
+    assert!(err.to_string().contains("unprocessed"), "unexpected error: {}", err);
+    assert_eq!(requests.load(Ordering::SeqCst), 8);
+}
+
+#[tokio::test]
+async fn test_set_many_outputs() {
+    let client = get_client_for_table(&format!("test-graph-many-outputs-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("many_src".to_string(), true, None).await.unwrap();
+    for i in 0..3 {
+        client.create_compute_node(format!("many_node_{}", i), vec!["many_src".to_string()], "Compile".to_string()).await.unwrap();
+    }
+    assert_eq!(client.stats().await.unwrap().dirty, 3);
+
+    let updates = (0..3)
+        .map(|i| (format!("many_node_{}", i), vec![format!("many_out_{}", i)]))
+        .collect();
+    client.set_many_outputs(updates).await.unwrap();
+
+    let mut dirty_stream = client.get_dirty_compute_nodes();
+    assert!(dirty_stream.next().await.is_none(), "all nodes should be clean after set_many_outputs");
+
+    // Replacing an output orphans the old one
+    client.register_artifact("many_out_0".to_string(), false, None).await.unwrap();
+    client.set_many_outputs(vec![("many_node_0".to_string(), vec!["many_out_0b".to_string()])]).await.unwrap();
+    assert_eq!(client.stats().await.unwrap().orphans, 1);
+
+    // Repeating the current outputs is a no-op; a repeated node takes its last entry
+    client.set_many_outputs(vec![
+        ("many_node_1".to_string(), vec!["many_out_1".to_string()]),
+        ("many_node_2".to_string(), vec!["many_out_2_first".to_string()]),
+        ("many_node_2".to_string(), vec!["many_out_2".to_string()]),
+    ]).await.unwrap();
+    assert_eq!(client.stats().await.unwrap().orphans, 1);
+
+    // A deleted or unknown node fails the whole batch before anything is written
+    client.remove_compute_node("many_node_1".to_string(), false).await.unwrap();
+    for missing in ["many_node_1", "many_node_never"] {
+        let err = client.set_many_outputs(vec![
+            ("many_node_0".to_string(), vec!["many_out_0c".to_string()]),
+            (missing.to_string(), vec!["many_out_x".to_string()]),
+        ]).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<NodeNotFound>(), Some(&NodeNotFound(missing.to_string())));
+    }
+    assert_eq!(client.stats().await.unwrap().orphans, 1);
+}
+
+#[tokio::test]
+async fn test_rebuild_from_storage() {
+    use curio_db::storage::{ArtifactMetadata, ProducerMetadata};
+
+    let client = get_client_for_table(&format!("test-graph-rebuild-{}", uuid::Uuid::new_v4())).await;
+    let storage = test_storage("rebuild").await;
+
+    let producer = ProducerMetadata {
+        id: "rebuild_compile".to_string(),
+        node_type: "Compile".to_string(),
+        inputs: vec!["aaaaaa_src".to_string()],
+    };
+    let artifacts = [
+        ArtifactMetadata { id: "aaaaaa_src".to_string(), generation: Some("g1".to_string()), producer: None },
+        ArtifactMetadata { id: "bbbbbb_obj".to_string(), generation: None, producer: Some(producer.clone()) },
+        ArtifactMetadata { id: "cccccc_map".to_string(), generation: None, producer: Some(producer) },
+    ];
+    // A content file whose name ends in artifact.yaml is not metadata
+    let report = ("report_artifact.yaml".to_string(), b"title: Nightly report\n".to_vec());
+    for artifact in &artifacts {
+        storage.save_artifact(&artifact.id, &artifact.to_yaml().unwrap(), vec![report.clone()]).await.unwrap();
+    }
+
+    // Re-running converges on the same graph
+    for _ in 0..2 {
+        let summary = client.rebuild_from_storage(&storage).await.unwrap();
+        assert_eq!(summary, RebuildSummary { artifacts: 3, compute_nodes: 1, skipped: 0 });
+        assert_eq!(client.stats().await.unwrap(), GraphStats { artifacts: 3, compute_nodes: 1, dirty: 0, orphans: 0 });
+    }
+
+    let mut downstream = client.get_downstream_compute_nodes("aaaaaa_src".to_string());
+    assert_eq!(downstream.next().await.unwrap().unwrap(), "rebuild_compile");
+    assert!(downstream.next().await.is_none());
+
+    let (node_type, inputs, generations) = client.get_compute_node_details("rebuild_compile".to_string()).await.unwrap();
+    assert_eq!(node_type, "Compile");
+    assert_eq!(inputs, vec!["aaaaaa_src".to_string()]);
+    assert_eq!(generations.get("aaaaaa_src").map(String::as_str), Some("g1"));
+}
+
+#[tokio::test]
+async fn test_reconcile() {
+    use curio_db::storage::{ArtifactMetadata, ProducerMetadata};
+
+    let client = get_client_for_table(&format!("test-graph-reconcile-{}", uuid::Uuid::new_v4())).await;
+    let storage = test_storage("reconcile").await;
+
+    // aaaaaa_src is on both sides; bbbbbb_stale only in storage; cccccc_lost only in the graph
+    let producer = ProducerMetadata { id: "reconcile_compile".to_string(), node_type: "Compile".to_string(), inputs: vec!["aaaaaa_src".to_string()] };
+    for metadata in [
+        ArtifactMetadata { id: "aaaaaa_src".to_string(), generation: None, producer: None },
+        ArtifactMetadata { id: "bbbbbb_stale".to_string(), generation: None, producer: Some(producer) },
+    ] {
+        storage.save_artifact(&metadata.id, &metadata.to_yaml().unwrap(), vec![("out.bin".to_string(), b"x".to_vec())]).await.unwrap();
+    }
+    client.register_artifact("aaaaaa_src".to_string(), true, None).await.unwrap();
+    client.register_artifact("cccccc_lost".to_string(), false, None).await.unwrap();
+    client.create_compute_node("reconcile_compile".to_string(), vec!["aaaaaa_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("reconcile_compile".to_string(), vec!["cccccc_lost".to_string()]).await.unwrap();
+
+    let expected = ReconcileReport {
+        storage_only: vec!["bbbbbb_stale".to_string()],
+        graph_only: vec!["cccccc_lost".to_string()],
+        undeletable: vec![],
+        dry_run: true,
+    };
+    // Everything is too recent to touch: it may belong to a build still in progress
+    let report = client.reconcile(&storage, std::time::Duration::from_secs(3600), false).await.unwrap();
+    assert!(report.storage_only.is_empty() && report.graph_only.is_empty(), "unexpected report: {:?}", report);
+
+    assert_eq!(client.reconcile(&storage, std::time::Duration::ZERO, true).await.unwrap(), expected);
+    // A dry run changes nothing
+    assert_eq!(client.reconcile(&storage, std::time::Duration::ZERO, true).await.unwrap(), expected);
+
+    let report = client.reconcile(&storage, std::time::Duration::ZERO, false).await.unwrap();
+    assert_eq!(report, ReconcileReport { dry_run: false, ..expected });
+    assert!(storage.get_artifact_metadata("bbbbbb_stale").await.is_err());
+    assert_eq!(client.stats().await.unwrap().orphans, 1);
+
+    // Both sides now agree
+    let report = client.reconcile(&storage, std::time::Duration::ZERO, false).await.unwrap();
+    assert!(report.storage_only.is_empty() && report.graph_only.is_empty());
+}
+
+#[tokio::test]
+async fn test_reconcile_keeps_short_ids() {
+    use curio_db::storage::ArtifactMetadata;
+
+    let client = get_client_for_table(&format!("test-graph-reconcile-short-{}", uuid::Uuid::new_v4())).await;
+    let storage = test_storage("reconcile-short").await;
+
+    // Too short to shard, so "abc"'s keys are a prefix of "abcd"'s
+    for id in ["abc", "abcd"] {
+        let metadata = ArtifactMetadata { id: id.to_string(), ..Default::default() };
+        storage.save_artifact(id, &metadata.to_yaml().unwrap(), vec![("out.bin".to_string(), id.as_bytes().to_vec())]).await.unwrap();
+    }
+    client.register_artifact("abcd".to_string(), true, None).await.unwrap();
+
+    let report = client.reconcile(&storage, std::time::Duration::ZERO, false).await.unwrap();
+    assert_eq!(report, ReconcileReport { undeletable: vec!["abc".to_string()], dry_run: false, ..Default::default() });
+    assert_eq!(storage.delete_artifact("abc").await.unwrap(), 0);
+    for id in ["abc", "abcd"] {
+        assert!(storage.get_artifact_metadata(id).await.is_ok(), "'{}' was deleted", id);
+        assert_eq!(storage.get_artifact_file(id, "out.bin").await.unwrap(), id.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_diff() {
+    let client = get_client_for_table(&format!("test-graph-snapshot-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("snap_src".to_string(), true, None).await.unwrap();
+    for id in ["snap_a", "snap_b", "snap_c"] {
+        client.create_compute_node(id.to_string(), vec!["snap_src".to_string()], "Compile".to_string()).await.unwrap();
+    }
+    client.set_many_outputs(vec![
+        ("snap_a".to_string(), vec!["snap_a_out".to_string()]),
+        ("snap_b".to_string(), vec!["snap_b_out".to_string()]),
+    ]).await.unwrap();
+
+    let before = client.snapshot().await.unwrap();
+    assert_eq!(before.nodes.len(), 3);
+    assert_eq!(before.nodes.get("snap_c"), Some(&true));
+
+    // Dirty a, clean c, drop b, add d
+    client.create_compute_node("snap_a".to_string(), vec!["snap_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("snap_c".to_string(), vec!["snap_c_out".to_string()]).await.unwrap();
+    client.remove_compute_node("snap_b".to_string(), false).await.unwrap();
+    client.create_compute_node("snap_d".to_string(), vec!["snap_src".to_string()], "Compile".to_string()).await.unwrap();
+
+    let after = client.snapshot().await.unwrap();
+    assert_eq!(GraphSnapshot::diff(&before, &after), GraphDiff {
+        added: vec!["snap_d".to_string()],
+        removed: vec!["snap_b".to_string()],
+        newly_dirty: vec!["snap_a".to_string()],
+        newly_clean: vec!["snap_c".to_string()],
+    });
+    assert_eq!(GraphSnapshot::diff(&after, &after), GraphDiff::default());
+}
+
+#[tokio::test]
+async fn test_cleanup_orphans_checks_producers() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    let table = format!("test-graph-producers-{}", uuid::Uuid::new_v4());
+    let client = get_client_for_table(&table).await;
+    client.register_artifact("producers_src".to_string(), true, None).await.unwrap();
+    for id in ["producers_a", "producers_b", "producers_c"] {
+        client.register_artifact(id.to_string(), false, None).await.unwrap();
+    }
+
+    // Switching outputs away and back leaves a stale orphan mark on an artifact still produced
+    client.create_compute_node("producers_live".to_string(), vec!["producers_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("producers_live".to_string(), vec!["producers_a".to_string()]).await.unwrap();
+    client.set_compute_node_outputs("producers_live".to_string(), vec!["producers_b".to_string()]).await.unwrap();
+    client.set_compute_node_outputs("producers_live".to_string(), vec!["producers_a".to_string()]).await.unwrap();
+
+    // An orphaned output of a tombstoned node
+    client.create_compute_node("producers_gone".to_string(), vec!["producers_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("producers_gone".to_string(), vec!["producers_c".to_string()]).await.unwrap();
+    client.mark_artifact_orphaned("producers_c".to_string()).await.unwrap();
+    client.remove_compute_node("producers_gone".to_string(), false).await.unwrap();
+
+    assert_eq!(client.cleanup_orphans().await.unwrap(), 2, "b and c should be collected");
+    let raw = dynamo_client().await;
+    let artifact_exists = |id: &str| {
+        let raw = raw.clone();
+        let table = table.clone();
+        let pk = format!("ARTIFACT#{}", id);
+        async move {
+            raw.get_item().table_name(table).key("pk", AttributeValue::S(pk)).key("sk", AttributeValue::S("META".to_string()))
+                .send().await.unwrap().item.is_some()
+        }
+    };
+    assert!(artifact_exists("producers_a").await, "a live producer keeps its output");
+    assert!(!artifact_exists("producers_b").await);
+    assert!(!artifact_exists("producers_c").await);
+    let items_under = |id: &str| {
+        let raw = raw.clone();
+        let table = table.clone();
+        let pk = format!("ARTIFACT#{}", id);
+        async move {
+            raw.query().table_name(table).key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(pk))
+                .send().await.unwrap().items.unwrap_or_default().into_iter()
+                .map(|item| item["sk"].as_s().unwrap().clone())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(items_under("producers_a").await, ["META", "PRODUCER#producers_live"]);
+    assert!(items_under("producers_b").await.is_empty(), "the dropped output's producer edge goes with it");
+    assert!(items_under("producers_c").await.is_empty(), "the tombstone's producer edge goes with the artifact");
+
+    // The tombstone no longer lists the deleted output, so purging it doesn't re-create it
+    let tombstone = raw.get_item().table_name(&table)
+        .key("pk", AttributeValue::S("COMPUTE#producers_gone".to_string()))
+        .key("sk", AttributeValue::S("META".to_string()))
+        .send().await.unwrap().item.unwrap();
+    assert_eq!(tombstone["outputs"].as_l().unwrap(), &Vec::<AttributeValue>::new());
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    client.purge_deleted(std::time::Duration::ZERO).await.unwrap();
+    assert!(!artifact_exists("producers_c").await);
+}
+
+#[tokio::test]
+async fn test_backfill_producer_edges() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    let table = format!("test-graph-backfill-producers-{}", uuid::Uuid::new_v4());
+    let client = get_client_for_table(&table).await;
+    client.register_artifact("backfill_src".to_string(), true, None).await.unwrap();
+    client.register_artifact("backfill_out".to_string(), false, None).await.unwrap();
+    client.create_compute_node("backfill_node".to_string(), vec!["backfill_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.set_compute_node_outputs("backfill_node".to_string(), vec!["backfill_out".to_string()]).await.unwrap();
+
+    // As written before producer edges existed, with a stale orphan mark
+    let raw = dynamo_client().await;
+    raw.delete_item().table_name(&table)
+        .key("pk", AttributeValue::S("ARTIFACT#backfill_out".to_string()))
+        .key("sk", AttributeValue::S("PRODUCER#backfill_node".to_string()))
+        .send().await.unwrap();
+    client.mark_artifact_orphaned("backfill_out".to_string()).await.unwrap();
+
+    assert_eq!(client.backfill_producer_edges().await.unwrap(), 1);
+    assert_eq!(client.cleanup_orphans().await.unwrap(), 0, "the backfilled edge shows the live producer");
+    assert_eq!(client.backfill_producer_edges().await.unwrap(), 1, "re-running rewrites the same edge");
+}
+
+#[tokio::test]
+async fn test_find_dangling_artifacts() {
+    let client = get_client_for_table(&format!("test-graph-dangling-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("dangling_src".to_string(), true, None).await.unwrap();
+    client.create_compute_node("dangling_node".to_string(), vec!["dangling_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.register_artifact("dangling_produced".to_string(), false, None).await.unwrap();
+    client.set_compute_node_outputs("dangling_node".to_string(), vec!["dangling_produced".to_string()]).await.unwrap();
+
+    // Internal, but nothing produces it
+    client.register_artifact("dangling_lost".to_string(), false, None).await.unwrap();
+    // Orphans are left to GC
+    client.register_artifact("dangling_orphan".to_string(), false, None).await.unwrap();
+    client.mark_artifact_orphaned("dangling_orphan".to_string()).await.unwrap();
+
+    let dangling: Vec<String> = client.find_dangling_artifacts().map(|r| r.unwrap()).collect().await;
+    assert_eq!(dangling, vec!["dangling_lost".to_string()]);
+}
+
+#[tokio::test]
+async fn test_get_compute_nodes_by_type() {
+    let client = get_client_for_table(&format!("test-graph-by-type-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("type_src".to_string(), true, None).await.unwrap();
+    for (id, node_type) in [("type_compile_a", "Compile"), ("type_link", "Link"), ("type_compile_b", "Compile"), ("type_compile_gone", "Compile")] {
+        client.create_compute_node(id.to_string(), vec!["type_src".to_string()], node_type.to_string()).await.unwrap();
+    }
+    // Soft-deleted nodes drop out of the index
+    client.remove_compute_node("type_compile_gone".to_string(), false).await.unwrap();
+
+    let compile: Vec<String> = client.get_compute_nodes_by_type("Compile".to_string()).map(|r| r.unwrap()).collect().await;
+    assert_eq!(compile, vec!["type_compile_a".to_string(), "type_compile_b".to_string()]);
+    let link: Vec<String> = client.get_compute_nodes_by_type("Link".to_string()).map(|r| r.unwrap()).collect().await;
+    assert_eq!(link, vec!["type_link".to_string()]);
+    assert!(client.get_compute_nodes_by_type("Missing".to_string()).next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_set_outputs_on_missing_node() {
+    let client = get_client_for_table(&format!("test-graph-missing-node-{}", uuid::Uuid::new_v4())).await;
+    let err = client.set_compute_node_outputs("never_created".to_string(), vec!["stray_out".to_string()]).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<NodeNotFound>(), Some(&NodeNotFound("never_created".to_string())));
+    assert_eq!(client.stats().await.unwrap(), GraphStats::default());
+
+    // A deleted node is gone too
+    client.register_artifact("missing_src".to_string(), true, None).await.unwrap();
+    client.create_compute_node("deleted_node".to_string(), vec!["missing_src".to_string()], "Compile".to_string()).await.unwrap();
+    client.remove_compute_node("deleted_node".to_string(), false).await.unwrap();
+    let err = client.set_compute_node_outputs("deleted_node".to_string(), vec!["stray_out".to_string()]).await.unwrap_err();
+    assert!(err.downcast_ref::<NodeNotFound>().is_some(), "unexpected error: {}", err);
+}
+
+#[tokio::test]
+async fn test_set_outputs_idempotent() {
+    let client = get_client_for_table(&format!("test-graph-idempotent-outputs-{}", uuid::Uuid::new_v4())).await;
+    client.register_artifact("idem_src".to_string(), true, None).await.unwrap();
+    client.create_compute_node("idem_node".to_string(), vec!["idem_src".to_string()], "Compile".to_string()).await.unwrap();
+    for out in ["idem_a", "idem_b"] {
+        client.register_artifact(out.to_string(), false, None).await.unwrap();
+    }
+
+    let outputs = vec!["idem_a".to_string(), "idem_b".to_string()];
+    client.set_compute_node_outputs("idem_node".to_string(), outputs.clone()).await.unwrap();
+    let before = client.stats().await.unwrap();
+    client.set_compute_node_outputs("idem_node".to_string(), outputs).await.unwrap();
+
+    assert_eq!(client.stats().await.unwrap(), before);
+    assert_eq!(before.orphans, 0);
+    assert!(!client.is_compute_node_stale("idem_node".to_string()).await.unwrap());
+    assert!(client.find_dangling_artifacts().next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_import_export_graph() {
+    let client = get_client_for_table(&format!("test-graph-import-{}", uuid::Uuid::new_v4())).await;
+    let description: GraphDescription = serde_json::from_value(serde_json::json!({
+        "artifacts": [
+            { "id": "imp_src", "is_external": true, "generation": "gen-1" },
+            { "id": "imp_lib", "is_external": true },
+            { "id": "imp_obj" },
+        ],
+        "nodes": [
+            { "id": "imp_compile", "type": "Compile", "dirty": false },
+            { "id": "imp_link", "type": "Link" },
+        ],
+        "edges": [
+            { "kind": "input", "artifact": "imp_src", "node": "imp_compile" },
+            { "kind": "input", "artifact": "imp_lib", "node": "imp_compile" },
+            { "kind": "output", "node": "imp_compile", "artifact": "imp_obj" },
+            { "kind": "input", "artifact": "imp_obj", "node": "imp_link" },
+        ],
+    })).unwrap();
+    client.import_graph(description.clone()).await.unwrap();
+
+    let (node_type, inputs, generations) = client.get_compute_node_details("imp_compile".to_string()).await.unwrap();
+    assert_eq!(node_type, "Compile");
+    assert_eq!(inputs, vec!["imp_src".to_string(), "imp_lib".to_string()]);
+    assert_eq!(generations.get("imp_src").map(String::as_str), Some("gen-1"));
+    let consumers: Vec<_> = client.get_downstream_compute_nodes("imp_obj".to_string()).collect().await;
+    assert_eq!(consumers.into_iter().map(Result::unwrap).collect::<Vec<_>>(), vec!["imp_link".to_string()]);
+    let expected_stats = GraphStats { artifacts: 3, compute_nodes: 2, dirty: 1, orphans: 0 };
+    assert_eq!(client.stats().await.unwrap(), expected_stats);
+
+    // Exporting gives back the description (in id order); importing it again changes nothing
+    let exported = client.export_graph().await.unwrap();
+    let sorted = |mut d: GraphDescription| {
+        d.artifacts.sort_by(|a, b| a.id.cmp(&b.id));
+        d.nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        d.edges.sort_by_key(|e| format!("{:?}", e));
+        d
+    };
+    assert_eq!(sorted(exported.clone()), sorted(description));
+    client.import_graph(exported.clone()).await.unwrap();
+    assert_eq!(client.export_graph().await.unwrap(), exported);
+    assert_eq!(client.stats().await.unwrap(), expected_stats);
+
+    // Edges must name described nodes
+    let bad: GraphDescription = serde_json::from_value(serde_json::json!({
+        "edges": [{ "kind": "input", "artifact": "imp_src", "node": "imp_unknown" }],
+    })).unwrap();
+    assert!(client.import_graph(bad).await.is_err());
+}
+
+#[tokio::test]
+async fn test_reimport_graph_replaces_edges() {
+    let client = get_client_for_table(&format!("test-graph-reimport-{}", uuid::Uuid::new_v4())).await;
+    let description = |input: &str, output: &str| -> GraphDescription {
+        serde_json::from_value(serde_json::json!({
+            "artifacts": [{ "id": input, "is_external": true }, { "id": output }],
+            "nodes": [{ "id": "re_node", "type": "Compile" }],
+            "edges": [
+                { "kind": "input", "artifact": input, "node": "re_node" },
+                { "kind": "input", "artifact": input, "node": "re_node" },
+                { "kind": "output", "node": "re_node", "artifact": output },
+            ],
+        })).unwrap()
+    };
+
+    // A repeated edge is written once; a dirty node keeps its outputs and stays dirty
+    client.import_graph(description("re_in_a", "re_out_a")).await.unwrap();
+    let (_, inputs, _) = client.get_compute_node_details("re_node".to_string()).await.unwrap();
+    assert_eq!(inputs, vec!["re_in_a".to_string()]);
+    let dirty: Vec<_> = client.get_dirty_compute_nodes().map(Result::unwrap).collect().await;
+    assert_eq!(dirty, vec!["re_node".to_string()]);
+    let exported = client.export_graph().await.unwrap();
+    assert_eq!(exported.edges.len(), 2);
+
+    // Re-importing with other edges drops the old input edge and orphans the old output
+    client.import_graph(description("re_in_b", "re_out_b")).await.unwrap();
+    let old_consumers: Vec<_> = client.get_downstream_compute_nodes("re_in_a".to_string()).collect().await;
+    assert!(old_consumers.is_empty());
+    let consumers: Vec<_> = client.get_downstream_compute_nodes("re_in_b".to_string()).map(Result::unwrap).collect().await;
+    assert_eq!(consumers, vec!["re_node".to_string()]);
+    assert_eq!(client.stats().await.unwrap(), GraphStats { artifacts: 4, compute_nodes: 1, dirty: 1, orphans: 1 });
+}
@@ -1,42 +1,38 @@
 use curio_db::CurioDbClient;
+use futures::StreamExt;
 use std::env;
 use aws_sdk_dynamodb::types::{AttributeDefinition, KeySchemaElement, KeyType, ScalarAttributeType, BillingMode};
 
-#[tokio::test]
-async fn test_save_and_get_record() {
-    // Ensure DYNAMODB_ENDPOINT is set (usually via env or just hardcode for this test if running locally)
-    // We expect the test runner to set this, or we default if missing but only if we know we are in test mode.
-    // For this test, we assume the user/script sets DYNAMODB_ENDPOINT=http://localhost:8000
-    
-    let table_name = "test-table-1";
-    let client = CurioDbClient::new(table_name.to_string()).await;
-    
-    // Create the table (since it's ephemeral local DB)
-    // We need to access the inner client or expose a create_table method.
-    // Ideally CurioDbClient exposes what we need, or we cheat for testing by recreating the client with raw sdk.
-    // Let's modify CurioDbClient to expose a create_table helper for testing? Or just use raw SDK here.
-    
+/// Raw DynamoDB client on the local endpoint, for setting up tables behind `CurioDbClient`.
+async fn raw_client() -> aws_sdk_dynamodb::Client {
     let region_provider = aws_config::meta::region::RegionProviderChain::default_provider().or_else(aws_sdk_dynamodb::config::Region::new("us-east-1"));
     let shared_config = aws_config::from_env().region(region_provider).load().await;
-    let raw_client = if let Ok(endpoint) = env::var("DYNAMODB_ENDPOINT") {
-        let conf = aws_sdk_dynamodb::config::Builder::from(&shared_config)
-            .endpoint_url(endpoint)
-            .build();
-        aws_sdk_dynamodb::Client::from_conf(conf)
-    } else {
-        panic!("DYNAMODB_ENDPOINT must be set for integration tests");
-    };
-
-    let _ = raw_client.create_table()
-        .table_name(table_name)
+    let endpoint = env::var("DYNAMODB_ENDPOINT").expect("DYNAMODB_ENDPOINT must be set for integration tests");
+    let conf = aws_sdk_dynamodb::config::Builder::from(&shared_config)
+        .endpoint_url(endpoint)
+        .build();
+    aws_sdk_dynamodb::Client::from_conf(conf)
+}
+
+/// Creates a fresh `{prefix}-{uuid}` table keyed on `id` and returns its name. Unique per
+/// run, since the local tables outlive the test process.
+async fn ensure_table(prefix: &str) -> String {
+    let table_name = format!("{}-{}", prefix, uuid::Uuid::new_v4());
+    raw_client().await.create_table()
+        .table_name(&table_name)
         .attribute_definitions(AttributeDefinition::builder().attribute_name("id").attribute_type(ScalarAttributeType::S).build().expect("failed to build attr"))
         .key_schema(KeySchemaElement::builder().attribute_name("id").key_type(KeyType::Hash).build().expect("failed to build key"))
         .billing_mode(BillingMode::PayPerRequest)
         .send()
-        .await;
-        
-    // Wait a bit for table creation (instant in local usually)
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        .await
+        .expect("failed to create table");
+    table_name
+}
+
+#[tokio::test]
+async fn test_save_and_get_record() {
+    let table_name = ensure_table("test-table").await;
+    let client = CurioDbClient::new(table_name).await;
 
     // Test Save
     client.save_record("item1", "some data").await.expect("failed to save");
@@ -45,3 +41,69 @@ async fn test_save_and_get_record() {
     let result = client.get_record("item1").await.expect("failed to get");
     assert_eq!(result, Some("some data".to_string()));
 }
+
+#[tokio::test]
+async fn test_scan_records_prefix() {
+    let table_name = ensure_table("test-table-scan").await;
+    let client = CurioDbClient::new(table_name).await;
+
+    client.save_record("job#1", "first").await.expect("failed to save");
+    client.save_record("job#2", "second").await.expect("failed to save");
+    client.save_record("other#1", "third").await.expect("failed to save");
+
+    let mut scanned: Vec<(String, String)> = client.scan_records(Some("job#"))
+        .map(|r| r.expect("scan failed"))
+        .collect()
+        .await;
+    scanned.sort();
+    assert_eq!(scanned, vec![
+        ("job#1".to_string(), "first".to_string()),
+        ("job#2".to_string(), "second".to_string()),
+    ]);
+
+    let all: Vec<_> = client.scan_records(None).collect().await;
+    assert_eq!(all.len(), 3);
+}
+
+#[tokio::test]
+async fn test_mark_processed() {
+    let table_name = ensure_table("test-table").await;
+    let client = CurioDbClient::new(table_name).await;
+
+    let message_id = format!("msg-{}", uuid::Uuid::new_v4());
+    assert!(!client.is_processed(&message_id).await.expect("lookup failed"));
+    client.mark_processed(&message_id, std::time::Duration::from_secs(3600)).await.expect("mark failed");
+    assert!(client.is_processed(&message_id).await.expect("lookup failed"));
+
+    // An expired marker no longer counts, even before TTL removes it
+    let expired_id = format!("msg-expired-{}", uuid::Uuid::new_v4());
+    client.mark_processed(&expired_id, std::time::Duration::ZERO).await.expect("mark failed");
+    assert!(!client.is_processed(&expired_id).await.expect("lookup failed"));
+}
+
+#[tokio::test]
+async fn test_save_and_get_item() {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use std::collections::HashMap;
+
+    let table_name = ensure_table("test-table-items").await;
+    let client = CurioDbClient::new(table_name).await;
+
+    let attrs = HashMap::from([
+        ("count".to_string(), AttributeValue::N("42".to_string())),
+        ("owner".to_string(), AttributeValue::S("alice".to_string())),
+    ]);
+    client.save_item("item-typed", attrs.clone()).await.expect("failed to save");
+
+    let item = client.get_item("item-typed").await.expect("failed to get").expect("item missing");
+    assert_eq!(item, attrs);
+    assert_eq!(item["count"].as_n().expect("count should be a number"), "42");
+    assert_eq!(item["owner"].as_s().expect("owner should be a string"), "alice");
+    assert!(client.get_item("item-missing").await.expect("failed to get").is_none());
+
+    // save_record is a single-attribute item
+    client.save_record("item-record", "payload").await.expect("failed to save");
+    let item = client.get_item("item-record").await.expect("failed to get").expect("item missing");
+    assert_eq!(item, HashMap::from([("data".to_string(), AttributeValue::S("payload".to_string()))]));
+    assert_eq!(client.get_record("item-record").await.expect("failed to get"), Some("payload".to_string()));
+}
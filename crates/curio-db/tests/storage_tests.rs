@@ -1,4 +1,4 @@
-use curio_db::storage::ArtifactStorage;
+use curio_db::storage::{ArtifactStorage, IdScheme};
 use aws_sdk_s3::Client;
 
 async fn get_client_and_storage() -> (Client, ArtifactStorage) {
@@ -33,10 +33,50 @@ async fn test_store_and_get_type() {
     
     storage.store_compute_node_type(type_name, content).await.expect("Store failed");
     
-    let retrieved = storage.get_compute_node_type(type_name).await.expect("Get failed");
+    let retrieved = storage.get_compute_node_type(type_name, None).await.expect("Get failed");
     assert_eq!(retrieved, content);
 }
 
+#[tokio::test]
+async fn test_compute_node_type_versions() {
+    let (client, _) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let storage = ArtifactStorage::new(client, "test-bucket".to_string(), Some(format!("types-{}", uuid::Uuid::new_v4())));
+
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: cc").await.unwrap(), 1);
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: clang").await.unwrap(), 2);
+    // Re-storing the latest definition doesn't create a version
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: clang").await.unwrap(), 2);
+
+    assert_eq!(storage.get_compute_node_type("Compile", Some(1)).await.unwrap(), "cmd: cc");
+    assert_eq!(storage.get_compute_node_type("Compile", Some(2)).await.unwrap(), "cmd: clang");
+    assert_eq!(storage.get_compute_node_type("Compile", None).await.unwrap(), "cmd: clang");
+    assert!(storage.get_compute_node_type("Compile", Some(3)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_compute_node_type_recovers_from_stale_latest() {
+    let (client, _) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let prefix = format!("types-{}", uuid::Uuid::new_v4());
+    let storage = ArtifactStorage::new(client.clone(), "test-bucket".to_string(), Some(prefix.clone()));
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: cc").await.unwrap(), 1);
+
+    // v2 lands but `latest` isn't updated
+    let failing = Client::from_conf(client.config().to_builder().interceptor(FailPutsTo("/latest")).build());
+    let stale = ArtifactStorage::new(failing, "test-bucket".to_string(), Some(prefix));
+    assert!(stale.store_compute_node_type("Compile", "cmd: clang").await.is_err());
+    assert_eq!(storage.get_compute_node_type("Compile", None).await.unwrap(), "cmd: cc");
+
+    // Storing the same definition again reuses v2; a new one skips past it
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: clang").await.unwrap(), 2);
+    assert_eq!(storage.get_compute_node_type("Compile", None).await.unwrap(), "cmd: clang");
+    assert!(stale.store_compute_node_type("Compile", "cmd: gcc").await.is_err());
+    assert_eq!(storage.store_compute_node_type("Compile", "cmd: tcc").await.unwrap(), 4);
+    assert_eq!(storage.get_compute_node_type("Compile", Some(3)).await.unwrap(), "cmd: gcc");
+    assert_eq!(storage.get_compute_node_type("Compile", None).await.unwrap(), "cmd: tcc");
+}
+
 #[tokio::test]
 async fn test_save_artifact_path_logic() {
     let (client, storage) = get_client_and_storage().await;
@@ -67,3 +107,151 @@ async fn test_save_artifact_path_logic() {
     
     assert!(resp.is_ok(), "Should find object at hashed path: {}", expected_key);
 }
+
+#[tokio::test]
+async fn test_save_artifact_hashed_id_scheme() {
+    let (client, storage) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let storage = storage.with_id_scheme(IdScheme::Hash);
+
+    let id = "6f1c2a3e-9b7d-4c8e-a1f0-3d2b5e7c9a41";
+    let metadata = "meta: uuid";
+    storage.save_artifact(id, metadata, vec![]).await.expect("Save failed");
+
+    let meta_retrieved = storage.get_artifact_metadata(id).await.expect("Get failed");
+    assert_eq!(meta_retrieved, metadata);
+
+    // Fan-out uses the hash of the id, not the UUID's own characters
+    let hash = format!("{:x}", md5::compute(id));
+    let c: Vec<char> = hash.chars().collect();
+    let expected_key = format!("curio-data/artifacts/{}/{}/{}/{}/{}/{}/{}/artifact.yaml", c[0], c[1], c[2], c[3], c[4], c[5], hash);
+    let resp = client.get_object().bucket("test-bucket").key(&expected_key).send().await;
+    assert!(resp.is_ok(), "Should find object at hashed path: {}", expected_key);
+}
+
+/// Fails any PUT whose URI ends with the given suffix, before it is sent.
+#[derive(Debug)]
+struct FailPutsTo(&'static str);
+
+impl aws_sdk_s3::config::Intercept for FailPutsTo {
+    fn name(&self) -> &'static str {
+        "FailPutsTo"
+    }
+
+    fn read_before_transmit(
+        &self,
+        context: &aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &aws_sdk_s3::config::RuntimeComponents,
+        _cfg: &mut aws_sdk_s3::config::ConfigBag,
+    ) -> Result<(), aws_sdk_s3::error::BoxError> {
+        let request = context.request();
+        let path = request.uri().split('?').next().unwrap_or_default();
+        if request.method() == "PUT" && path.ends_with(self.0) {
+            return Err("injected upload failure".into());
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_save_artifact_rolls_back_partial_upload() {
+    let (client, _) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let failing = Client::from_conf(client.config().to_builder().interceptor(FailPutsTo("/second.bin")).build());
+    let storage = ArtifactStorage::new(failing, "test-bucket".to_string(), None);
+
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let files = vec![
+        ("first.bin".to_string(), vec![1]),
+        ("second.bin".to_string(), vec![2]),
+        ("third.bin".to_string(), vec![3]),
+    ];
+    assert!(storage.save_artifact(&id, "meta: partial", files).await.is_err());
+
+    // No commit marker, and the file that did upload was removed
+    assert!(storage.get_artifact_metadata(&id).await.is_err());
+    let c: Vec<char> = id.chars().collect();
+    let base = format!("curio-data/artifacts/{}/{}/{}/{}/{}/{}/{}/", c[0], c[1], c[2], c[3], c[4], c[5], id);
+    let listed = client.list_objects_v2().bucket("test-bucket").prefix(&base).send().await.unwrap();
+    assert!(listed.contents().is_empty(), "leftover objects: {:?}", listed.contents());
+
+    // Without the fault the same call succeeds, metadata last
+    let storage = ArtifactStorage::new(client.clone(), "test-bucket".to_string(), None);
+    storage.save_artifact(&id, "meta: complete", vec![("first.bin".to_string(), vec![1])]).await.unwrap();
+    assert_eq!(storage.get_artifact_metadata(&id).await.unwrap(), "meta: complete");
+}
+
+#[tokio::test]
+async fn test_failed_resave_keeps_existing_artifact() {
+    let (client, _) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let storage = ArtifactStorage::new(client.clone(), "test-bucket".to_string(), None);
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    storage.save_artifact(&id, "meta: v1", vec![("data.bin".to_string(), vec![1])]).await.unwrap();
+
+    // The re-save fails on its commit marker after writing a new file
+    let failing = Client::from_conf(client.config().to_builder().interceptor(FailPutsTo("/artifact.yaml")).build());
+    let resave = ArtifactStorage::new(failing, "test-bucket".to_string(), None);
+    let files = vec![("data.bin".to_string(), vec![2]), ("extra.bin".to_string(), vec![3])];
+    assert!(resave.save_artifact(&id, "meta: v2", files).await.is_err());
+
+    // The committed artifact survives; only the file the re-save created is removed
+    assert_eq!(storage.get_artifact_metadata(&id).await.unwrap(), "meta: v1");
+    assert!(storage.get_artifact_file(&id, "data.bin").await.is_ok());
+    assert!(storage.get_artifact_file(&id, "extra.bin").await.is_err());
+}
+
+#[derive(Default)]
+struct CapturingLogger {
+    events: std::sync::Mutex<Vec<curio_db::storage::AccessEvent>>,
+}
+
+impl curio_db::storage::AccessLogger for CapturingLogger {
+    fn log(&self, event: curio_db::storage::AccessEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[tokio::test]
+async fn test_access_logging() {
+    let (client, storage) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let logger = std::sync::Arc::new(CapturingLogger::default());
+    let storage = storage.with_access_logger(logger.clone());
+
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    storage.save_artifact(&id, "meta: logged", vec![("content.bin".to_string(), vec![7, 8])]).await.unwrap();
+    assert!(logger.events.lock().unwrap().is_empty(), "writes are not logged");
+
+    let before = chrono::Utc::now();
+    storage.get_artifact_metadata(&id).await.unwrap();
+    let events = logger.events.lock().unwrap().clone();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].id, id);
+    assert_eq!(events[0].file, "artifact.yaml");
+    assert!(events[0].at >= before);
+
+    assert_eq!(storage.get_artifact_file(&id, "content.bin").await.unwrap(), vec![7, 8]);
+    assert_eq!(logger.events.lock().unwrap().last().map(|e| e.file.clone()), Some("content.bin".to_string()));
+}
+
+#[tokio::test]
+async fn test_get_artifact_file_range() {
+    let (client, storage) = get_client_and_storage().await;
+    let _ = client.create_bucket().bucket("test-bucket").send().await;
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let content: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+    storage.save_artifact(&id, "meta: large", vec![("content.bin".to_string(), content.clone())]).await.unwrap();
+
+    let preview = storage.get_artifact_file_range(&id, "content.bin", 0, 1024).await.unwrap();
+    assert_eq!(preview, &content[..1024]);
+    let middle = storage.get_artifact_file_range(&id, "content.bin", 100_000, 100_500).await.unwrap();
+    assert_eq!(middle, &content[100_000..100_500]);
+
+    // Out-of-range windows are clamped to the object size
+    let len = content.len() as u64;
+    let tail = storage.get_artifact_file_range(&id, "content.bin", len - 10, len + 1000).await.unwrap();
+    assert_eq!(tail, &content[content.len() - 10..]);
+    assert!(storage.get_artifact_file_range(&id, "content.bin", len, len + 10).await.unwrap().is_empty());
+    assert!(storage.get_artifact_file_range(&id, "content.bin", 50, 50).await.unwrap().is_empty());
+}
@@ -7,6 +7,7 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tracing::{info, warn, error};
 use futures::StreamExt;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc;
@@ -38,27 +39,105 @@ struct MessageBody {
 // ... ResourceController implementation (omitted for brevity if using replace_file_content partial, but here I am replacing full file structure potentially? No, just partial.)
 // I will keep ResourceController as is if I can match the surrounding code.
 
+/// Counters for sizing the poller -> worker channel.
+#[derive(Debug, Default)]
+struct PollerMetrics {
+    /// Times the poller found the channel full and had to wait for a worker.
+    backpressure_events: AtomicUsize,
+    /// Peak number of messages being handled concurrently.
+    max_in_flight: AtomicUsize,
+}
+
+/// Hands `msg` to the workers, recording a backpressure event (and yielding once) if
+/// the channel is full before falling back to a blocking send. Errors if the receiver is gone.
+async fn send_to_workers<T>(tx: &mpsc::Sender<T>, msg: T, metrics: &PollerMetrics) -> Result<(), mpsc::error::SendError<T>> {
+    match tx.try_send(msg) {
+        Ok(()) => Ok(()),
+        Err(mpsc::error::TrySendError::Closed(msg)) => Err(mpsc::error::SendError(msg)),
+        Err(mpsc::error::TrySendError::Full(msg)) => {
+            let events = metrics.backpressure_events.fetch_add(1, Ordering::Relaxed) + 1;
+            if events.is_power_of_two() {
+                warn!("Worker channel full ({} backpressure events so far); poller is waiting on workers.", events);
+            }
+            tokio::task::yield_now().await;
+            tx.send(msg).await
+        }
+    }
+}
+
+const MEMORY_THRESHOLD_PERCENT: f64 = 90.0;
+const CPU_THRESHOLD_PERCENT: f64 = 95.0;
+const DISK_THRESHOLD_PERCENT: f64 = 90.0;
+
+/// Bounds for the adaptive throttle backoff.
+const MIN_THROTTLE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_THROTTLE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Outcome of a resource check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceStatus {
+    Ok,
+    /// Over a threshold; wait this long before checking again.
+    Throttle(Duration),
+}
+
+/// Usage percentages sampled in one check. `disk` is the disk holding the work dir.
+#[derive(Debug, Clone, Copy)]
+struct ResourceUsage {
+    memory: f64,
+    cpu: f64,
+    disk: f64,
+}
+
+impl ResourceUsage {
+    /// How far the worst resource is over its threshold, in percentage points (<= 0 when clear).
+    fn overage(&self) -> f64 {
+        (self.memory - MEMORY_THRESHOLD_PERCENT)
+            .max(self.cpu - CPU_THRESHOLD_PERCENT)
+            .max(self.disk - DISK_THRESHOLD_PERCENT)
+    }
+}
+
 struct ResourceController {
     sys: System,
     disks: Disks,
+    /// Only the disk holding this directory counts towards disk usage.
+    work_dir: PathBuf,
     last_check: Instant,
     check_interval: Duration,
+    /// Backoff and overage from the previous check, while throttled.
+    throttle: Option<(Duration, f64)>,
 }
 
 impl ResourceController {
     fn new() -> Self {
+        let work_dir = env::var("WORK_DIR").map(PathBuf::from).unwrap_or_else(|_| env::temp_dir());
+        Self::with_work_dir(work_dir)
+    }
+
+    fn with_work_dir(work_dir: PathBuf) -> Self {
+        // Resolve symlinks so the path is compared against real mount points
+        let work_dir = std::fs::canonicalize(&work_dir).unwrap_or(work_dir);
+        let check_interval = env::var("RESOURCE_CHECK_INTERVAL_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(5));
         Self {
             sys: System::new_all(),
             disks: Disks::new_with_refreshed_list(),
+            work_dir,
             last_check: Instant::now() - Duration::from_secs(60), // Force immediate check
-            check_interval: Duration::from_secs(5),
+            check_interval,
+            throttle: None,
         }
     }
 
-    fn check_resources(&mut self) -> bool {
+    /// Samples usage at most once per `check_interval` while healthy. Once throttled,
+    /// every call re-samples so polling resumes as soon as usage is back under the thresholds.
+    fn check_resources(&mut self) -> ResourceStatus {
         let now = Instant::now();
-        if now.duration_since(self.last_check) < self.check_interval {
-            return true;
+        if self.throttle.is_none() && now.duration_since(self.last_check) < self.check_interval {
+            return ResourceStatus::Ok;
         }
         self.last_check = now;
         
@@ -70,39 +149,66 @@ impl ResourceController {
         // Check Memory
         let total_mem = self.sys.total_memory();
         let used_mem = self.sys.used_memory();
-        let mem_usage_percent = if total_mem > 0 {
+        let memory = if total_mem > 0 {
             (used_mem as f64 / total_mem as f64) * 100.0
         } else {
             0.0
         };
 
         // Check CPU (global)
-        let global_cpu = self.sys.global_cpu_info().cpu_usage();
-        
-        if mem_usage_percent > 90.0 {
-            warn!("High memory usage detected: {:.2}%. Throttling polling.", mem_usage_percent);
-            return false;
-        }
+        let cpu = self.sys.global_cpu_info().cpu_usage() as f64;
 
-        if global_cpu > 95.0 {
-            warn!("High CPU usage detected: {:.2}%. Throttling polling.", global_cpu);
-            return false;
+        // Check Disk Usage (work dir only)
+        let disk = self.work_disk_usage(self.disks.iter().map(|d| (d.mount_point(), d.total_space(), d.available_space())));
+
+        self.evaluate(ResourceUsage { memory, cpu, disk })
+    }
+
+    /// Usage percentage of the disk containing `work_dir`, i.e. the `(mount point, total,
+    /// available)` entry with the longest mount point that is a prefix of it. Other mounts
+    /// (read-only data volumes and the like) are ignored.
+    fn work_disk_usage<'a>(&self, disks: impl Iterator<Item = (&'a Path, u64, u64)>) -> f64 {
+        let Some((mount, total, available)) = disks
+            .filter(|(mount, _, _)| self.work_dir.starts_with(mount))
+            .max_by_key(|(mount, _, _)| mount.components().count())
+        else {
+            warn!("No disk found for work dir {:?}; skipping disk check.", self.work_dir);
+            return 0.0;
+        };
+        if total == 0 {
+            return 0.0;
+        }
+        let usage = 100.0 - ((available as f64 / total as f64) * 100.0);
+        if usage > DISK_THRESHOLD_PERCENT {
+            warn!("High disk usage detected on {:?}: {:.2}%.", mount, usage);
         }
+        usage
+    }
 
-        // Check Disk Usage
-        for disk in &self.disks {
-            let total_space = disk.total_space();
-            let available_space = disk.available_space();
-            if total_space > 0 {
-                let usage = 100.0 - ((available_space as f64 / total_space as f64) * 100.0);
-                if usage > 90.0 {
-                    warn!("High disk usage detected on {:?}: {:.2}%. Throttling polling.", disk.mount_point(), usage);
-                    return false;
-                }
+    /// Turns a usage sample into a status. The backoff doubles while usage stays at or
+    /// above the previous check's level, halves while it is falling, and is dropped
+    /// entirely once everything is back under the thresholds.
+    fn evaluate(&mut self, usage: ResourceUsage) -> ResourceStatus {
+        let overage = usage.overage();
+        if overage <= 0.0 {
+            if self.throttle.take().is_some() {
+                info!("Resource usage back under thresholds. Resuming polling.");
             }
+            return ResourceStatus::Ok;
         }
-        
-        true
+
+        let backoff = match self.throttle {
+            None => MIN_THROTTLE_BACKOFF,
+            Some((previous, previous_overage)) if overage < previous_overage => (previous / 2).max(MIN_THROTTLE_BACKOFF),
+            Some((previous, _)) => (previous * 2).min(MAX_THROTTLE_BACKOFF),
+        };
+        self.throttle = Some((backoff, overage));
+
+        warn!(
+            "High resource usage (memory {:.2}%, cpu {:.2}%, disk {:.2}%). Throttling polling for {:?}.",
+            usage.memory, usage.cpu, usage.disk, backoff
+        );
+        ResourceStatus::Throttle(backoff)
     }
 }
 
@@ -799,6 +905,7 @@ async fn main() -> anyhow::Result<()> {
     let (tx, rx) = mpsc::channel(concurrency * 2);
 
     let active_tasks = Arc::new(AtomicUsize::new(0));
+    let metrics = Arc::new(PollerMetrics::default());
     let shutdown_signal = Arc::new(tokio::sync::Notify::new());
 
     // Worker Task
@@ -811,6 +918,7 @@ async fn main() -> anyhow::Result<()> {
         let status_table = status_table.clone();
         let build_bucket = build_bucket.clone();
         let active_tasks = active_tasks.clone();
+        let metrics = metrics.clone();
         
         tokio::spawn(async move {
             ReceiverStream::new(rx)
@@ -824,8 +932,10 @@ async fn main() -> anyhow::Result<()> {
                     let build_bucket = build_bucket.clone();
                     
                     let active_tasks = active_tasks.clone();
+                    let metrics = metrics.clone();
                     async move {
-                        active_tasks.fetch_add(1, Ordering::SeqCst);
+                        let in_flight = active_tasks.fetch_add(1, Ordering::SeqCst) + 1;
+                        metrics.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
                         if let Err(e) = handle_message(&sqs_client, &s3_client, &dynamo_client, &queue_url, &table_name, &status_table, &build_bucket, message).await {
                             error!("Error processing message: {}", e);
                         }
@@ -852,9 +962,9 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting poller loop...");
     loop {
         // Resource Check
-        if !resources.check_resources() {
+        if let ResourceStatus::Throttle(backoff) = resources.check_resources() {
             // Resources are exhausted. Wait a bit and skip this poll cycle.
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            tokio::time::sleep(backoff).await;
             
             // Check shutdown while backoff
             if futures::poll!(Box::pin(sigterm.recv())).is_ready() {
@@ -887,7 +997,7 @@ async fn main() -> anyhow::Result<()> {
                         if !messages.is_empty() {
                             // last_activity = Instant::now();
                             for msg in messages {
-                                if tx.send(msg).await.is_err() {
+                                if send_to_workers(&tx, msg, &metrics).await.is_err() {
                                     info!("Receiver dropped, stopping poller.");
                                     return Ok(());
                                 }
@@ -906,6 +1016,84 @@ async fn main() -> anyhow::Result<()> {
     drop(tx);
     worker_handle.await?;
 
+    info!(
+        "Poller metrics: {} backpressure events, max in flight {} (channel capacity {}, concurrency {}).",
+        metrics.backpressure_events.load(Ordering::Relaxed),
+        metrics.max_in_flight.load(Ordering::Relaxed),
+        concurrency * 2,
+        concurrency,
+    );
+
     info!("Exiting processor.");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(memory: f64) -> ResourceUsage {
+        ResourceUsage { memory, cpu: 10.0, disk: 50.0 }
+    }
+
+    #[test]
+    fn test_throttle_backoff_shrinks_on_recovery() {
+        let mut resources = ResourceController::new();
+
+        assert_eq!(resources.evaluate(usage(97.0)), ResourceStatus::Throttle(Duration::from_secs(1)));
+        assert_eq!(resources.evaluate(usage(98.0)), ResourceStatus::Throttle(Duration::from_secs(2)));
+        assert_eq!(resources.evaluate(usage(98.0)), ResourceStatus::Throttle(Duration::from_secs(4)));
+
+        // Falling but still over the threshold: back off less
+        assert_eq!(resources.evaluate(usage(93.0)), ResourceStatus::Throttle(Duration::from_secs(2)));
+
+        // Clear: resume immediately, and the next throttle starts from the minimum again
+        assert_eq!(resources.evaluate(usage(60.0)), ResourceStatus::Ok);
+        assert_eq!(resources.evaluate(usage(95.0)), ResourceStatus::Throttle(Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_events_recorded() {
+        let metrics = PollerMetrics::default();
+        let (tx, mut rx) = mpsc::channel(2);
+
+        send_to_workers(&tx, 1, &metrics).await.unwrap();
+        send_to_workers(&tx, 2, &metrics).await.unwrap();
+        assert_eq!(metrics.backpressure_events.load(Ordering::Relaxed), 0);
+
+        // Channel is saturated; a slow worker frees a slot later
+        let worker = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut received = Vec::new();
+            while let Some(v) = rx.recv().await {
+                received.push(v);
+            }
+            received
+        });
+        send_to_workers(&tx, 3, &metrics).await.unwrap();
+        send_to_workers(&tx, 4, &metrics).await.unwrap();
+        drop(tx);
+
+        assert!(metrics.backpressure_events.load(Ordering::Relaxed) >= 1);
+        assert_eq!(worker.await.unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_disk_check_scoped_to_work_dir() {
+        let mut resources = ResourceController::with_work_dir(PathBuf::from("/nonexistent/work/tmp"));
+        let disks = || [
+            (Path::new("/"), 100, 80),
+            (Path::new("/nonexistent"), 100, 40),
+            (Path::new("/mnt/readonly-data"), 100, 0),
+            (Path::new("/nonexistent/work-other"), 100, 0),
+        ].into_iter();
+
+        // Longest matching mount wins; the full, unrelated mounts are ignored
+        let disk = resources.work_disk_usage(disks());
+        assert_eq!(disk, 60.0);
+        assert_eq!(resources.evaluate(ResourceUsage { memory: 10.0, cpu: 10.0, disk }), ResourceStatus::Ok);
+
+        let resources = ResourceController::with_work_dir(PathBuf::from("/mnt/readonly-data/scratch"));
+        assert_eq!(resources.work_disk_usage(disks()), 100.0);
+    }
+}
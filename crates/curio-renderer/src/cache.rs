@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+const DEFAULT_MAX_ENTRIES: usize = 256;
+const DEFAULT_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// (artifact id, S3 key)
+pub type CacheKey = (String, String);
+
+#[derive(Debug)]
+struct Entry {
+    etag: String,
+    content: String,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<CacheKey, Entry>,
+    bytes: usize,
+    clock: u64,
+}
+
+/// Artifact content fetched by this (warm) Lambda instance, kept so repeat requests only
+/// need a `head_object` to confirm the ETag is unchanged. Bounded by entry count and total
+/// content bytes; the least recently used entries are evicted first.
+#[derive(Debug)]
+pub struct ArtifactCache {
+    max_entries: usize,
+    max_bytes: usize,
+    state: Mutex<State>,
+}
+
+impl ArtifactCache {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self { max_entries, max_bytes, state: Mutex::new(State::default()) }
+    }
+
+    /// Bounds come from `RENDERER_CACHE_MAX_ENTRIES` and `RENDERER_CACHE_MAX_BYTES`;
+    /// setting either to 0 disables caching.
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: usize| env::var(name).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(default);
+        Self::new(var("RENDERER_CACHE_MAX_ENTRIES", DEFAULT_MAX_ENTRIES), var("RENDERER_CACHE_MAX_BYTES", DEFAULT_MAX_BYTES))
+    }
+
+    /// Returns the cached (etag, content) for `key`, marking it recently used.
+    pub fn get(&self, key: &CacheKey) -> Option<(String, String)> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let now = state.clock;
+        let entry = state.entries.get_mut(key)?;
+        entry.last_used = now;
+        Some((entry.etag.clone(), entry.content.clone()))
+    }
+
+    pub fn insert(&self, key: CacheKey, etag: String, content: String) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(old) = state.entries.remove(&key) {
+            state.bytes -= old.content.len();
+        }
+        if self.max_entries == 0 || content.len() > self.max_bytes {
+            return;
+        }
+        while state.entries.len() >= self.max_entries || state.bytes + content.len() > self.max_bytes {
+            let Some(oldest) = state.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            if let Some(evicted) = state.entries.remove(&oldest) {
+                state.bytes -= evicted.content.len();
+            }
+        }
+        state.clock += 1;
+        state.bytes += content.len();
+        let last_used = state.clock;
+        state.entries.insert(key, Entry { etag, content, last_used });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> CacheKey {
+        (id.to_string(), format!("key/{}", id))
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = ArtifactCache::new(2, 10);
+        cache.insert(key("a"), "e1".to_string(), "aaa".to_string());
+        cache.insert(key("b"), "e2".to_string(), "bbb".to_string());
+        assert!(cache.get(&key("a")).is_some());
+
+        // Entry bound: "b" is the least recently used
+        cache.insert(key("c"), "e3".to_string(), "ccc".to_string());
+        assert!(cache.get(&key("b")).is_none());
+        assert_eq!(cache.get(&key("a")), Some(("e1".to_string(), "aaa".to_string())));
+
+        // Byte bound: 4 + 4 + 4 > 10
+        let cache = ArtifactCache::new(10, 10);
+        for id in ["a", "b", "c"] {
+            cache.insert(key(id), "e".to_string(), "xxxx".to_string());
+        }
+        assert!(cache.get(&key("a")).is_none());
+        assert!(cache.get(&key("b")).is_some());
+        assert!(cache.get(&key("c")).is_some());
+
+        // Larger than the whole cache: not stored
+        cache.insert(key("e"), "e5".to_string(), "e".repeat(11));
+        assert!(cache.get(&key("e")).is_none());
+    }
+}
@@ -3,9 +3,45 @@ use serde_json::{json, Value};
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_dynamodb::Client as DynamoClient;
+use curio_db::{ArtifactMetadata, DependencyGraph};
 use std::collections::HashMap;
 use std::env;
 use anyhow::{Context, Result};
+use std::io::Write;
+use base64::Engine;
+use flate2::{write::GzEncoder, Compression};
+use std::sync::Arc;
+
+mod cache;
+use cache::ArtifactCache;
+mod template;
+
+/// Lambda caps a synchronous response at 6 MB including the envelope, and API Gateway
+/// fails such responses opaquely, so bodies are kept a little under that.
+const DEFAULT_MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+
+/// Deployment settings, read from the environment once per Lambda instance.
+#[derive(Debug, Default)]
+struct RendererConfig {
+    /// `EXTERNAL_INPUTS_CONFIG`: `[{"name": ..., "bucket": ...}]` entries mapping
+    /// `external/<name>/...` ids to buckets.
+    external_inputs: Vec<Value>,
+    /// `TABLE_NAME`: the catalog mapping ids to S3 keys. Without it, the id is the key.
+    table_name: String,
+    /// `GRAPH_TABLE_NAME`: the dependency graph, for producer details in metadata mode.
+    graph_table_name: String,
+}
+
+impl RendererConfig {
+    fn from_env() -> Self {
+        let config_str = env::var("EXTERNAL_INPUTS_CONFIG").unwrap_or_else(|_| "[]".to_string());
+        Self {
+            external_inputs: serde_json::from_str(&config_str).unwrap_or_default(),
+            table_name: env::var("TABLE_NAME").unwrap_or_default(),
+            graph_table_name: env::var("GRAPH_TABLE_NAME").unwrap_or_default(),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -19,45 +55,159 @@ async fn main() -> Result<(), Error> {
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     let s3_client = S3Client::new(&config);
     let dynamo_client = DynamoClient::new(&config);
+    // Lives as long as the warm Lambda instance.
+    let cache = Arc::new(ArtifactCache::from_env());
+    let renderer_config = Arc::new(RendererConfig::from_env());
 
     let func = service_fn(move |event| {
         let s3 = s3_client.clone();
         let dynamo = dynamo_client.clone();
-        async move { handler(event, s3, dynamo).await }
+        let cache = cache.clone();
+        let renderer_config = renderer_config.clone();
+        async move { handler(event, s3, dynamo, &cache, &renderer_config).await }
     });
 
     lambda_runtime::run(func).await?;
     Ok(())
 }
 
-async fn handler(event: LambdaEvent<Value>, s3: S3Client, dynamo: DynamoClient) -> Result<Value, Error> {
+async fn handler(event: LambdaEvent<Value>, s3: S3Client, dynamo: DynamoClient, cache: &ArtifactCache, config: &RendererConfig) -> Result<Value, Error> {
     let payload = event.payload;
     println!("Received event: {}", payload);
 
+    let response = respond(&payload, s3, dynamo, cache, config).await?;
+    let max_body_bytes = env::var("RENDERER_MAX_BODY_BYTES").ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES);
+    let truncate = payload.pointer("/queryStringParameters/truncate").and_then(|v| v.as_str()) == Some("true");
+    Ok(fit_body(response, accepts_gzip(&payload), max_body_bytes, truncate)?)
+}
+
+/// Encodes the response for sending and checks the body against `limit`. An oversized
+/// body becomes a 413, or with `truncate` its content is cut to fit and flagged.
+fn fit_body(response: Value, gzip: bool, limit: usize, truncate: bool) -> Result<Value> {
+    let encode = |r: Value| if gzip { gzip_response(r) } else { Ok(r) };
+    let sent = encode(response.clone())?;
+    let size = body_len(&sent);
+    if size <= limit {
+        return Ok(sent);
+    }
+    if truncate {
+        if let Some(truncated) = truncate_content(&response, limit) {
+            return encode(truncated);
+        }
+    }
+    Ok(api_response(413, json!({
+        "error": format!(
+            "Response body is {} bytes, over the {} byte limit; fetch the artifact from S3 directly (e.g. with a presigned URL) or pass truncate=true",
+            size, limit
+        )
+    })))
+}
+
+fn body_len(response: &Value) -> usize {
+    response["body"].as_str().map_or(0, str::len)
+}
+
+/// Cuts the `content` of a successful response so the body fits in `limit` bytes, marking
+/// it `"truncated": true`. `None` for responses without string content.
+fn truncate_content(response: &Value, limit: usize) -> Option<Value> {
+    if response["statusCode"] != 200 {
+        return None;
+    }
+    let body: Value = serde_json::from_str(response["body"].as_str()?).ok()?;
+    let content = body["content"].as_str()?;
+    let mut keep = content.len();
+    loop {
+        while !content.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        let candidate = api_response(200, json!({ "content": &content[..keep], "truncated": true }));
+        let len = body_len(&candidate);
+        if len <= limit {
+            return Some(candidate);
+        }
+        if keep == 0 {
+            return None;
+        }
+        // Escaping makes the body longer than the content, so shrink in proportion
+        keep = (keep * limit / len).min(keep - 1);
+    }
+}
+
+async fn respond(payload: &Value, s3: S3Client, dynamo: DynamoClient, cache: &ArtifactCache, config: &RendererConfig) -> Result<Value, Error> {
     let query_params = payload.get("queryStringParameters")
         .and_then(|qp| qp.as_object())
         .cloned()
         .unwrap_or_default();
 
+    let format = query_params.get("format")
+        .and_then(|v| v.as_str())
+        .unwrap_or("text");
+
+    if format == "template" {
+        return Ok(render_template(&query_params, &s3, &dynamo, cache, config).await);
+    }
+
     let id = match query_params.get("id").and_then(|v| v.as_str()) {
         Some(id) => id.to_string(),
         None => return Ok(api_response(400, json!({"error": "Missing 'id' parameter"}))),
     };
 
-    let format = query_params.get("format")
-        .and_then(|v| v.as_str())
-        .unwrap_or("text");
+    if query_params.get("mode").and_then(|v| v.as_str()) == Some("metadata") {
+        return Ok(fetch_metadata(&id, &s3, &dynamo, config).await);
+    }
+
+    let content_str = match fetch_artifact(&id, &s3, &dynamo, cache, config).await {
+        Ok(content) => content,
+        Err(response) => return Ok(response),
+    };
+
+    // 3a. Extract Field if pointer requested
+    let pointer = query_params.get("pointer").and_then(|v| v.as_str());
+    let content_to_render = if let Some(ptr) = pointer {
+        // Parse JSON
+        let json_val: Value = match serde_json::from_str(&content_str) {
+            Ok(v) => v,
+            Err(e) => return Ok(api_response(400, json!({"error": format!("Failed to parse S3 content as JSON: {}", e)}))),
+        };
+
+        match json_val.pointer(ptr) {
+            Some(v) => {
+                match v {
+                    Value::String(s) => s.clone(),
+                    _ => v.to_string(), // Fallback for non-string
+                }
+            },
+            None => return Ok(api_response(404, json!({"error": format!("Pointer '{}' not found in document", ptr)}))),
+        }
+    } else {
+        content_str
+    };
 
+    // 4. Render
+    let result = if format == "text" {
+        // Decode HTML entities (e.g. &lt; -> <) before parsing tags
+        let decoded = html_escape::decode_html_entities(&content_to_render);
+        // Use html2text to strip tags and format
+        html2text::from_read(decoded.as_bytes(), 80)
+    } else {
+        content_to_render
+    };
+
+    Ok(api_response(200, json!({ "content": result })))
+}
+
+/// Resolves an artifact id to its bucket and S3 key.
+/// On failure, returns the API response to send back instead.
+async fn resolve_artifact(id: &str, dynamo: &DynamoClient, config: &RendererConfig) -> Result<(String, String), Value> {
     // 1. Resolve Bucket from Config
-    let config_str = env::var("EXTERNAL_INPUTS_CONFIG").unwrap_or_else(|_| "[]".to_string());
-    let config_json: Vec<Value> = serde_json::from_str(&config_str).unwrap_or_default();
-    
     let mut target_bucket = None;
     if id.starts_with("external/") {
          let parts: Vec<&str> = id.split('/').collect();
          if parts.len() > 1 {
              let input_name = parts[1];
-             for cfg in config_json {
+             for cfg in &config.external_inputs {
                  if cfg["name"] == input_name {
                      target_bucket = cfg["bucket"].as_str().map(|s| s.to_string());
                      break;
@@ -68,83 +218,222 @@ async fn handler(event: LambdaEvent<Value>, s3: S3Client, dynamo: DynamoClient)
 
     let bucket = match target_bucket {
         Some(b) => b,
-        None => return Ok(api_response(404, json!({"error": "Bucket not found for item"}))),
+        None => return Err(api_response(404, json!({"error": "Bucket not found for item"}))),
     };
 
     // 2. Resolve S3 Key from DynamoDB
-    let table_name = env::var("TABLE_NAME").unwrap_or_default();
-    let s3_key = if !table_name.is_empty() {
+    let s3_key = if !config.table_name.is_empty() {
         match dynamo.get_item()
-            .table_name(&table_name)
-            .key("id", aws_sdk_dynamodb::types::AttributeValue::S(id.clone()))
+            .table_name(&config.table_name)
+            .key("id", aws_sdk_dynamodb::types::AttributeValue::S(id.to_string()))
             .send().await 
         {
             Ok(resp) => {
                  if let Some(item) = resp.item {
-                     item.get("s3_key").and_then(|k| k.as_s().ok()).cloned().unwrap_or(id.clone())
+                     item.get("s3_key").and_then(|k| k.as_s().ok()).cloned().unwrap_or(id.to_string())
                  } else {
-                     return Ok(api_response(404, json!({"error": "Item not found in catalog"})));
+                     return Err(api_response(404, json!({"error": "Item not found in catalog"})));
                  }
             },
             Err(e) => {
                 println!("DynamoDB error: {}", e);
-                return Ok(api_response(500, json!({"error": "Database lookup failed"})));
+                return Err(api_response(500, json!({"error": "Database lookup failed"})));
             }
         }
     } else {
         // Fallback or dev mode without table?
-        id.clone()
+        id.to_string()
     };
 
-    // 3. Fetch from S3
-    let content_str = match s3.get_object()
+    Ok((bucket, s3_key))
+}
+
+/// Resolves an artifact id to its bucket and S3 key and fetches its content.
+/// Content in `cache` is reused while the object's ETag is unchanged.
+/// On failure, returns the API response to send back instead.
+async fn fetch_artifact(id: &str, s3: &S3Client, dynamo: &DynamoClient, cache: &ArtifactCache, config: &RendererConfig) -> Result<String, Value> {
+    let (bucket, s3_key) = resolve_artifact(id, dynamo, config).await?;
+
+    // 3. Fetch from S3, unless the cached copy is still current
+    let cache_key = (id.to_string(), s3_key.clone());
+    if let Some((etag, content)) = cache.get(&cache_key) {
+        match s3.head_object().bucket(&bucket).key(&s3_key).send().await {
+            Ok(head) if head.e_tag() == Some(etag.as_str()) => return Ok(content),
+            Ok(_) => {}
+            // Let the get_object below report the failure
+            Err(e) => println!("S3 head error: {}", e),
+        }
+    }
+
+    let fetched = match s3.get_object()
         .bucket(&bucket)
         .key(&s3_key)
         .send().await 
     {
         Ok(output) => {
-            let bytes = output.body.collect().await?.into_bytes();
-            String::from_utf8_lossy(&bytes).to_string()
-        },
+            let etag = output.e_tag().map(str::to_string);
+            output.body.collect().await.map(|body| (etag, body)).map_err(|e| e.to_string())
+        }
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Err(artifact_not_found()),
+        Err(e) => Err(e.to_string()),
+    };
+    match fetched {
+        Ok((etag, bytes)) => {
+            let content = String::from_utf8_lossy(&bytes.into_bytes()).to_string();
+            if let Some(etag) = etag {
+                cache.insert(cache_key, etag, content.clone());
+            }
+            Ok(content)
+        }
         Err(e) => {
              println!("S3 fetch error: {}", e);
-             return Ok(api_response(500, json!({"error": format!("S3 fetch failed: {}", e)})));
+             Err(api_response(500, json!({"error": format!("S3 fetch failed: {}", e)})))
         }
+    }
+}
+
+fn artifact_not_found() -> Value {
+    api_response(404, json!({"error": "Artifact not found"}))
+}
+
+/// Describes an artifact without fetching its content: the object's content type, size and
+/// ETag, and the `artifact.yaml` stored beside it (`null` for objects without one, such as
+/// external inputs). With `GRAPH_TABLE_NAME` set, the producing node's details are added.
+async fn fetch_metadata(id: &str, s3: &S3Client, dynamo: &DynamoClient, config: &RendererConfig) -> Value {
+    let (bucket, s3_key) = match resolve_artifact(id, dynamo, config).await {
+        Ok(location) => location,
+        Err(response) => return response,
     };
 
-    // 3a. Extract Field if pointer requested
-    let pointer = query_params.get("pointer").and_then(|v| v.as_str());
-    let content_to_render = if let Some(ptr) = pointer {
-        // Parse JSON
-        let json_val: Value = match serde_json::from_str(&content_str) {
-            Ok(v) => v,
-            Err(e) => return Ok(api_response(400, json!({"error": format!("Failed to parse S3 content as JSON: {}", e)}))),
-        };
+    let head = match s3.head_object().bucket(&bucket).key(&s3_key).send().await {
+        Ok(head) => head,
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => return artifact_not_found(),
+        Err(e) => {
+            println!("S3 head error: {}", e);
+            return api_response(500, json!({"error": format!("S3 head failed: {}", e)}));
+        }
+    };
 
-        match json_val.pointer(ptr) {
-            Some(v) => {
-                match v {
-                    Value::String(s) => s.clone(),
-                    _ => v.to_string(), // Fallback for non-string
+    let metadata_key = match s3_key.rsplit_once('/') {
+        Some((dir, _)) => format!("{}/artifact.yaml", dir),
+        None => "artifact.yaml".to_string(),
+    };
+    let yaml = match s3.get_object().bucket(&bucket).key(&metadata_key).send().await {
+        Ok(output) => match output.body.collect().await {
+            Ok(body) => Some(String::from_utf8_lossy(&body.into_bytes()).to_string()),
+            Err(e) => return api_response(500, json!({"error": format!("S3 fetch failed: {}", e)})),
+        },
+        Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => None,
+        Err(e) => {
+            println!("S3 fetch error: {}", e);
+            return api_response(500, json!({"error": format!("S3 fetch failed: {}", e)}));
+        }
+    };
+    let metadata = match yaml.as_deref().map(ArtifactMetadata::from_yaml).transpose() {
+        Ok(metadata) => metadata,
+        Err(e) => return api_response(500, json!({"error": format!("Failed to parse artifact metadata: {}", e)})),
+    };
+
+    let producer = metadata.as_ref().and_then(|m| m.producer.as_ref());
+    let producer_node = match producer {
+        Some(producer) if !config.graph_table_name.is_empty() => {
+            let graph = DependencyGraph::new(dynamo.clone(), config.graph_table_name.clone());
+            let details = match graph.is_compute_node_live(producer.id.clone()).await {
+                // Missing or soft-deleted producers are reported as gone
+                Ok(false) => None,
+                Ok(true) => Some(graph.get_compute_node_details(producer.id.clone()).await),
+                Err(e) => Some(Err(e)),
+            };
+            match details {
+                None => Value::Null,
+                Some(Ok((node_type, inputs, input_generations))) => json!({
+                    "id": producer.id,
+                    "type": node_type,
+                    "inputs": inputs,
+                    "input_generations": input_generations,
+                }),
+                Some(Err(e)) => {
+                    println!("DynamoDB error: {}", e);
+                    return api_response(500, json!({"error": "Database lookup failed"}));
                 }
-            },
-            None => return Ok(api_response(404, json!({"error": format!("Pointer '{}' not found in document", ptr)}))),
+            }
         }
-    } else {
-        content_str
+        _ => Value::Null,
     };
 
-    // 4. Render
-    let result = if format == "text" {
-        // Decode HTML entities (e.g. &lt; -> <) before parsing tags
-        let decoded = html_escape::decode_html_entities(&content_to_render);
-        // Use html2text to strip tags and format
-        html2text::from_read(decoded.as_bytes(), 80)
-    } else {
-        content_to_render
+    api_response(200, json!({
+        "id": id,
+        "content_type": head.content_type(),
+        "size": head.content_length(),
+        "etag": head.e_tag(),
+        "metadata": metadata,
+        "producer_node": producer_node,
+    }))
+}
+
+/// Renders a Tera template (the `template` param, or the artifact named by `template_id`)
+/// with a context built from the comma-separated artifact `ids`. Each artifact is exposed
+/// in `artifacts` in request order, parsed as JSON where possible and as a string otherwise.
+async fn render_template(query_params: &serde_json::Map<String, Value>, s3: &S3Client, dynamo: &DynamoClient, cache: &ArtifactCache, config: &RendererConfig) -> Value {
+    let template = match (query_params.get("template").and_then(|v| v.as_str()), query_params.get("template_id").and_then(|v| v.as_str())) {
+        (Some(template), _) => template.to_string(),
+        (None, Some(template_id)) => match fetch_artifact(template_id, s3, dynamo, cache, config).await {
+            Ok(template) => template,
+            Err(response) => return response,
+        },
+        (None, None) => return api_response(400, json!({"error": "Missing 'template' or 'template_id' parameter"})),
     };
 
-    Ok(api_response(200, json!({ "content": result })))
+    let ids: Vec<&str> = query_params.get("ids")
+        .and_then(|v| v.as_str())
+        .map(|ids| ids.split(',').map(str::trim).filter(|id| !id.is_empty()).collect())
+        .unwrap_or_default();
+
+    let mut artifacts = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let content = match fetch_artifact(id, s3, dynamo, cache, config).await {
+            Ok(content) => content,
+            Err(response) => return response,
+        };
+        artifacts.push(serde_json::from_str(&content).unwrap_or(Value::String(content)));
+    }
+
+    // The result is typically shown as HTML, so artifact values are escaped
+    match template::render(&template, &json!({ "ids": ids, "artifacts": artifacts }), true) {
+        Ok(rendered) => api_response(200, json!({ "content": rendered })),
+        Err(e) => api_response(400, json!({"error": format!("Failed to render template: {}", e)})),
+    }
+}
+
+/// Whether the request's `Accept-Encoding` header allows gzip (header names are case-insensitive).
+fn accepts_gzip(payload: &Value) -> bool {
+    let Some(headers) = payload.get("headers").and_then(|h| h.as_object()) else {
+        return false;
+    };
+    headers.iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("accept-encoding"))
+        .filter_map(|(_, value)| value.as_str())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let refused = parts.any(|param| matches!(param.strip_prefix("q="), Some(q) if q.parse::<f32>().map(|q| q == 0.0).unwrap_or(false)));
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+}
+
+/// Gzips the response body. API Gateway needs binary bodies base64-encoded.
+fn gzip_response(mut response: Value) -> Result<Value> {
+    let body = response["body"].as_str().unwrap_or_default().to_string();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body.as_bytes())?;
+    let compressed = encoder.finish()?;
+
+    response["body"] = Value::String(base64::engine::general_purpose::STANDARD.encode(compressed));
+    response["isBase64Encoded"] = Value::Bool(true);
+    response["headers"]["Content-Encoding"] = Value::String("gzip".to_string());
+    response["headers"]["Vary"] = Value::String("Accept-Encoding".to_string());
+    Ok(response)
 }
 
 fn api_response(status: u16, body: Value) -> Value {
@@ -160,3 +449,275 @@ fn api_response(status: u16, body: Value) -> Value {
         "body": body.to_string()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_BUCKET: &str = "test-renderer-bucket";
+
+    fn test_config() -> RendererConfig {
+        RendererConfig {
+            external_inputs: vec![json!({ "name": "dash", "bucket": TEST_BUCKET })],
+            ..Default::default()
+        }
+    }
+
+    // Requires Moto on localhost:5000 (scripts/run_s3_local.sh)
+    async fn local_clients() -> (S3Client, DynamoClient) {
+        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        let s3_config = aws_sdk_s3::config::Builder::from(&config)
+            .endpoint_url("http://localhost:5000")
+            .force_path_style(true)
+            .build();
+        let s3 = S3Client::from_conf(s3_config);
+        let _ = s3.create_bucket().bucket(TEST_BUCKET).send().await;
+        let dynamo = DynamoClient::from_conf(aws_sdk_dynamodb::config::Builder::from(&config)
+            .endpoint_url("http://localhost:5000")
+            .build());
+        (s3, dynamo)
+    }
+
+    async fn put(s3: &S3Client, key: &str, body: &str) {
+        s3.put_object()
+            .bucket(TEST_BUCKET)
+            .key(key)
+            .body(body.as_bytes().to_vec().into())
+            .send()
+            .await
+            .unwrap();
+    }
+
+    fn request(params: Value) -> LambdaEvent<Value> {
+        LambdaEvent::new(json!({ "queryStringParameters": params }), lambda_runtime::Context::default())
+    }
+
+    #[tokio::test]
+    async fn test_template_format_composes_artifacts() {
+        let (s3, dynamo) = local_clients().await;
+        put(&s3, "external/dash/summary.json", r#"{"title": "Nightly", "passed": 12}"#).await;
+        put(&s3, "external/dash/notes.txt", "all green").await;
+
+        let event = request(json!({
+            "format": "template",
+            "template": "<h1>{{ artifacts.0.title }}</h1><p>{{ artifacts.0.passed }} passed: {{ artifacts.1 }}</p>",
+            "ids": "external/dash/summary.json,external/dash/notes.txt",
+        }));
+        let response = handler(event, s3.clone(), dynamo.clone(), &ArtifactCache::new(0, 0), &test_config()).await.unwrap();
+
+        assert_eq!(response["statusCode"], 200);
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["content"], "<h1>Nightly</h1><p>12 passed: all green</p>");
+
+        // Artifact content is escaped, so it can't inject markup into the page
+        put(&s3, "external/dash/comment.txt", "<script>alert(1)</script>").await;
+        let event = request(json!({ "format": "template", "template": "<p>{{ artifacts.0 }}</p>", "ids": "external/dash/comment.txt" }));
+        let response = handler(event, s3, dynamo, &ArtifactCache::new(0, 0), &test_config()).await.unwrap();
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["content"], "<p>&lt;script&gt;alert(1)&lt;&#x2F;script&gt;</p>");
+    }
+
+    #[tokio::test]
+    async fn test_template_format_rejects_get_env() {
+        let (s3, dynamo) = local_clients().await;
+        let event = request(json!({ "format": "template", "template": r#"{{ get_env(name="PATH") }}"# }));
+        let response = handler(event, s3, dynamo, &ArtifactCache::new(0, 0), &test_config()).await.unwrap();
+
+        assert_eq!(response["statusCode"], 400);
+        let body = response["body"].as_str().unwrap();
+        assert!(body.contains("Failed to render template"), "unexpected body: {}", body);
+        assert!(!body.contains(&env::var("PATH").unwrap()), "environment leaked: {}", body);
+    }
+
+    #[tokio::test]
+    async fn test_gzip_negotiation() {
+        let (s3, dynamo) = local_clients().await;
+        let html = format!("<p>{}</p>", "compress me please ".repeat(50));
+        put(&s3, "external/dash/large.html", &html).await;
+
+        let mut event = request(json!({ "id": "external/dash/large.html", "format": "raw" }));
+        event.payload["headers"] = json!({ "accept-encoding": "br;q=1.0, gzip;q=0.8" });
+        let cache = ArtifactCache::new(0, 0);
+        let response = handler(event, s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["isBase64Encoded"], true);
+        assert_eq!(response["headers"]["Content-Encoding"], "gzip");
+        let compressed = base64::engine::general_purpose::STANDARD.decode(response["body"].as_str().unwrap()).unwrap();
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&compressed[..]), &mut decoded).unwrap();
+        let body: Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(body["content"], html);
+
+        // Without gzip in Accept-Encoding the body stays plain
+        let mut event = request(json!({ "id": "external/dash/large.html", "format": "raw" }));
+        event.payload["headers"] = json!({ "Accept-Encoding": "gzip;q=0, identity" });
+        let response = handler(event, s3, dynamo, &cache, &test_config()).await.unwrap();
+        assert!(response.get("isBase64Encoded").is_none());
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["content"], html);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let (s3, dynamo) = local_clients().await;
+        let content = "x".repeat(DEFAULT_MAX_BODY_BYTES + 1);
+        put(&s3, "external/dash/oversized.txt", &content).await;
+        let cache = ArtifactCache::new(0, 0);
+
+        let event = request(json!({ "id": "external/dash/oversized.txt", "format": "raw" }));
+        let response = handler(event, s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+        assert_eq!(response["statusCode"], 413);
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("presigned URL"), "unexpected body: {}", body);
+
+        // Gzip brings it under the limit
+        let mut event = request(json!({ "id": "external/dash/oversized.txt", "format": "raw" }));
+        event.payload["headers"] = json!({ "Accept-Encoding": "gzip" });
+        let response = handler(event, s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+        assert_eq!(response["statusCode"], 200);
+
+        let event = request(json!({ "id": "external/dash/oversized.txt", "format": "raw", "truncate": "true" }));
+        let response = handler(event, s3, dynamo, &cache, &test_config()).await.unwrap();
+        assert_eq!(response["statusCode"], 200);
+        let raw_body = response["body"].as_str().unwrap();
+        assert!(raw_body.len() <= DEFAULT_MAX_BODY_BYTES);
+        let body: Value = serde_json::from_str(raw_body).unwrap();
+        assert_eq!(body["truncated"], true);
+        assert!(content.starts_with(body["content"].as_str().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_mode() {
+        let (s3, dynamo) = local_clients().await;
+        put(&s3, "external/dash/build/report.json", r#"{"passed": 12}"#).await;
+        put(&s3, "external/dash/build/artifact.yaml", "id: report\ngeneration: g7\nproducer:\n  id: node-1\n  node_type: JsonSelect\n  inputs: [raw]\n").await;
+        let cache = ArtifactCache::new(0, 0);
+
+        let event = request(json!({ "id": "external/dash/build/report.json", "mode": "metadata" }));
+        let response = handler(event, s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+        assert_eq!(response["statusCode"], 200);
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["size"], 14);
+        assert!(body["etag"].is_string());
+        assert_eq!(body["metadata"]["id"], "report");
+        assert_eq!(body["metadata"]["generation"], "g7");
+        assert_eq!(body["metadata"]["producer"], json!({ "id": "node-1", "node_type": "JsonSelect", "inputs": ["raw"] }));
+        assert!(body.get("content").is_none());
+
+        // Missing artifacts 404 the same way in both modes
+        for params in [
+            json!({ "id": "external/dash/build/missing.json", "mode": "metadata" }),
+            json!({ "id": "external/dash/build/missing.json", "format": "raw" }),
+        ] {
+            let response = handler(request(params), s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+            assert_eq!(response["statusCode"], 404);
+            let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+            assert_eq!(body["error"], "Artifact not found");
+        }
+        let response = handler(request(json!({ "id": "external/nowhere/x.json", "mode": "metadata" })), s3, dynamo, &cache, &test_config()).await.unwrap();
+        assert_eq!(response["statusCode"], 404);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_mode_producer_node() {
+        let (s3, dynamo) = local_clients().await;
+        let table = format!("test-renderer-graph-{}", uuid::Uuid::new_v4());
+        curio_db::bootstrap::create_graph_table(&dynamo, &table).await.unwrap();
+        let config = RendererConfig { graph_table_name: table.clone(), ..test_config() };
+        let graph = DependencyGraph::new(dynamo.clone(), table);
+        graph.register_artifact("renderer_raw".to_string(), true, None).await.unwrap();
+        graph.create_compute_node("renderer_node".to_string(), vec!["renderer_raw".to_string()], "JsonSelect".to_string()).await.unwrap();
+
+        put(&s3, "external/dash/graph/report.json", "{}").await;
+        put(&s3, "external/dash/graph/artifact.yaml", "id: report\nproducer:\n  id: renderer_node\n  node_type: JsonSelect\n  inputs: [renderer_raw]\n").await;
+        let cache = ArtifactCache::new(0, 0);
+        let producer_node = || async {
+            let event = request(json!({ "id": "external/dash/graph/report.json", "mode": "metadata" }));
+            let response = handler(event, s3.clone(), dynamo.clone(), &cache, &config).await.unwrap();
+            assert_eq!(response["statusCode"], 200);
+            let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+            body["producer_node"].clone()
+        };
+
+        let node = producer_node().await;
+        assert_eq!(node["id"], "renderer_node");
+        assert_eq!(node["type"], "JsonSelect");
+        assert_eq!(node["inputs"], json!(["renderer_raw"]));
+
+        // A soft-deleted producer is reported as gone, like a missing one
+        graph.remove_compute_node("renderer_node".to_string(), false).await.unwrap();
+        assert_eq!(producer_node().await, Value::Null);
+    }
+
+    #[test]
+    fn test_truncate_content_respects_char_boundaries() {
+        let response = api_response(200, json!({ "content": "é\"".repeat(100) }));
+        let truncated = truncate_content(&response, 120).unwrap();
+        let raw_body = truncated["body"].as_str().unwrap();
+        assert!(raw_body.len() <= 120);
+        let body: Value = serde_json::from_str(raw_body).unwrap();
+        assert!(!body["content"].as_str().unwrap().is_empty());
+
+        assert!(truncate_content(&api_response(404, json!({ "error": "missing" })), 10).is_none());
+    }
+
+    /// A stand-in S3 endpoint serving one object whose body and ETag the test can change.
+    /// Returns the endpoint URL and the recorded request lines ("GET /bucket/key ...").
+    async fn mock_s3(object: Arc<std::sync::Mutex<(String, String)>>) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut chunk).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                    }
+                }
+                let request_line = String::from_utf8_lossy(&buf).lines().next().unwrap_or_default().to_string();
+                let (body, etag) = object.lock().unwrap().clone();
+                let head = format!("HTTP/1.1 200 OK\r\nETag: \"{}\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", etag, body.len());
+                let mut response = head.into_bytes();
+                if !request_line.starts_with("HEAD") {
+                    response.extend_from_slice(body.as_bytes());
+                }
+                recorded.lock().unwrap().push(request_line);
+                let _ = socket.write_all(&response).await;
+            }
+        });
+        (endpoint, requests)
+    }
+
+    #[tokio::test]
+    async fn test_artifact_cache_skips_unchanged_fetch() {
+        let (_, dynamo) = local_clients().await;
+        let object = Arc::new(std::sync::Mutex::new(("cached body".to_string(), "v1".to_string())));
+        let (endpoint, requests) = mock_s3(object.clone()).await;
+        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        let s3 = S3Client::from_conf(aws_sdk_s3::config::Builder::from(&config).endpoint_url(endpoint).force_path_style(true).build());
+        let cache = ArtifactCache::new(16, 1024);
+
+        let fetch = || async {
+            let event = request(json!({ "id": "external/dash/cached.txt", "format": "raw" }));
+            let response = handler(event, s3.clone(), dynamo.clone(), &cache, &test_config()).await.unwrap();
+            let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+            body["content"].as_str().unwrap().to_string()
+        };
+        let methods = || requests.lock().unwrap().iter().map(|r| r.split(' ').next().unwrap().to_string()).collect::<Vec<_>>();
+
+        assert_eq!(fetch().await, "cached body");
+        assert_eq!(fetch().await, "cached body");
+        assert_eq!(methods(), ["GET", "HEAD"], "second request should only revalidate");
+
+        // A new ETag invalidates the cached copy
+        *object.lock().unwrap() = ("updated body".to_string(), "v2".to_string());
+        assert_eq!(fetch().await, "updated body");
+        assert_eq!(methods(), ["GET", "HEAD", "HEAD", "GET"]);
+    }
+}
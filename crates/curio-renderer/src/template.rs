@@ -0,0 +1,43 @@
+//! # Template Rendering
+//!
+//! Renders caller-supplied Tera templates for `format=template`. Templates come straight
+//! from the query string, so Tera's built-in `get_env` is replaced with one that always
+//! fails; otherwise any caller could read the Lambda's credentials out of its environment.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Renders `template` against `context`, which must be a JSON object.
+///
+/// With `autoescape`, interpolated values are HTML-escaped (templates can opt out per
+/// value with `| safe`).
+pub fn render(template: &str, context: &Value, autoescape: bool) -> Result<String, tera::Error> {
+    let ctx = tera::Context::from_value(context.clone())?;
+    let mut tera = tera::Tera::default();
+    tera.register_function("get_env", |_: &HashMap<String, Value>| -> tera::Result<Value> {
+        Err(tera::Error::msg("get_env is not available in templates"))
+    });
+    // `render_str` renders under Tera's one-off name, so escaping is keyed to that
+    tera.autoescape_on(if autoescape { vec!["__tera_one_off"] } else { vec![] });
+    tera.render_str(template, &ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_escapes_only_with_autoescape() {
+        let ctx = json!({ "name": "<b>Ada</b>" });
+        assert_eq!(render("Hi {{ name }}", &ctx, true).unwrap(), "Hi &lt;b&gt;Ada&lt;&#x2F;b&gt;");
+        assert_eq!(render("Hi {{ name | safe }}", &ctx, true).unwrap(), "Hi <b>Ada</b>");
+        assert_eq!(render("Hi {{ name }}", &ctx, false).unwrap(), "Hi <b>Ada</b>");
+    }
+
+    #[test]
+    fn test_render_rejects_non_object_context() {
+        assert!(render("{{ x }}", &json!([1, 2]), false).is_err());
+        assert!(render("{{ missing }}", &json!({}), false).is_err());
+    }
+}
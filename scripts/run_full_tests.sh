@@ -45,6 +45,7 @@ sleep 5
 # Set Environment Variables
 export DYNAMODB_ENDPOINT="http://localhost:8000"
 export AWS_ENDPOINT_URL="http://localhost:5000"
+export S3_ENDPOINT="http://localhost:5000"
 export AWS_ACCESS_KEY_ID="test"
 export AWS_SECRET_ACCESS_KEY="test"
 export AWS_REGION="us-east-1"